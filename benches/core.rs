@@ -0,0 +1,69 @@
+#![allow(clippy::needless_return, clippy::unused_unit)]
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use krhip8::Chip8::Chip8;
+use krhip8::Random::Random;
+
+const CYCLES: u64 = 100_000;
+
+const ROMS: [(&str, &[u8]); 7] = [
+    ("3-corax+", include_bytes!("../3-corax+.ch8")),
+    ("4-flags", include_bytes!("../4-flags.ch8")),
+    ("5-quirks", include_bytes!("../5-quirks.ch8")),
+    ("6-keypad", include_bytes!("../6-keypad.ch8")),
+    ("test_opcode", include_bytes!("../test_opcode.ch8")),
+    ("Particle Demo", include_bytes!("../Particle Demo.ch8")),
+    ("Trip8 Demo", include_bytes!("../Trip8 Demo.ch8")),
+];
+
+/// Draws a 15 row sprite, moves it and loops: four instructions per draw.
+const SPRITES: [u8; 25] = [
+    0xA2, 0x0A, // LD I, 0x20A
+    0xD0, 0x1F, // DRW V0, V1, 15
+    0x70, 0x03, // ADD V0, 0x03
+    0x71, 0x01, // ADD V1, 0x01
+    0x12, 0x02, // JP 0x202
+    0xFF, 0x81, 0xBD, 0xA5, 0xA5, 0xBD, 0x81, 0xFF, 0x18, 0x3C, 0x7E, 0xFF, 0x7E, 0x3C, 0x18,
+];
+
+fn machine(rom: &[u8]) -> Chip8 {
+    let mut chip8 = Chip8::headless();
+    chip8.rng = Random::new(0);
+    chip8.load_rom(rom).unwrap();
+    return chip8;
+}
+
+/// Runs `chip8` for `CYCLES` instructions in frames of ten. Test ROMs stop
+/// at a final jump to themselves, which still counts.
+fn run(chip8: &mut Chip8) -> () {
+    for _ in 0..CYCLES / 10 {
+        chip8.run_frame().unwrap();
+    }
+}
+
+/// Instructions per second of the core on every bundled ROM.
+fn roms(c: &mut Criterion) {
+    let mut group = c.benchmark_group("core");
+    group.throughput(Throughput::Elements(CYCLES));
+    for (name, rom) in ROMS {
+        group.bench_function(name, |b| {
+            let mut chip8 = machine(rom);
+            b.iter(|| run(&mut chip8));
+        });
+    }
+    group.finish();
+}
+
+/// Sprites drawn per second, each one 8x15 and mostly on screen.
+fn draw_sprite(c: &mut Criterion) {
+    let mut group = c.benchmark_group("draw_sprite");
+    group.throughput(Throughput::Elements(CYCLES / 4));
+    group.bench_function("8x15", |b| {
+        let mut chip8 = machine(&SPRITES);
+        b.iter(|| run(&mut chip8));
+    });
+    group.finish();
+}
+
+criterion_group!(benches, roms, draw_sprite);
+criterion_main!(benches);
@@ -0,0 +1,39 @@
+#![allow(clippy::needless_return, clippy::unused_unit)]
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use krhip8::Chip8::Chip8;
+use krhip8::Random::Random;
+
+const CYCLES: u64 = 100_000;
+
+fn machine(rom: &[u8], cached: bool) -> Chip8 {
+    let mut chip8 = Chip8::headless();
+    chip8.rng = Random::new(0);
+    chip8.set_block_cache(cached);
+    chip8.load_rom(rom).unwrap();
+    return chip8;
+}
+
+/// Instructions per second of the plain interpreter against the block
+/// cache, on a demo that keeps running forever.
+fn interpreter(c: &mut Criterion) {
+    let rom = include_bytes!("../Trip8 Demo.ch8");
+    let mut group = c.benchmark_group("interpreter");
+    group.throughput(Throughput::Elements(CYCLES));
+
+    for (name, cached) in [("step", false), ("block cache", true)] {
+        group.bench_function(name, |b| {
+            let mut chip8 = machine(rom, cached);
+            b.iter(|| {
+                for _ in 0..CYCLES / 10 {
+                    chip8.begin_frame();
+                    chip8.run_cycles(10).unwrap();
+                }
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, interpreter);
+criterion_main!(benches);
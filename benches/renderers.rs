@@ -0,0 +1,59 @@
+#![allow(clippy::needless_return, clippy::unused_unit)]
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use krhip8::Chip8::Chip8;
+use krhip8::Random::Random;
+use krhip8::Recorder::{RecordFormat, Recorder};
+use krhip8::Screenshot::{Frame, Screenshot};
+use std::hint::black_box;
+
+/// A machine showing a busy screen, the Trip8 demo a few seconds in.
+fn demo() -> Chip8 {
+    let mut chip8 = Chip8::headless();
+    chip8.rng = Random::new(0);
+    chip8.load_rom(include_bytes!("../Trip8 Demo.ch8")).unwrap();
+    for _ in 0..300 {
+        chip8.run_frame().unwrap();
+    }
+    return chip8;
+}
+
+/// Frames per second of every way krhip8 puts the screen out.
+fn renderers(c: &mut Criterion) {
+    let chip8 = demo();
+    let frame = Frame::from_chip8(&chip8);
+    // the GIF recorder merges identical frames, alternate so every one is encoded
+    let inverted = Frame {
+        pixels: frame.pixels.iter().map(|p| 1 - p).collect(),
+        ..frame.clone()
+    };
+    let screenshot = Screenshot {
+        scale: 4,
+        ..Default::default()
+    };
+
+    let mut group = c.benchmark_group("renderers");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("terminal", |b| b.iter(|| black_box(chip8.terminal_frame())));
+    group.bench_function("frame", |b| b.iter(|| black_box(Frame::from_chip8(&chip8))));
+    group.bench_function("png", |b| {
+        b.iter(|| screenshot.write_png(&frame, &mut Vec::new()).unwrap());
+    });
+    group.bench_function("pbm", |b| {
+        b.iter(|| screenshot.write_pbm(&frame, &mut Vec::new()).unwrap());
+    });
+    for (name, format) in [("gif", RecordFormat::Gif), ("y4m", RecordFormat::Y4m), ("rgb", RecordFormat::Rgb)] {
+        group.bench_function(name, |b| {
+            let mut recorder = Recorder::new(Box::new(std::io::sink()), format, screenshot.clone());
+            let mut odd = false;
+            b.iter(|| {
+                odd = !odd;
+                recorder.capture(if odd { &frame } else { &inverted }).unwrap();
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, renderers);
+criterion_main!(benches);
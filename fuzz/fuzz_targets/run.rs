@@ -0,0 +1,50 @@
+#![no_main]
+#![allow(clippy::needless_return, clippy::unused_unit)]
+
+use krhip8::Chip8::{Chip8, Chip8Errors, Config};
+use krhip8::Quirks::Quirks;
+use krhip8::Rom::Platform;
+use libfuzzer_sys::fuzz_target;
+
+const PLATFORMS: [Platform; 4] = [Platform::Chip8, Platform::Schip, Platform::XoChip, Platform::Eti660];
+
+/// Instructions run per input.
+const STEPS: usize = 10000;
+
+// The first byte picks the platform (low two bits) and the quirks (the
+// others), the second the keys held down, the rest is the ROM. Errors are
+// fine, panics are what libFuzzer is looking for.
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 3 {
+        return;
+    }
+    let platform = PLATFORMS[(data[0] & 3) as usize];
+    let mut quirks = Quirks::default();
+    for (bit, name) in Quirks::NAMES.iter().enumerate() {
+        quirks.set(name, data[0] >> (bit + 1) & 1 == 1);
+    }
+    let rom = &data[2..];
+    let mut chip8 = Chip8::new(Config {
+        platform,
+        quirks,
+        seed: Some(0),
+        ..Default::default()
+    });
+    if chip8.load_rom(&rom[..rom.len().min(platform.max_rom_size())]).is_err() {
+        return;
+    }
+    for key in 0..8 {
+        chip8.set_key(key, data[1] >> key & 1 == 1);
+    }
+    for step in 0..STEPS {
+        if step % 10 == 0 {
+            chip8.begin_frame();
+        }
+        if let Err(err) = chip8.step() {
+            let _ = err.to_string();
+            if matches!(err, Chip8Errors::OutOfBounds { .. }) {
+                break;
+            }
+        }
+    }
+});
@@ -0,0 +1,94 @@
+use crate::Cfg::Flow;
+use crate::Op::Op;
+use crate::Ram::Ram;
+use std::rc::Rc;
+
+/// Longest run of instructions decoded into one block.
+const MAX_BLOCK: usize = 64;
+
+/// Straight line code decoded once: the opcodes from `start` on with their
+/// decoded form, up to and including the first instruction that can go
+/// anywhere but the next one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedBlock {
+    pub start: u16,
+    pub ops: Vec<(u16, Op)>,
+}
+
+/// Decoded blocks by start address, for running ROMs without fetching and
+/// decoding every instruction again. Writing to a byte any block was
+/// decoded from throws the whole cache away, which keeps self modifying
+/// code correct and costs little since most ROMs never do it.
+pub struct BlockCache {
+    blocks: Vec<Option<Rc<CachedBlock>>>,
+    /// Bytes some cached block was decoded from.
+    code: Vec<bool>,
+    /// Bumped whenever the cache is cleared, so a running block can tell it
+    /// went stale.
+    generation: u64,
+    pub hits: u64,
+    pub misses: u64,
+    pub invalidations: u64,
+}
+
+impl BlockCache {
+    pub fn new(memory_size: usize) -> Self {
+        return BlockCache {
+            blocks: vec![None; memory_size],
+            code: vec![false; memory_size],
+            generation: 0,
+            hits: 0,
+            misses: 0,
+            invalidations: 0,
+        };
+    }
+
+    pub fn generation(&self) -> u64 {
+        return self.generation;
+    }
+
+    /// The block starting at `pc`, decoded from `ram` the first time. Empty
+    /// when not even one instruction fits before the end of memory.
+    pub fn get(&mut self, ram: &Ram, pc: u16) -> Rc<CachedBlock> {
+        if pc as usize >= self.blocks.len() {
+            return Rc::new(CachedBlock { start: pc, ops: Vec::new() });
+        }
+        if let Some(block) = self.blocks[pc as usize].as_ref() {
+            self.hits += 1;
+            return Rc::clone(block);
+        }
+        self.misses += 1;
+
+        let mut ops = Vec::new();
+        let mut address = pc as usize;
+        while address + 1 < ram.size() && ops.len() < MAX_BLOCK {
+            let opcode = (ram[address] as u16) << 8 | ram[address + 1] as u16;
+            let op = Op::decode(opcode);
+            ops.push((opcode, op));
+            self.code[address] = true;
+            self.code[address + 1] = true;
+            if op == Op::Undefined || Flow::of(opcode) != Flow::Next {
+                break;
+            }
+            address += 2;
+        }
+
+        let block = Rc::new(CachedBlock { start: pc, ops });
+        self.blocks[pc as usize] = Some(Rc::clone(&block));
+        return block;
+    }
+
+    /// Called for every write to memory, drops the cache if the byte is code.
+    pub fn write(&mut self, address: u16) -> () {
+        if self.code[address as usize] {
+            self.invalidations += 1;
+            self.clear();
+        }
+    }
+
+    pub fn clear(&mut self) -> () {
+        self.blocks.iter_mut().for_each(|block| *block = None);
+        self.code.iter_mut().for_each(|byte| *byte = false);
+        self.generation += 1;
+    }
+}
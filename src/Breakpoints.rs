@@ -0,0 +1,234 @@
+use crate::Chip8::{Chip8, Chip8Errors, MemoryWarning};
+use crate::Expr::{BinaryOp, Expr, ExprErrors, Var};
+use std::fmt;
+
+/// What makes a breakpoint stop the machine.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Trigger {
+    /// Before running the instruction at `pc`, when the expression holds.
+    Condition(Expr),
+    /// After running an instruction whose opcode matches `value` in the
+    /// bits of `mask`, when the condition (if any) holds afterwards.
+    Opcode {
+        mask: u16,
+        value: u16,
+        condition: Option<Expr>,
+    },
+    /// When an instruction fails, with any error or the one named.
+    Error(Option<&'static str>),
+    /// After an instruction gave a memory warning, any or the one named.
+    Warning(Option<&'static str>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Breakpoint {
+    /// As typed, for showing it back.
+    pub text: String,
+    pub trigger: Trigger,
+    /// Stops from the `hit`th time the trigger matches on, 1 stops every
+    /// time.
+    pub hit: u64,
+    /// Times the trigger matched so far.
+    pub hits: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BreakpointErrors {
+    Parse { text: String, reason: &'static str },
+    Expr(ExprErrors),
+}
+
+impl fmt::Display for BreakpointErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            BreakpointErrors::Parse { text, reason } => write!(f, "`{}`: {}", text, reason),
+            BreakpointErrors::Expr(err) => write!(f, "{}", err),
+        };
+    }
+}
+
+/// `DXYN`-style patterns: hex digits must match, anything else matches any
+/// nibble. Returns the mask and value.
+fn parse_pattern(pattern: &str) -> Option<(u16, u16)> {
+    if pattern.chars().count() != 4 {
+        return None;
+    }
+    let (mut mask, mut value) = (0, 0);
+    for c in pattern.chars() {
+        mask <<= 4;
+        value <<= 4;
+        if let Some(digit) = c.to_digit(16) {
+            mask |= 0xF;
+            value |= digit as u16;
+        }
+    }
+    return Some((mask, value));
+}
+
+impl Breakpoint {
+    /// Parses one of
+    ///
+    /// - `EXPR`: stop before an instruction when `EXPR` holds. A plain
+    ///   number is short for `pc == NUMBER`.
+    /// - `op PATTERN [if EXPR]`: stop after an instruction matching
+    ///   `PATTERN` (`DXYN`, `00EE`, `8XY4`...) ran, if `EXPR` holds then.
+    /// - `error [NAME]`: stop instead of crashing when an instruction fails,
+    ///   `NAME` is one of `Chip8Errors::NAMES`.
+    /// - `warning [NAME]`: stop after an instruction gave a memory warning,
+    ///   `NAME` is one of `MemoryWarning::NAMES`.
+    ///
+    /// each optionally followed by `hit N` to let the first N - 1 matches
+    /// pass.
+    pub fn parse(text: &str) -> Result<Self, BreakpointErrors> {
+        let fail = |reason| BreakpointErrors::Parse {
+            text: text.to_string(),
+            reason,
+        };
+        let mut words: Vec<&str> = text.split_whitespace().collect();
+
+        let mut hit = 1;
+        if words.len() >= 2 && words[words.len() - 2] == "hit" {
+            hit = words[words.len() - 1].parse::<u64>().map_err(|_| fail("hit takes a count"))?;
+            if hit == 0 {
+                return Err(fail("hit counts start at 1"));
+            }
+            words.truncate(words.len() - 2);
+        }
+
+        let trigger = match words.as_slice() {
+            [] => return Err(fail("empty breakpoint")),
+            ["error"] => Trigger::Error(None),
+            ["error", name] => {
+                let name = Chip8Errors::NAMES.iter().find(|known| *known == name);
+                Trigger::Error(Some(name.ok_or(fail("unknown error"))?))
+            }
+            ["warning"] => Trigger::Warning(None),
+            ["warning", name] => {
+                let name = MemoryWarning::NAMES.iter().find(|known| *known == name);
+                Trigger::Warning(Some(name.ok_or(fail("unknown warning"))?))
+            }
+            ["op", pattern, rest @ ..] => {
+                let (mask, value) = parse_pattern(pattern).ok_or(fail("opcode patterns have four characters"))?;
+                let condition = match rest {
+                    [] => None,
+                    ["if", expr @ ..] if !expr.is_empty() => {
+                        Some(Expr::parse(&expr.join(" ")).map_err(BreakpointErrors::Expr)?)
+                    }
+                    _ => return Err(fail("expected `if CONDITION` after the pattern")),
+                };
+                Trigger::Opcode {
+                    mask,
+                    value,
+                    condition,
+                }
+            }
+            expr => match Expr::parse(&expr.join(" ")).map_err(BreakpointErrors::Expr)? {
+                Expr::Number(address) => Trigger::Condition(Expr::Binary(
+                    BinaryOp::Eq,
+                    Box::new(Expr::Var(Var::Pc)),
+                    Box::new(Expr::Number(address)),
+                )),
+                expr => Trigger::Condition(expr),
+            },
+        };
+        return Ok(Breakpoint {
+            text: text.trim().to_string(),
+            trigger,
+            hit,
+            hits: 0,
+        });
+    }
+
+    /// Counts a match, returns whether to stop.
+    fn matched(&mut self) -> bool {
+        self.hits += 1;
+        return self.hits >= self.hit;
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "{} (hit {} times)", self.text, self.hits);
+    }
+}
+
+/// The breakpoints of a debugging session. Every check goes through all of
+/// them, so hit counts stay right when several match at once, and returns
+/// the first that stops.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Breakpoints {
+    list: Vec<Breakpoint>,
+}
+
+impl Breakpoints {
+    pub fn new() -> Self {
+        return Breakpoints { list: Vec::new() };
+    }
+
+    pub fn add(&mut self, text: &str) -> Result<(), BreakpointErrors> {
+        self.list.push(Breakpoint::parse(text)?);
+        return Ok(());
+    }
+
+    pub fn clear(&mut self) -> () {
+        self.list.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.list.is_empty();
+    }
+
+    pub fn len(&self) -> usize {
+        return self.list.len();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Breakpoint> {
+        return self.list.iter();
+    }
+
+    fn first_stop(&mut self, mut matches: impl FnMut(&Trigger) -> bool) -> Option<&Breakpoint> {
+        let mut stop = None;
+        for (i, bp) in self.list.iter_mut().enumerate() {
+            if matches(&bp.trigger) && bp.matched() && stop.is_none() {
+                stop = Some(i);
+            }
+        }
+        return stop.map(|i| &self.list[i]);
+    }
+
+    /// The breakpoint stopping before the instruction at `pc` runs.
+    pub fn before(&mut self, chip8: &Chip8) -> Option<&Breakpoint> {
+        return self.first_stop(|trigger| match trigger {
+            Trigger::Condition(expr) => expr.holds(chip8),
+            _ => false,
+        });
+    }
+
+    /// The breakpoint stopping after `opcode` ran.
+    pub fn after(&mut self, chip8: &Chip8, opcode: u16) -> Option<&Breakpoint> {
+        return self.first_stop(|trigger| match trigger {
+            Trigger::Opcode {
+                mask,
+                value,
+                condition,
+            } => opcode & mask == *value && condition.as_ref().is_none_or(|expr| expr.holds(chip8)),
+            _ => false,
+        });
+    }
+
+    /// The breakpoint stopping after `warning`.
+    pub fn warning(&mut self, warning: &MemoryWarning) -> Option<&Breakpoint> {
+        return self.first_stop(|trigger| match trigger {
+            Trigger::Warning(name) => name.is_none_or(|name| name == warning.name()),
+            _ => false,
+        });
+    }
+
+    /// The breakpoint catching `err`.
+    pub fn error(&mut self, err: &Chip8Errors) -> Option<&Breakpoint> {
+        return self.first_stop(|trigger| match trigger {
+            Trigger::Error(name) => name.is_none_or(|name| name == err.name()),
+            _ => false,
+        });
+    }
+}
@@ -0,0 +1,410 @@
+use crate::Helpers::*;
+use crate::Op::Op;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+/// Where execution can go after an instruction, as far as can be told
+/// without running it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Flow {
+    /// On to the next instruction.
+    Next,
+    /// `1NNN`.
+    Jump(u16),
+    /// `2NNN`, execution comes back to the next instruction.
+    Call(u16),
+    /// `00EE`.
+    Return,
+    /// `3XKK`, `4XKK`, `5XY0`, `9XY0`, `EX9E` and `EXA1`: the next
+    /// instruction or the one after it. krhip8 runs any `5XYN` and `9XYN`
+    /// as `5XY0` and `9XY0`.
+    Skip,
+    /// `BNNN`, a jump to NNN plus a register, the target is unknown.
+    Indirect(u16),
+    /// Machine code routines (`0NNN`), SCHIP's `00FD` exit and anything that
+    /// isn't an instruction krhip8 runs. Nothing after it is followed.
+    Stop,
+}
+
+impl Flow {
+    /// Where krhip8 goes after `opcode`, as `Op::decode` reads it. It
+    /// doesn't run the SCHIP and XO-CHIP instructions, so those stop like
+    /// anything undefined.
+    pub fn of(opcode: u16) -> Self {
+        return match Op::decode(opcode) {
+            Op::Ret => Flow::Return,
+            Op::Jp(nnn) => Flow::Jump(nnn),
+            Op::Call(nnn) => Flow::Call(nnn),
+            Op::SeByte(..) | Op::SneByte(..) | Op::SeReg(..) | Op::SneReg(..) | Op::Skp(_) | Op::Sknp(_) => {
+                Flow::Skip
+            }
+            Op::JpV0(nnn, _) => Flow::Indirect(nnn),
+            Op::Undefined => Flow::Stop,
+            _ => Flow::Next,
+        };
+    }
+
+    /// Where a SCHIP or XO-CHIP machine goes after `opcode`, which goes on
+    /// past their instructions. Platform detection walks ROMs this way.
+    pub fn of_extended(opcode: u16) -> Self {
+        return match bg_id(opcode) {
+            0x0 if opcode == 0x00EE => Flow::Return,
+            0x0 if opcode == 0x00E0
+                || opcode & 0xFFF0 == 0x00C0
+                || opcode & 0xFFF0 == 0x00D0
+                || (0x00FB..=0x00FF).contains(&opcode) && opcode != 0x00FD =>
+            {
+                Flow::Next
+            }
+            0x0 => Flow::Stop,
+            0x1 => Flow::Jump(nnn(opcode)),
+            0x2 => Flow::Call(nnn(opcode)),
+            0x3 | 0x4 => Flow::Skip,
+            0x5 | 0x9 if end_id(opcode) == 0 => Flow::Skip,
+            0x5 if end_id(opcode) == 0x2 || end_id(opcode) == 0x3 => Flow::Next,
+            0x5 | 0x9 => Flow::Stop,
+            0x8 if matches!(end_id(opcode), 0x0..=0x7 | 0xE) => Flow::Next,
+            0x8 => Flow::Stop,
+            0xB => Flow::Indirect(nnn(opcode)),
+            0xE if matches!(two_end_id(opcode), 0x9E | 0xA1) => Flow::Skip,
+            0xE => Flow::Stop,
+            0xF if opcode == 0xF000 || opcode == 0xF002 => Flow::Next,
+            0xF if matches!(
+                two_end_id(opcode),
+                0x01 | 0x07 | 0x0A | 0x15 | 0x18 | 0x1E | 0x29 | 0x30 | 0x33 | 0x3A | 0x55 | 0x65 | 0x75 | 0x85
+            ) =>
+            {
+                Flow::Next
+            }
+            0xF => Flow::Stop,
+            _ => Flow::Next,
+        };
+    }
+}
+
+/// Bytes the instruction takes, XO-CHIP's `F000 NNNN` is the only one with
+/// four.
+pub fn length(opcode: u16) -> u16 {
+    if opcode == 0xF000 {
+        return 4;
+    }
+    return 2;
+}
+
+/// A run of instructions only entered at the top and only left at the
+/// bottom.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub start: u16,
+    /// Address of the last instruction.
+    pub last: u16,
+    /// How the last instruction leaves the block.
+    pub flow: Flow,
+    /// Blocks execution continues in, not counting calls.
+    pub successors: Vec<u16>,
+}
+
+/// A subroutine: the entry of a `2NNN` (or the program itself) and the
+/// blocks reachable from it without following calls.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subroutine {
+    pub entry: u16,
+    pub blocks: Vec<u16>,
+}
+
+/// Control flow graph of a ROM, found by following jumps, calls and skips
+/// from the load address. Code only reached through `BNNN` or from
+/// machine code isn't found, those jumps are listed in `indirect`.
+///
+/// Code the ROM overwrites is only what the graph shows before it runs.
+/// Where a block sets `I` with `ANNN` and then stores into an instruction
+/// with `FX33` or `FX55`, the store is listed in `code_writes`; stores
+/// through an `I` set elsewhere aren't followed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cfg {
+    pub entry: u16,
+    /// Every reachable instruction by address.
+    pub instructions: BTreeMap<u16, u16>,
+    pub blocks: BTreeMap<u16, Block>,
+    /// The program first, then subroutines by address.
+    pub subroutines: Vec<Subroutine>,
+    /// Addresses of `BNNN` instructions.
+    pub indirect: Vec<u16>,
+    /// Stores into code: the address of the `FX33`/`FX55` and of the
+    /// instruction it overwrites.
+    pub code_writes: Vec<(u16, u16)>,
+}
+
+impl Cfg {
+    /// Analyses `rom` as loaded at `load_address`.
+    pub fn build(rom: &[u8], load_address: u16) -> Self {
+        return Cfg::build_with(rom, load_address, Flow::of);
+    }
+
+    /// `build`, with `flow` telling where each instruction goes.
+    pub fn build_with(rom: &[u8], load_address: u16, flow: fn(u16) -> Flow) -> Self {
+        let fetch = |address: u16| -> Option<u16> {
+            let offset = (address as usize).checked_sub(load_address as usize)?;
+            if offset + 1 >= rom.len() {
+                return None;
+            }
+            return Some((rom[offset] as u16) << 8 | rom[offset + 1] as u16);
+        };
+
+        let mut instructions = BTreeMap::new();
+        let mut leaders = BTreeSet::from([load_address]);
+        let mut calls = BTreeSet::new();
+        let mut indirect = Vec::new();
+        let mut todo = vec![load_address];
+
+        while let Some(address) = todo.pop() {
+            if instructions.contains_key(&address) {
+                continue;
+            }
+            let Some(opcode) = fetch(address) else {
+                continue;
+            };
+            instructions.insert(address, opcode);
+
+            let next = address.wrapping_add(length(opcode));
+            match flow(opcode) {
+                Flow::Next => todo.push(next),
+                Flow::Jump(target) => {
+                    leaders.insert(target);
+                    todo.push(target);
+                }
+                Flow::Call(target) => {
+                    calls.insert(target);
+                    leaders.extend([target, next]);
+                    todo.extend([target, next]);
+                }
+                Flow::Skip => {
+                    let skipped = fetch(next).map_or(next.wrapping_add(2), |op| next.wrapping_add(length(op)));
+                    leaders.extend([next, skipped]);
+                    todo.extend([next, skipped]);
+                }
+                Flow::Indirect(_) => indirect.push(address),
+                Flow::Return | Flow::Stop => {}
+            }
+        }
+        indirect.sort();
+
+        let mut blocks = BTreeMap::new();
+        for &start in leaders.iter() {
+            let Some(mut opcode) = instructions.get(&start).copied() else {
+                continue;
+            };
+            let mut last = start;
+            loop {
+                let next = last.wrapping_add(length(opcode));
+                let ends = flow(opcode) != Flow::Next;
+                match instructions.get(&next) {
+                    Some(op) if !ends && !leaders.contains(&next) => {
+                        last = next;
+                        opcode = *op;
+                    }
+                    _ => break,
+                }
+            }
+
+            let flow = flow(opcode);
+            let next = last.wrapping_add(length(opcode));
+            let successors = match flow {
+                Flow::Next | Flow::Call(_) => vec![next],
+                Flow::Jump(target) => vec![target],
+                Flow::Skip => {
+                    let skipped = instructions.get(&next).map_or(next.wrapping_add(2), |op| next.wrapping_add(length(*op)));
+                    vec![next, skipped]
+                }
+                Flow::Return | Flow::Indirect(_) | Flow::Stop => vec![],
+            };
+            let successors = successors.into_iter().filter(|s| instructions.contains_key(s)).collect();
+            blocks.insert(
+                start,
+                Block {
+                    start,
+                    last,
+                    flow,
+                    successors,
+                },
+            );
+        }
+
+        let mut cfg = Cfg {
+            entry: load_address,
+            instructions,
+            blocks,
+            subroutines: Vec::new(),
+            indirect,
+            code_writes: Vec::new(),
+        };
+        cfg.code_writes = cfg.find_code_writes();
+        let entries = [load_address].into_iter().chain(calls.into_iter().filter(|c| *c != load_address));
+        cfg.subroutines = entries.map(|entry| cfg.subroutine(entry)).collect();
+        return cfg;
+    }
+
+    fn subroutine(&self, entry: u16) -> Subroutine {
+        let mut seen = BTreeSet::new();
+        let mut todo = vec![entry];
+        while let Some(start) = todo.pop() {
+            if !self.blocks.contains_key(&start) || !seen.insert(start) {
+                continue;
+            }
+            todo.extend(self.blocks[&start].successors.iter());
+        }
+        return Subroutine {
+            entry,
+            blocks: seen.into_iter().collect(),
+        };
+    }
+
+    /// `FX33`/`FX55` storing into an instruction, with `I` from an `ANNN`
+    /// earlier in the same block.
+    fn find_code_writes(&self) -> Vec<(u16, u16)> {
+        let mut writes = Vec::new();
+        for block in self.blocks.values() {
+            let mut ir = None;
+            for (address, opcode) in self.block_instructions(block) {
+                let stored = match (bg_id(opcode), two_end_id(opcode)) {
+                    (0xA, _) => {
+                        ir = Some(nnn(opcode));
+                        continue;
+                    }
+                    (0xF, 0x33) => 3,
+                    (0xF, 0x55) => x(opcode) as u16 + 1,
+                    // `FX1E`, `FX29` and with some quirks `FX65` change `I`
+                    (0xF, 0x1E | 0x29 | 0x65) => {
+                        ir = None;
+                        continue;
+                    }
+                    _ => continue,
+                };
+                let Some(start) = ir else {
+                    continue;
+                };
+                let end = start.saturating_add(stored);
+                // an instruction starting up to 3 bytes before `I` can reach into it
+                for (&target, &op) in self.instructions.range(start.saturating_sub(3)..end) {
+                    if target.saturating_add(length(op)) > start {
+                        writes.push((address, target));
+                    }
+                }
+                if opcode & 0xF0FF == 0xF055 {
+                    // the memory quirks decide where it ends up
+                    ir = None;
+                }
+            }
+        }
+        return writes;
+    }
+
+    /// Whether an instruction starts at `address`.
+    pub fn is_code(&self, address: u16) -> bool {
+        return self.instructions.contains_key(&address);
+    }
+
+    /// Instructions of a block in order.
+    pub fn block_instructions(&self, block: &Block) -> impl Iterator<Item = (u16, u16)> + '_ {
+        return self.instructions.range(block.start..=block.last).map(|(a, op)| (*a, *op));
+    }
+
+    /// The blocks and subroutines as text, one disassembled instruction per
+    /// line.
+    pub fn listing(&self) -> String {
+        let mut out = String::new();
+        for subroutine in self.subroutines.iter() {
+            let name = if subroutine.entry == self.entry { "program" } else { "subroutine" };
+            writeln!(out, "{} {:#06x}", name, subroutine.entry).unwrap();
+            for start in subroutine.blocks.iter() {
+                let block = &self.blocks[start];
+                let successors: Vec<String> = block.successors.iter().map(|s| format!("{:#06x}", s)).collect();
+                writeln!(out, "  block {:#06x} -> [{}]", start, successors.join(", ")).unwrap();
+                for (address, opcode) in self.block_instructions(block) {
+                    writeln!(out, "    {:#06x}  {:04X}  {}", address, opcode, disassemble(opcode)).unwrap();
+                }
+            }
+        }
+        for address in self.indirect.iter() {
+            writeln!(out, "indirect jump at {:#06x}", address).unwrap();
+        }
+        for (address, target) in self.code_writes.iter() {
+            writeln!(out, "{:#06x} writes over the instruction at {:#06x}", address, target).unwrap();
+        }
+        return out;
+    }
+
+    /// Graphviz DOT: one cluster per subroutine, a node per block holding
+    /// its disassembly. Calls are dashed edges, `BNNN` points at a `?` node.
+    /// Blocks the ROM writes into are red, with a red edge from the block
+    /// doing the writing.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "digraph rom {{").unwrap();
+        writeln!(out, "  node [shape=box fontname=monospace];").unwrap();
+
+        let mut placed = BTreeSet::new();
+        for subroutine in self.subroutines.iter() {
+            let name = if subroutine.entry == self.entry { "program" } else { "subroutine" };
+            writeln!(out, "  subgraph cluster_{:04x} {{", subroutine.entry).unwrap();
+            writeln!(out, "    label=\"{} {:#06x}\";", name, subroutine.entry).unwrap();
+            for start in subroutine.blocks.iter() {
+                if !placed.insert(*start) {
+                    continue;
+                }
+                let block = &self.blocks[start];
+                let mut label = String::new();
+                for (address, opcode) in self.block_instructions(block) {
+                    write!(label, "{:04X}  {}\\l", address, disassemble(opcode)).unwrap();
+                }
+                let colour = if self.code_writes.iter().any(|(_, target)| (block.start..=block.last).contains(target)) {
+                    " color=red"
+                } else {
+                    ""
+                };
+                writeln!(out, "    b{:04x} [label=\"{}\"{}];", start, label, colour).unwrap();
+            }
+            writeln!(out, "  }}").unwrap();
+        }
+
+        for block in self.blocks.values() {
+            let labels: &[&str] = match block.flow {
+                Flow::Skip => &["no skip", "skip"],
+                _ => &[""],
+            };
+            for (i, successor) in block.successors.iter().enumerate() {
+                let label = labels.get(i).copied().unwrap_or("");
+                writeln!(out, "  b{:04x} -> b{:04x} [label=\"{}\"];", block.start, successor, label).unwrap();
+            }
+            match block.flow {
+                Flow::Call(target) if self.blocks.contains_key(&target) => {
+                    writeln!(out, "  b{:04x} -> b{:04x} [style=dashed label=\"call\"];", block.start, target).unwrap();
+                }
+                Flow::Indirect(base) => {
+                    writeln!(out, "  i{:04x} [label=\"?\" shape=circle];", block.last).unwrap();
+                    writeln!(
+                        out,
+                        "  b{:04x} -> i{:04x} [style=dotted label=\"{:#05x} + V\"];",
+                        block.start, block.last, base
+                    )
+                    .unwrap();
+                }
+                _ => {}
+            }
+        }
+        for (address, target) in self.code_writes.iter() {
+            let (Some(from), Some(to)) = (self.block_of(*address), self.block_of(*target)) else {
+                continue;
+            };
+            writeln!(out, "  b{:04x} -> b{:04x} [color=red style=dashed label=\"writes\"];", from, to).unwrap();
+        }
+        writeln!(out, "}}").unwrap();
+        return out;
+    }
+
+    /// Start of the block holding the instruction at `address`.
+    fn block_of(&self, address: u16) -> Option<u16> {
+        let (start, block) = self.blocks.range(..=address).next_back()?;
+        return (address <= block.last).then_some(*start);
+    }
+}
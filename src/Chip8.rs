@@ -1,9 +1,18 @@
-use crate::Helpers::*;
+use crate::BlockCache::BlockCache;
+use crate::Coverage::Coverage;
+use crate::Framebuffer::Framebuffer;
 use crate::NonBlockingReader::NonblockingBufReader;
+use crate::Op::Op;
+use crate::Profiler::Profiler;
 use crate::Ram;
+use crate::Random::Random;
+use crate::Quirks::Quirks;
 use crate::Restart;
-use rand::random;
+use crate::Rom::{Platform, RomErrors};
+use crate::Tracer::{TraceEntry, Tracer};
 use stack_stack::Stack;
+use std::collections::VecDeque;
+use std::fmt;
 use std::io::prelude::*;
 use std::io::{stdout, Stdout};
 use std::write;
@@ -14,65 +23,345 @@ pub struct Chip8 {
     ir: u16,
     pub ram: Ram::Ram,
     v: [u8; 16],
-    display: [bool; 2048],
-    stack: Stack<u16, 16>,
+    display: Framebuffer,
+    /// Return addresses, innermost call last.
+    stack: Vec<u16>,
+    /// Most return addresses `stack` may hold.
+    stack_depth: usize,
     pub keys: [u8; 16],
+    /// Frames the keys typed in the terminal stay down for.
+    typed: [u8; 16],
+    /// The key `FX0A` saw go down and waits to come up.
+    waiting_key: Option<usize>,
     pub delay_timer: u8,
     pub soud_timer: u8,
-    stdin: NonblockingBufReader,
-    pub outbuff: RawTerminal<Stdout>,
+    stdin: Option<NonblockingBufReader>,
+    pub outbuff: Option<RawTerminal<Stdout>>,
+    pub tracer: Option<Tracer>,
+    pub profiler: Option<Profiler>,
+    pub coverage: Option<Coverage>,
+    pub rng: Random,
+    pub cycles_per_frame: usize,
+    /// Set once the user asked to leave, the main loop should stop.
+    pub quit: bool,
+    platform: Platform,
+    pub quirks: Quirks,
+    /// A sprite was drawn this frame, for the vblank quirk.
+    drawn: bool,
+    /// The last addresses written to, newest at the back.
+    recent_writes: VecDeque<u16>,
+    /// Address and height of the sprite the last `DXYN` drew.
+    last_sprite: Option<(u16, usize)>,
+    /// Pre-decoded blocks, when running in the fast mode.
+    cache: Option<BlockCache>,
+    /// Callbacks registered with `on_event`.
+    listeners: Vec<Listener>,
+    /// Bytes of the ROM loaded last, initialized memory when tracking.
+    rom_size: usize,
+    /// The last memory warnings, newest at the back, and how many there
+    /// were in all.
+    recent_warnings: VecDeque<MemoryWarning>,
+    warning_count: u64,
+}
+
+/// A callback registered with `Chip8::on_event`.
+type Listener = Box<dyn FnMut(&Event)>;
+
+/// Settings for `Chip8::new`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub platform: Platform,
+    pub quirks: Quirks,
+    /// Instructions `run_frame` executes.
+    pub cycles_per_frame: usize,
+    /// Seed for `CXKK`, a random one when `None`.
+    pub seed: Option<u64>,
+    /// Run through the block cache, like `--fast`.
+    pub fast: bool,
+    /// Return addresses the stack holds, the platform's when `None`.
+    /// `usize::MAX` makes it unlimited.
+    pub stack_depth: Option<usize>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        return Config {
+            platform: Platform::Chip8,
+            quirks: Default::default(),
+            cycles_per_frame: 10,
+            seed: None,
+            fast: false,
+            stack_depth: None,
+        };
+    }
+}
+
+/// Something the program did, passed to the callbacks registered with
+/// `Chip8::on_event` as it happens.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    /// `00E0` cleared the screen.
+    Clear,
+    /// `DXYN` drew a sprite of `height` rows from `address` at `x`, `y`,
+    /// the values of VX and VY before the screen size is applied.
+    Draw {
+        address: u16,
+        x: u8,
+        y: u8,
+        height: u8,
+        collided: bool,
+    },
+    /// The sound timer was started, the buzzer is on.
+    SoundStart,
+    /// The sound timer ran out or was set to zero.
+    SoundStop,
+    /// A byte of memory was written, by `FX33`/`FX55` or `write_ram`.
+    Write { address: u16, value: u8 },
+    /// The program did something suspicious to memory, only sent while
+    /// memory is tracked.
+    Warning(MemoryWarning),
+}
+
+/// How many written addresses `recent_writes` remembers.
+const RECENT_WRITES: usize = 32;
+/// How many warnings `recent_warnings` remembers.
+const RECENT_WARNINGS: usize = 16;
+/// How many frames a key typed in the terminal stays down.
+const TYPED_KEY_FRAMES: u8 = 8;
+
+/// Something suspicious the program did to memory, found by
+/// `Chip8::set_memory_tracking`. `at` is the address of the instruction.
+/// Each address is only warned about once for writes and once for reads.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MemoryWarning {
+    /// `FX33`/`FX55` wrote over a byte that already ran as an instruction.
+    CodeWrite { at: u16, address: u16 },
+    /// `FX33`/`FX55` wrote into the font at 0x50.
+    FontWrite { at: u16, address: u16 },
+    /// `DXYN`/`FX65` read a byte that the ROM, the font or a write never
+    /// set.
+    UninitializedRead { at: u16, address: u16 },
+}
+
+impl MemoryWarning {
+    /// The names `name` gives, in the order of the variants.
+    pub const NAMES: [&'static str; 3] = ["code-write", "font-write", "uninitialized-read"];
+
+    /// Short name for breakpoints.
+    pub fn name(&self) -> &'static str {
+        return match self {
+            MemoryWarning::CodeWrite { .. } => "code-write",
+            MemoryWarning::FontWrite { .. } => "font-write",
+            MemoryWarning::UninitializedRead { .. } => "uninitialized-read",
+        };
+    }
+
+    /// Address of the instruction that did it.
+    pub fn at(&self) -> u16 {
+        return match self {
+            MemoryWarning::CodeWrite { at, .. }
+            | MemoryWarning::FontWrite { at, .. }
+            | MemoryWarning::UninitializedRead { at, .. } => *at,
+        };
+    }
+}
+
+impl fmt::Display for MemoryWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            MemoryWarning::CodeWrite { at, address } => {
+                write!(f, "{:#06x} wrote over code at {:#06x}", at, address)
+            }
+            MemoryWarning::FontWrite { at, address } => {
+                write!(f, "{:#06x} wrote into the font at {:#06x}", at, address)
+            }
+            MemoryWarning::UninitializedRead { at, address } => {
+                write!(f, "{:#06x} read uninitialized memory at {:#06x}", at, address)
+            }
+        };
+    }
+}
+
+/// One subroutine being run, see `Chip8::call_stack`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StackFrame {
+    /// `pc` for the innermost frame, the `2NNN` waiting for its return for
+    /// the others.
+    pub address: u16,
+    /// Where the subroutine starts: the target of the call, or the load
+    /// address for the program itself. `None` when the call instruction has
+    /// been overwritten since.
+    pub routine: Option<u16>,
 }
 
 #[derive(Debug)]
-enum Chip8Errors {
+pub enum Chip8Errors {
     UndefinedInstruction,
-    StackOverflow,
+    /// The `2NNN` at `at` found the stack full with `depth` addresses.
+    StackOverflow { at: u16, depth: usize },
+    /// The `00EE` at `at` had no address to return to.
+    StackUnderflow { at: u16 },
+    /// The instruction at `at` (or fetching it) needed the byte at
+    /// `address`, past the end of memory.
+    OutOfBounds { at: u16, address: usize },
 }
 
-impl Chip8 {
-    fn draw_sprite(&mut self, x: usize, y: usize, spheight: u16) -> () {
-        let xpos = self.v[x] % 64;
-        let ypos = self.v[y] % 32;
-        self.v[0xF] = 0;
+impl Chip8Errors {
+    /// The names `name` gives, in the order of the variants.
+    pub const NAMES: [&'static str; 4] = ["undefined", "stack-overflow", "stack-underflow", "out-of-bounds"];
 
-        let mut row: usize = 0;
-        let mut col;
+    /// Short name for breakpoints and the command line.
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Chip8Errors::UndefinedInstruction => "undefined",
+            Chip8Errors::StackOverflow { .. } => "stack-overflow",
+            Chip8Errors::StackUnderflow { .. } => "stack-underflow",
+            Chip8Errors::OutOfBounds { .. } => "out-of-bounds",
+        };
+    }
+}
 
-        while row < spheight as usize {
-            if row + ypos as usize > 31 {
-                break;
+impl fmt::Display for Chip8Errors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            Chip8Errors::UndefinedInstruction => write!(f, "undefined instruction"),
+            Chip8Errors::StackOverflow { at, depth } => write!(
+                f,
+                "stack overflow: call at {:#06x} with all {} return addresses in use",
+                at, depth
+            ),
+            Chip8Errors::StackUnderflow { at } => {
+                write!(f, "stack underflow: return at {:#06x} with no caller to return to", at)
+            }
+            Chip8Errors::OutOfBounds { at, address } => {
+                write!(f, "out of bounds: {:#06x} needed memory at {:#06x}, past the end", at, address)
             }
+        };
+    }
+}
 
-            col = 0;
+impl Chip8 {
+    /// Rows of a `DXYN` sprite read from memory: rows clipped at the bottom
+    /// aren't read at all.
+    fn sprite_rows(&self, y: usize, spheight: u16) -> usize {
+        let ypos = self.v[y] as usize % self.display.height();
+        if self.quirks.wrap {
+            return spheight as usize;
+        }
+        return (spheight as usize).min(self.display.height() - ypos);
+    }
 
-            while col < 8 {
-                if col + xpos as usize > 63 {
-                    break;
-                }
+    /// Fails the running instruction unless the `len` bytes from `address`
+    /// are all in memory.
+    fn check_memory(&self, address: u16, len: usize) -> Result<(), Chip8Errors> {
+        let end = address as usize + len;
+        if len > 0 && end > self.ram.size() {
+            return Err(Chip8Errors::OutOfBounds {
+                at: self.pc.wrapping_sub(2),
+                address: (address as usize).max(self.ram.size()),
+            });
+        }
+        return Ok(());
+    }
 
-                if ((self.ram[self.ir as usize + row] as u16 >> (7 - col as u16)) & 1) == 1 {
-                    if self.display[index(xpos as usize + col, ypos as usize + row)] == true {
-                        self.v[0xF] = 1;
-                        self.display[index(xpos as usize + col, ypos as usize + row)] = false;
-                    } else {
-                        self.display[index(xpos as usize + col, ypos as usize + row)] = true;
-                    }
-                }
-                col += 1;
+    fn draw_sprite(&mut self, x: usize, y: usize, spheight: u16) -> () {
+        let (vx, vy) = (self.v[x], self.v[y]);
+        let xpos = vx as usize;
+        let ypos = vy as usize % self.display.height();
+        let rows = self.sprite_rows(y, spheight);
+        let mut sprite = [0u16; 16];
+        if self.ram.tracking() {
+            for (row, bits) in sprite.iter_mut().enumerate().take(rows) {
+                *bits = self.load(self.ir as usize + row) as u16;
+            }
+        } else {
+            for (row, bits) in sprite.iter_mut().enumerate().take(rows) {
+                *bits = self.ram[self.ir as usize + row] as u16;
             }
-            row += 1;
         }
+
+        let collided = self.display.draw(xpos, ypos, &sprite[..rows], 8, self.quirks.wrap);
+        self.v[0xF] = collided as u8;
+        self.emit(Event::Draw {
+            address: self.ir,
+            x: vx,
+            y: vy,
+            height: spheight as u8,
+            collided,
+        });
+    }
+
+    /// Builds a machine that is not attached to the terminal, for running
+    /// ROMs from tools and tests. Input only comes through `keys`.
+    pub fn headless() -> Self {
+        return Self::with_terminal(None, None);
+    }
+
+    /// A headless machine set up from `config`, ready for `load_rom`.
+    pub fn new(config: Config) -> Self {
+        let mut chip8 = Self::headless();
+        chip8.set_platform(config.platform);
+        chip8.quirks = config.quirks;
+        chip8.cycles_per_frame = config.cycles_per_frame;
+        let seed = config.seed.unwrap_or_else(rand::random::<u64>);
+        chip8.rng = Random::new(seed);
+        chip8.set_block_cache(config.fast);
+        if let Some(depth) = config.stack_depth {
+            chip8.set_stack_depth(depth);
+        }
+        return chip8;
+    }
+
+    fn with_terminal(
+        stdin: Option<NonblockingBufReader>,
+        outbuff: Option<RawTerminal<Stdout>>,
+    ) -> Self {
+        return Self {
+            pc: 0x200,
+            ir: 0,
+            ram: Default::default(),
+            v: [0; 16],
+            display: Framebuffer::lores(),
+            stack: Vec::with_capacity(16),
+            stack_depth: Platform::Chip8.stack_depth(),
+            keys: [0; 16],
+            typed: [0; 16],
+            waiting_key: None,
+            delay_timer: 0,
+            soud_timer: 0,
+            stdin,
+            outbuff,
+            tracer: None,
+            profiler: None,
+            coverage: None,
+            rng: Default::default(),
+            cycles_per_frame: 10,
+            quit: false,
+            platform: Platform::Chip8,
+            quirks: Default::default(),
+            drawn: false,
+            recent_writes: VecDeque::with_capacity(RECENT_WRITES),
+            last_sprite: None,
+            cache: None,
+            listeners: Vec::new(),
+            rom_size: 0,
+            recent_warnings: VecDeque::with_capacity(RECENT_WARNINGS),
+            warning_count: 0,
+        };
     }
 
     fn clear_screen(&mut self) -> () {
+        let Some(outbuff) = self.outbuff.as_mut() else {
+            return;
+        };
         write!(
-            self.outbuff,
+            outbuff,
             "{}{}",
             termion::cursor::Goto(1, 1),
             termion::cursor::Hide,
         )
         .unwrap();
-        self.outbuff.flush().unwrap();
+        outbuff.flush().unwrap();
         //} else {
         //    print!("\x1b[0;0H");
         //}
@@ -81,344 +370,724 @@ impl Chip8 {
     }
 
     pub fn render(&mut self) -> () {
+        if self.outbuff.is_none() {
+            return;
+        }
         self.clear_screen();
+        let scrn = self.terminal_frame();
+        let outbuff = self.outbuff.as_mut().unwrap();
+        write!(
+            outbuff,
+            "{} {}",
+            std::str::from_utf8(scrn.as_slice()).unwrap(),
+            scrn.len()
+        )
+        .unwrap();
+        outbuff.flush().unwrap();
+    }
+
+    /// The display as the escape sequences `render` writes to the terminal,
+    /// two spaces with a white or black background per pixel. The buffer
+    /// holds a 64x32 screen.
+    pub fn terminal_frame(&self) -> Stack<u8, 22688> {
         let mut scrn: Stack<u8, 22688> = Stack::new();
 
-        for i in 0..2048 {
-            if i % 64 == 0 {
-                for c in (*b"\x1b[1G").into_iter() {
-                    scrn.push(c).unwrap();
+        for y in 0..self.display.height() {
+            for c in (*b"\x1b[1G").into_iter() {
+                scrn.push(c).unwrap();
+            }
+            for c in (*b"\n").into_iter() {
+                scrn.push(c).unwrap();
+            }
+
+            for x in 0..self.display.width() {
+                if self.display.pixel(x, y) {
+                    for c in (*b"\x1b[47m  \x1b[0m").into_iter() {
+                        scrn.push(c).unwrap();
+                    }
+                } else {
+                    for c in (*b"\x1b[40m  \x1b[0m").into_iter() {
+                        scrn.push(c).unwrap();
+                    }
                 }
-                for c in (*b"\n").into_iter() {
-                    scrn.push(c).unwrap();
+            }
+        }
+        return scrn;
+    }
+
+    /// Executes one instruction, the same as `step`.
+    pub fn do_cycle(&mut self) -> Result<(), Chip8Errors> {
+        return self.step();
+    }
+
+    /// Runs `cycles_per_frame` instructions, stopping early on errors or
+    /// when the user quits.
+    pub fn run_frame(&mut self) -> Result<(), Chip8Errors> {
+        self.begin_frame();
+        return self.run_cycles(self.cycles_per_frame);
+    }
+
+    /// Runs up to `cycles` instructions, stopping early on errors or when
+    /// the user quits. With the block cache on, whole blocks of pre-decoded
+    /// instructions run at a time; the result is the same as stepping.
+    pub fn run_cycles(&mut self, cycles: usize) -> Result<(), Chip8Errors> {
+        let mut executed = 0;
+        while executed < cycles && !self.quit {
+            let block = match self.cache.as_mut() {
+                Some(cache) => cache.get(&self.ram, self.pc),
+                None => {
+                    self.step()?;
+                    executed += 1;
+                    continue;
                 }
+            };
+            if block.ops.is_empty() {
+                self.step()?;
+                executed += 1;
+                continue;
             }
 
-            if self.display[i] {
-                for c in (*b"\x1b[47m  \x1b[0m").into_iter() {
-                    scrn.push(c).unwrap();
+            let generation = self.cache.as_ref().map(BlockCache::generation);
+            for (opcode, op) in block.ops.iter() {
+                let next = self.pc.wrapping_add(2);
+                self.run_op(*opcode, *op)?;
+                executed += 1;
+                // left the block, ran out of cycles or the code changed
+                if self.pc != next
+                    || executed == cycles
+                    || self.quit
+                    || self.cache.as_ref().map(BlockCache::generation) != generation
+                {
+                    break;
                 }
+            }
+        }
+        return Ok(());
+    }
+
+    /// Turns the block cache on or off. Code written through `ram` directly
+    /// instead of `write_ram` isn't noticed, call this again after such
+    /// writes to start with an empty cache.
+    pub fn set_block_cache(&mut self, on: bool) -> () {
+        self.cache = on.then(|| BlockCache::new(self.ram.size()));
+    }
+
+    pub fn block_cache(&self) -> Option<&BlockCache> {
+        return self.cache.as_ref();
+    }
+
+    pub fn pc(&self) -> u16 {
+        return self.pc;
+    }
+
+    pub fn set_pc(&mut self, pc: u16) -> () {
+        self.pc = pc;
+    }
+
+    pub fn ir(&self) -> u16 {
+        return self.ir;
+    }
+
+    pub fn set_ir(&mut self, ir: u16) -> () {
+        self.ir = ir;
+    }
+
+    /// Register VX, `x` from 0 to 0xF.
+    pub fn v(&self, x: usize) -> u8 {
+        return self.v[x];
+    }
+
+    pub fn set_v(&mut self, x: usize, value: u8) -> () {
+        self.v[x] = value;
+    }
+
+    pub fn registers(&self) -> &[u8; 16] {
+        return &self.v;
+    }
+
+    /// Return addresses of the subroutines being run, innermost last.
+    pub fn stack(&self) -> &[u16] {
+        return &self.stack;
+    }
+
+    pub fn stack_depth(&self) -> usize {
+        return self.stack_depth;
+    }
+
+    /// How many return addresses fit on the stack, `usize::MAX` for no
+    /// limit. `set_platform` sets the platform's.
+    pub fn set_stack_depth(&mut self, depth: usize) -> () {
+        self.stack_depth = depth;
+    }
+
+    /// The subroutines being run, innermost first and the program last.
+    /// Each frame's routine is the target of the `2NNN` before the return
+    /// address that leads back into the frame below it.
+    pub fn call_stack(&self) -> Vec<StackFrame> {
+        let mut frames = Vec::with_capacity(self.stack.len() + 1);
+        let mut address = self.pc;
+        for ret in self.stack.iter().rev() {
+            let call = ret.wrapping_sub(2);
+            let mut opcode = 0;
+            if (call as usize + 1) < self.ram.size() {
+                opcode = (self.ram[call] as u16) << 8 | self.ram[call + 1] as u16;
+            }
+            let routine = (opcode & 0xF000 == 0x2000).then_some(opcode & 0x0FFF);
+            frames.push(StackFrame { address, routine });
+            address = call;
+        }
+        frames.push(StackFrame {
+            address,
+            routine: Some(self.platform.load_address()),
+        });
+        return frames;
+    }
+
+    /// The byte at `address`, `None` past the end of memory.
+    pub fn read_ram(&self, address: u16) -> Option<u8> {
+        return self.ram.as_slice().get(address as usize).copied();
+    }
+
+    /// All of memory.
+    pub fn memory(&self) -> &[u8] {
+        return self.ram.as_slice();
+    }
+
+    /// Presses or releases key `key` (0 to 0xF) of the keypad.
+    pub fn set_key(&mut self, key: u8, pressed: bool) -> () {
+        self.keys[key as usize & 0xF] = pressed as u8;
+    }
+
+    /// Whether the buzzer sounds, that is the sound timer is running.
+    pub fn sound_active(&self) -> bool {
+        return self.soud_timer > 0;
+    }
+
+    /// Calls `listener` with every `Event` from now on. Listeners run in the
+    /// order they were added and can't be removed.
+    pub fn on_event(&mut self, listener: impl FnMut(&Event) + 'static) -> () {
+        self.listeners.push(Box::new(listener));
+    }
+
+    fn emit(&mut self, event: Event) -> () {
+        for listener in self.listeners.iter_mut() {
+            listener(&event);
+        }
+    }
+
+    /// Stores a byte, remembers the address in `recent_writes` and sends
+    /// `Event::Write`. With memory tracking on the byte counts as set.
+    /// Past the end of memory nothing is written and the error is
+    /// `OutOfBounds` with `at` the current `pc`.
+    pub fn write_ram(&mut self, address: u16, value: u8) -> Result<(), Chip8Errors> {
+        if address as usize >= self.ram.size() {
+            return Err(Chip8Errors::OutOfBounds {
+                at: self.pc,
+                address: address as usize,
+            });
+        }
+        self.ram.mark(address as usize, Ram::INITIALIZED);
+        self.write(address, value);
+        return Ok(());
+    }
+
+    /// `write_ram` for an address known to be in memory.
+    fn write(&mut self, address: u16, value: u8) -> () {
+        self.ram[address] = value;
+        self.emit(Event::Write { address, value });
+        if let Some(cache) = self.cache.as_mut() {
+            cache.write(address);
+        }
+        if self.recent_writes.len() == RECENT_WRITES {
+            self.recent_writes.pop_front();
+        }
+        self.recent_writes.push_back(address);
+    }
+
+    /// Addresses written by the program (or through `write_ram`) most
+    /// recently, oldest first.
+    pub fn recent_writes(&self) -> &VecDeque<u16> {
+        return &self.recent_writes;
+    }
+
+    /// Records read/write/execute bits for every byte of memory (see
+    /// `Ram::access`) and warns about writes over code that already ran,
+    /// writes into the font and reads of memory nothing set, through
+    /// `Event::Warning`, `recent_warnings` and the trace. The font and the
+    /// last ROM loaded count as set.
+    pub fn set_memory_tracking(&mut self, on: bool) -> () {
+        self.ram.track_access(on);
+        let start = self.platform.load_address() as usize;
+        for address in start..start + self.rom_size {
+            self.ram.mark(address, Ram::INITIALIZED);
+        }
+    }
+
+    /// The last memory warnings, oldest first.
+    pub fn recent_warnings(&self) -> &VecDeque<MemoryWarning> {
+        return &self.recent_warnings;
+    }
+
+    /// Memory warnings since the machine was made.
+    pub fn warning_count(&self) -> u64 {
+        return self.warning_count;
+    }
+
+    fn warn(&mut self, warning: MemoryWarning) -> () {
+        if self.recent_warnings.len() == RECENT_WARNINGS {
+            self.recent_warnings.pop_front();
+        }
+        self.recent_warnings.push_back(warning);
+        self.warning_count += 1;
+        self.emit(Event::Warning(warning));
+    }
+
+    /// A byte the running instruction reads as data.
+    fn load(&mut self, address: usize) -> u8 {
+        if let Some(access) = self.ram.access(address) {
+            self.ram.mark(address, Ram::READ);
+            if access & (Ram::INITIALIZED | Ram::WARNED_READ) == 0 {
+                self.ram.mark(address, Ram::WARNED_READ);
+                let at = self.pc.wrapping_sub(2);
+                self.warn(MemoryWarning::UninitializedRead {
+                    at,
+                    address: address as u16,
+                });
+            }
+        }
+        return self.ram[address];
+    }
+
+    /// `write_ram` for the running instruction.
+    fn store(&mut self, address: u16, value: u8) -> () {
+        if let Some(access) = self.ram.access(address as usize) {
+            self.ram.mark(address as usize, Ram::WRITE | Ram::INITIALIZED);
+            let at = self.pc.wrapping_sub(2);
+            let warning = if access & Ram::EXECUTE != 0 {
+                Some(MemoryWarning::CodeWrite { at, address })
+            } else if (0x50..0xA0).contains(&address) {
+                Some(MemoryWarning::FontWrite { at, address })
             } else {
-                for c in (*b"\x1b[40m  \x1b[0m").into_iter() {
-                    scrn.push(c).unwrap();
-                }
+                None
+            };
+            if let Some(warning) = warning.filter(|_| access & Ram::WARNED_WRITE == 0) {
+                self.ram.mark(address as usize, Ram::WARNED_WRITE);
+                self.warn(warning);
             }
         }
-        write!(
-            self.outbuff,
-            "{} {}",
-            std::str::from_utf8(scrn.as_slice()).unwrap(),
-            scrn.len()
-        )
-        .unwrap();
-        self.outbuff.flush().unwrap();
+        self.write(address, value);
     }
 
-    pub fn do_cycle(&mut self) -> () {
-        let op1 = self.ram[self.pc as usize];
-        let op2 = self.ram[self.pc as usize + 1];
+    /// Address and height of the sprite the last `DXYN` drew, if any.
+    pub fn last_sprite(&self) -> Option<(u16, usize)> {
+        return self.last_sprite;
+    }
+
+    pub fn platform(&self) -> Platform {
+        return self.platform;
+    }
+
+    /// Switches to another platform. Memory is resized and cleared, the
+    /// program counter moves to the platform's load address and the stack
+    /// takes its depth, so this is meant to be called before `load_rom`.
+    pub fn set_platform(&mut self, platform: Platform) -> () {
+        self.platform = platform;
+        let tracking = self.ram.tracking();
+        self.ram = Ram::Ram::with_size(platform.memory_size());
+        self.ram.track_access(tracking);
+        self.pc = platform.load_address();
+        self.stack_depth = platform.stack_depth();
+        if self.cache.is_some() {
+            self.set_block_cache(true);
+        }
+    }
 
-        self.pc += 2;
+    /// Copies a ROM to the platform's load address and points `pc` at it.
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), RomErrors> {
+        if rom.is_empty() {
+            return Err(RomErrors::Empty);
+        }
+        if rom.len() > self.platform.max_rom_size() {
+            return Err(RomErrors::TooLarge {
+                size: rom.len(),
+                max: self.platform.max_rom_size(),
+                platform: self.platform,
+            });
+        }
 
+        let start = self.platform.load_address() as usize;
+        for (i, byte) in rom.iter().enumerate() {
+            self.ram[start + i] = *byte;
+            self.ram.mark(start + i, Ram::INITIALIZED);
+        }
+        self.rom_size = rom.len();
+        self.pc = self.platform.load_address();
+        if let Some(cache) = self.cache.as_mut() {
+            cache.clear();
+        }
+        return Ok(());
+    }
+
+    pub fn framebuffer(&self) -> &Framebuffer {
+        return &self.display;
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        return self.display.pixel(x, y);
+    }
+
+    /// Marks the start of a new 60 Hz frame and ticks the timers down.
+    /// `run_frame` does this itself, callers stepping one instruction at a
+    /// time call it every frame.
+    pub fn begin_frame(&mut self) -> () {
+        self.drawn = false;
         if self.delay_timer > 0 {
             self.delay_timer -= 1;
         }
-
         if self.soud_timer > 0 {
             self.soud_timer -= 1;
-            // Beep;
-        }
-
-        match self.exec((op1 as u16) << 8 | op2 as u16) {
-            Ok(()) => {}
-            Err(_) => {
-                self.ram.debug();
-                panic!(
-                    "pc: {} \n, opcode {:#x}",
-                    self.pc,
-                    (op1 as u16) << 8 | op2 as u16
-                );
-            }
-        }
-    }
-    pub fn handle_input(&mut self, wait: bool) -> u8 {
-        loop {
-            if let Ok(Some(key)) = self.stdin.read_char_only_if_data() {
-                match key {
-                    27 | 3 | 4 => {
-                        self.outbuff.suspend_raw_mode().unwrap();
-                        let mut i = 0;
-                        println!("pressed: {} ", key);
-                        for val in self.keys.iter() {
-                            println!("key: {:#x} = {}", i, val);
-                            i += 1;
-                        }
-                        std::process::exit(0);
-                    }
-                    b'1' => {
-                        self.keys[1] = 1;
-                        //let mut i = 0;
-                        //println!("pressed: {} ", key);
-                        //for val in self.keys.iter() {
-                        //    println!("key: {:#x} = {}", i, val);
-                        //    i += 1;
-                        //}
-                        //std::process::exit(0);
-
-                        return 1;
-                    }
-                    b'2' => {
-                        self.keys[2] = 1;
-                        return 2;
-                    }
-                    b'3' => {
-                        self.keys[3] = 1;
-                        return 3;
-                    }
-                    b'4' => {
-                        self.keys[0xC] = 1;
-                        return 0xC;
-                    }
-                    b'q' => {
-                        self.keys[4] = 1;
-                        return 4;
-                    }
-                    b'w' => {
-                        self.keys[5] = 1;
-                        return 5;
-                    }
-                    b'e' => {
-                        self.keys[6] = 1;
-                        return 6;
-                    }
-                    b'r' => {
-                        self.keys[0xD] = 1;
-                        return 0xD;
-                    }
-                    b'a' => {
-                        self.keys[7] = 1;
-                        return 7;
-                    }
-                    b's' => {
-                        self.keys[8] = 1;
-                        return 8;
-                    }
-                    b'd' => {
-                        self.keys[9] = 1;
-                        return 9;
-                    }
-                    b'f' => {
-                        self.keys[0xE] = 1;
-                        return 0xE;
-                    }
-                    b'z' => {
-                        self.keys[0xA] = 1;
-                        return 0xA;
-                    }
-                    b'x' => {
-                        self.keys[0] = 1;
-                        return 0;
-                    }
-                    b'c' => {
-                        self.keys[0xB] = 1;
-                        return 0xB;
-                    }
-                    b'v' => {
-                        self.keys[0xF] = 1;
-                        return 0xF;
-                    }
-                    a => {
-                        println!("{} : {}\n", a, std::str::from_utf8(&[a]).unwrap());
-                        break a;
-                    }
+            if self.soud_timer == 0 {
+                self.emit(Event::SoundStop);
+            }
+        }
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.frame();
+        }
+    }
+
+    /// Stops reading the terminal, keys then only change through `keys`.
+    /// Rendering is unaffected.
+    pub fn detach_input(&mut self) -> () {
+        self.stdin = None;
+    }
+
+    /// Executes one instruction, returning the error instead of panicking.
+    pub fn step(&mut self) -> Result<(), Chip8Errors> {
+        let Some(opcode) = self.ram.opcode(self.pc as usize) else {
+            return Err(Chip8Errors::OutOfBounds {
+                at: self.pc,
+                address: (self.pc as usize).max(self.ram.size()),
+            });
+        };
+        return self.run_op(opcode, Op::decode(opcode));
+    }
+
+    /// Executes the instruction at `pc`, already fetched and decoded.
+    fn run_op(&mut self, opcode: u16, op: Op) -> Result<(), Chip8Errors> {
+        let pc = self.pc;
+        let v = self.v;
+        let warnings = self.warning_count;
+
+        if self.ram.tracking() {
+            self.ram.mark(pc as usize, Ram::EXECUTE);
+            self.ram.mark(pc as usize + 1, Ram::EXECUTE);
+        }
+        self.pc = self.pc.wrapping_add(2);
+
+        let result = self.exec(op);
+
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.record(pc, opcode, self.pc, self.stack.len(), self.delay_timer);
+        }
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.record(pc, opcode, self.pc);
+        }
+
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.record(TraceEntry {
+                delay_timer: self.delay_timer,
+                sound_timer: self.soud_timer,
+                sp: self.stack.len() as u8,
+                ..TraceEntry::new(pc, opcode, &v, &self.v, self.ir)
+            });
+            let new = (self.warning_count - warnings) as usize;
+            for warning in self.recent_warnings.iter().skip(self.recent_warnings.len().saturating_sub(new)) {
+                tracer.note(pc, &format!("warning: {}", warning));
+            }
+        }
+
+        return result;
+    }
+    /// Reads a byte typed in the terminal, called once a frame. Keypad keys
+    /// go down and stay down for `TYPED_KEY_FRAMES` calls, a terminal never
+    /// says when a key goes up. Any other byte is returned as is for the
+    /// caller's hotkeys.
+    pub fn handle_input(&mut self) -> Option<u8> {
+        let stdin = self.stdin.as_mut()?;
+        for key in 0..16 {
+            if self.typed[key] > 0 {
+                self.typed[key] -= 1;
+                if self.typed[key] == 0 {
+                    self.keys[key] = 0;
                 }
-            } else if wait {
-                continue;
-            } else {
-                break 0;
             }
         }
+        let byte = stdin.read_char_only_if_data().ok().flatten()?;
+        let key = match byte {
+            27 | 3 | 4 => {
+                if let Some(outbuff) = self.outbuff.as_ref() {
+                    outbuff.suspend_raw_mode().unwrap();
+                }
+                if let Some(tracer) = self.tracer.as_mut() {
+                    let _ = tracer.flush();
+                }
+                println!("pressed: {} ", byte);
+                for (i, val) in self.keys.iter().enumerate() {
+                    println!("key: {:#x} = {}", i, val);
+                }
+                self.quit = true;
+                return None;
+            }
+            b'1' => 1,
+            b'2' => 2,
+            b'3' => 3,
+            b'4' => 0xC,
+            b'q' => 4,
+            b'w' => 5,
+            b'e' => 6,
+            b'r' => 0xD,
+            b'a' => 7,
+            b's' => 8,
+            b'd' => 9,
+            b'f' => 0xE,
+            b'z' => 0xA,
+            b'x' => 0,
+            b'c' => 0xB,
+            b'v' => 0xF,
+            _ => return Some(byte),
+        };
+        self.keys[key] = 1;
+        self.typed[key] = TYPED_KEY_FRAMES;
+        return None;
     }
+    /// The next byte typed in the terminal as is, without pressing a key.
+    /// `None` when nothing was typed or there is no terminal.
+    pub fn read_key(&mut self) -> Option<u8> {
+        return self.stdin.as_mut()?.read_char_only_if_data().ok().flatten();
+    }
+
     pub fn clear_keys(&mut self) -> () {
         for i in 0..16 {
             self.keys[i] = 0;
         }
     }
 
-    fn exec(&mut self, uc: u16) -> Result<(), Chip8Errors> {
-        match bg_id(uc) {
-            0x0 => match two_end_id(uc) {
-                0xE0 => {
-                    self.clear_screen();
-                }
-                0xEE => {
-                    self.pc = self.stack.pop().unwrap();
-                }
-                _ => {
-                    return Err(Chip8Errors::UndefinedInstruction);
+    /// Moves I past the registers `FX55`/`FX65` stored or loaded.
+    fn advance_ir(&mut self, x: usize) -> () {
+        if self.quirks.memory_leave_i_unchanged {
+            return;
+        }
+        if self.quirks.memory_increment_by_x {
+            self.ir = self.ir.wrapping_add(x as u16);
+        } else {
+            self.ir = self.ir.wrapping_add(x as u16 + 1);
+        }
+    }
+
+    fn exec(&mut self, op: Op) -> Result<(), Chip8Errors> {
+        match op {
+            Op::Cls => {
+                self.display.clear();
+                self.clear_screen();
+                self.emit(Event::Clear);
+            }
+            Op::Ret => match self.stack.pop() {
+                Some(address) => self.pc = address,
+                None => {
+                    return Err(Chip8Errors::StackUnderflow {
+                        at: self.pc.wrapping_sub(2),
+                    })
                 }
             },
-            0x1 => {
-                self.pc = nnn(uc);
+            Op::Jp(nnn) => {
+                self.pc = nnn;
             }
-            0x2 => match self.stack.push(self.pc) {
-                Ok(()) => {
-                    self.pc = nnn(uc);
+            Op::Call(nnn) => {
+                if self.stack.len() >= self.stack_depth {
+                    return Err(Chip8Errors::StackOverflow {
+                        at: self.pc.wrapping_sub(2),
+                        depth: self.stack_depth,
+                    });
                 }
-                Err(_) => {
-                    return Err(Chip8Errors::StackOverflow);
-                }
-            },
-            0x3 => {
-                if self.v[x(uc)] == kk(uc) as u8 {
-                    self.pc += 2;
+                self.stack.push(self.pc);
+                self.pc = nnn;
+            }
+            Op::SeByte(x, kk) => {
+                if self.v[x] == kk {
+                    self.pc = self.pc.wrapping_add(2);
                 }
             }
-            0x4 => {
-                if self.v[x(uc)] != kk(uc) as u8 {
-                    self.pc += 2;
+            Op::SneByte(x, kk) => {
+                if self.v[x] != kk {
+                    self.pc = self.pc.wrapping_add(2);
                 }
             }
-            0x5 => {
-                if self.v[x(uc)] == self.v[y(uc)] {
-                    self.pc += 2;
+            Op::SeReg(x, y) => {
+                if self.v[x] == self.v[y] {
+                    self.pc = self.pc.wrapping_add(2);
                 }
             }
-            0x6 => {
-                self.v[x(uc)] = kk(uc) as u8;
+            Op::LdByte(x, kk) => {
+                self.v[x] = kk;
             }
-            0x7 => {
-                self.v[x(uc)] = self.v[x(uc)].wrapping_add(kk(uc) as u8);
+            Op::AddByte(x, kk) => {
+                self.v[x] = self.v[x].wrapping_add(kk);
             }
-            0x8 => match end_id(uc) {
-                0x0 => {
-                    self.v[x(uc)] = self.v[y(uc)];
-                }
-                0x1 => {
-                    self.v[x(uc)] |= self.v[y(uc)];
-                }
-                0x2 => {
-                    self.v[x(uc)] &= self.v[y(uc)];
-                }
-                0x3 => {
-                    self.v[x(uc)] ^= self.v[y(uc)];
-                }
-                0x4 => {
-                    let sum = self.v[x(uc)] as u16 + self.v[y(uc)] as u16;
-                    self.v[x(uc)] = (sum & 0x00FF) as u8;
-
-                    if sum > 255 {
-                        self.v[0xF] = 1;
-                    } else {
-                        self.v[0xF] = 0;
-                    }
-                }
-                0x5 => {
-                    let (res, bor) = self.v[x(uc)].overflowing_sub(self.v[y(uc)]);
-                    let flag = if bor { 0 } else { 1 };
-
-                    self.v[x(uc)] = res;
-                    self.v[0xF] = flag;
-                }
-                0x6 => {
-                    let carry = self.v[x(uc)] & 1;
-                    self.v[x(uc)] = self.v[x(uc)] >> 1;
-                    self.v[0xF] = carry;
+            Op::LdReg(x, y) => {
+                self.v[x] = self.v[y];
+            }
+            Op::Or(x, y) => {
+                self.v[x] |= self.v[y];
+                if self.quirks.logic {
+                    self.v[0xF] = 0;
                 }
-                0x7 => {
-                    let (res, borr) = self.v[y(uc)].overflowing_sub(self.v[x(uc)]);
-                    let flag = if borr { 0 } else { 1 };
-                    self.v[x(uc)] = res;
-                    self.v[0xF] = flag;
+            }
+            Op::And(x, y) => {
+                self.v[x] &= self.v[y];
+                if self.quirks.logic {
+                    self.v[0xF] = 0;
                 }
-                0x0E => {
-                    let carry = self.v[x(uc)] >> 7;
-                    self.v[x(uc)] = self.v[x(uc)] << 1;
-                    self.v[0xF] = carry;
+            }
+            Op::Xor(x, y) => {
+                self.v[x] ^= self.v[y];
+                if self.quirks.logic {
+                    self.v[0xF] = 0;
                 }
+            }
+            Op::AddReg(x, y) => {
+                let sum = self.v[x] as u16 + self.v[y] as u16;
+                self.v[x] = (sum & 0x00FF) as u8;
 
-                _ => {
-                    return Err(Chip8Errors::UndefinedInstruction);
-                }
-            },
-            0x9 => {
-                if self.v[x(uc)] != self.v[y(uc)] {
-                    self.pc += 2;
+                if sum > 255 {
+                    self.v[0xF] = 1;
+                } else {
+                    self.v[0xF] = 0;
                 }
             }
-            0xA => {
-                self.ir = nnn(uc);
+            Op::Sub(x, y) => {
+                let (res, bor) = self.v[x].overflowing_sub(self.v[y]);
+                let flag = if bor { 0 } else { 1 };
+
+                self.v[x] = res;
+                self.v[0xF] = flag;
             }
-            0xB => {
-                self.pc = nnn(uc) + self.v[0] as u16;
+            Op::Shr(x, y) => {
+                let value = if self.quirks.shift { self.v[x] } else { self.v[y] };
+                let carry = value & 1;
+                self.v[x] = value >> 1;
+                self.v[0xF] = carry;
             }
-            0xC => {
-                self.v[x(uc)] = random::<u8>() & kk(uc);
+            Op::Subn(x, y) => {
+                let (res, borr) = self.v[y].overflowing_sub(self.v[x]);
+                let flag = if borr { 0 } else { 1 };
+                self.v[x] = res;
+                self.v[0xF] = flag;
             }
-            0xD => {
-                self.draw_sprite(x(uc), y(uc), n(uc));
-                //std::process::exit(0);
+            Op::Shl(x, y) => {
+                let value = if self.quirks.shift { self.v[x] } else { self.v[y] };
+                let carry = value >> 7;
+                self.v[x] = value << 1;
+                self.v[0xF] = carry;
             }
-            0xE => match two_end_id(uc) {
-                0x9E => {
-                    let key = self.v[x(uc)] as usize;
-                    if self.keys[key] == 1 {
-                        self.pc += 2;
-                    }
-                }
-                0xA1 => {
-                    let key = self.v[x(uc)] as usize;
-                    if self.keys[key] != 1 {
-                        self.pc += 2;
-                    }
+            Op::SneReg(x, y) => {
+                if self.v[x] != self.v[y] {
+                    self.pc = self.pc.wrapping_add(2);
                 }
-                _ => {
-                    return Err(Chip8Errors::UndefinedInstruction);
-                }
-            },
-            0xF => match two_end_id(uc) {
-                0x07 => {
-                    self.v[x(uc)] = self.delay_timer;
-                }
-                0x0A => {
-                    self.v[x(uc)] = self.handle_input(true);
-                }
-                0x15 => {
-                    self.delay_timer = self.v[x(uc)];
-                }
-                0x18 => {
-                    self.soud_timer = self.v[x(uc)];
-                }
-                0x1E => {
-                    self.ir += self.v[x(uc)] as u16;
+            }
+            Op::LdI(nnn) => {
+                self.ir = nnn;
+            }
+            Op::JpV0(nnn, x) => {
+                let offset = if self.quirks.jump { self.v[x] } else { self.v[0] };
+                self.pc = nnn + offset as u16;
+            }
+            Op::Rnd(x, kk) => {
+                self.v[x] = self.rng.draw() & kk;
+            }
+            Op::Drw(x, y, n) => {
+                if self.quirks.vblank && self.drawn {
+                    // wait for the next frame
+                    self.pc = self.pc.wrapping_sub(2);
+                    return Ok(());
                 }
-                0x29 => {
-                    self.ir = 0x50 + (5 * self.v[x(uc)] as u16); // addres to digit x
+                self.check_memory(self.ir, self.sprite_rows(y, n))?;
+                self.drawn = true;
+                self.last_sprite = Some((self.ir, n as usize));
+                self.draw_sprite(x, y, n);
+            }
+            Op::Skp(x) => {
+                let key = self.v[x] as usize & 0xF;
+                if self.keys[key] == 1 {
+                    self.pc = self.pc.wrapping_add(2);
                 }
-                0x33 => {
-                    let mut value = self.v[x(uc)];
-                    self.ram[self.ir + 2] = value % 10;
-                    value /= 10;
-                    self.ram[self.ir + 1] = value % 10;
-                    value /= 10;
-                    self.ram[self.ir] = value % 10;
+            }
+            Op::Sknp(x) => {
+                let key = self.v[x] as usize & 0xF;
+                if self.keys[key] != 1 {
+                    self.pc = self.pc.wrapping_add(2);
                 }
-                0x55 => {
-                    for i in 0..=x(uc) {
-                        self.ram[self.ir as usize + i] = self.v[i];
+            }
+            Op::LdVxDt(x) => {
+                self.v[x] = self.delay_timer;
+            }
+            Op::LdVxK(x) => {
+                // Like the VIP: wait for a key to go down, then for it to
+                // come up again. Keys only change between frames, so this
+                // runs again until they do.
+                match self.waiting_key {
+                    Some(key) if self.keys[key] != 1 => {
+                        self.v[x] = key as u8;
+                        self.waiting_key = None;
                     }
-                }
-                0x65 => {
-                    for i in 0..=x(uc) {
-                        self.v[i] = self.ram[self.ir as usize + i];
+                    Some(_) => self.pc = self.pc.wrapping_sub(2),
+                    None => {
+                        self.waiting_key = self.keys.iter().position(|key| *key == 1);
+                        self.pc = self.pc.wrapping_sub(2);
                     }
                 }
-                _ => {
-                    return Err(Chip8Errors::UndefinedInstruction);
+            }
+            Op::LdDtVx(x) => {
+                self.delay_timer = self.v[x];
+            }
+            Op::LdStVx(x) => {
+                let was_active = self.sound_active();
+                self.soud_timer = self.v[x];
+                match (was_active, self.sound_active()) {
+                    (false, true) => self.emit(Event::SoundStart),
+                    (true, false) => self.emit(Event::SoundStop),
+                    _ => {}
                 }
-            },
-
-            _ => {
+            }
+            Op::AddI(x) => {
+                self.ir = self.ir.wrapping_add(self.v[x] as u16);
+            }
+            Op::LdF(x) => {
+                self.ir = 0x50 + (5 * self.v[x] as u16); // addres to digit x
+            }
+            Op::LdB(x) => {
+                self.check_memory(self.ir, 3)?;
+                let mut value = self.v[x];
+                self.store(self.ir + 2, value % 10);
+                value /= 10;
+                self.store(self.ir + 1, value % 10);
+                value /= 10;
+                self.store(self.ir, value % 10);
+            }
+            Op::LdIVx(x) => {
+                self.check_memory(self.ir, x + 1)?;
+                for i in 0..=x {
+                    self.store(self.ir + i as u16, self.v[i]);
+                }
+                self.advance_ir(x);
+            }
+            Op::LdVxI(x) => {
+                self.check_memory(self.ir, x + 1)?;
+                for i in 0..=x {
+                    self.v[i] = self.load(self.ir as usize + i);
+                }
+                self.advance_ir(x);
+            }
+            Op::Undefined => {
                 return Err(Chip8Errors::UndefinedInstruction);
             }
         }
@@ -428,29 +1097,28 @@ impl Chip8 {
 
 impl Default for Chip8 {
     fn default() -> Self {
-        return Self {
-            pc: 0x200,
-            ir: 0,
-            ram: Default::default(),
-            v: [0; 16],
-            display: [false; 2048],
-            stack: Stack::with_capacity::<16>(),
-            keys: [0; 16],
-            delay_timer: 0,
-            soud_timer: 0,
-            stdin: NonblockingBufReader::new(std::io::stdin()),
-            outbuff: stdout().into_raw_mode().unwrap(),
-        };
+        return Self::with_terminal(
+            Some(NonblockingBufReader::new(std::io::stdin())),
+            Some(stdout().into_raw_mode().unwrap()),
+        );
     }
 }
 
 impl Restart for Chip8 {
     fn restart(&mut self) -> () {
-        self.pc = 0x200;
+        self.pc = self.platform.load_address();
         self.ir = 0;
         self.ram.restart();
         self.v = [0; 16];
-        self.display = [false; 2048];
-        self.stack = Stack::with_capacity::<16>();
+        self.display = Framebuffer::lores();
+        self.stack.clear();
+        self.rng.restart();
+        self.recent_writes.clear();
+        self.recent_warnings.clear();
+        self.last_sprite = None;
+        self.waiting_key = None;
+        if let Some(cache) = self.cache.as_mut() {
+            cache.clear();
+        }
     }
 }
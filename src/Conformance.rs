@@ -0,0 +1,233 @@
+use crate::Chip8::{Chip8, Config};
+use crate::Quirks::Quirks;
+use crate::Rom::Platform;
+use crate::Screenshot::{Frame, Screenshot};
+use crate::Script::InputScript;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// A platform the suite runs on: the chip-8-database platform id, which
+/// gives the quirks, and the machine it runs on.
+pub struct Profile {
+    pub id: &'static str,
+    pub platform: Platform,
+}
+
+impl Profile {
+    pub fn quirks(&self) -> Quirks {
+        return Quirks::for_platform(self.id).unwrap();
+    }
+}
+
+pub const PROFILES: [Profile; 4] = [
+    Profile {
+        id: "originalChip8",
+        platform: Platform::Chip8,
+    },
+    Profile {
+        id: "modernChip8",
+        platform: Platform::Chip8,
+    },
+    Profile {
+        id: "superchip",
+        platform: Platform::Schip,
+    },
+    Profile {
+        id: "xochip",
+        platform: Platform::XoChip,
+    },
+];
+
+/// One screen of Timendus' CHIP-8 test suite: a ROM run for `frames`
+/// frames with scripted keys, after which the screen is compared with the
+/// golden one.
+pub struct Case {
+    pub name: &'static str,
+    pub rom: &'static str,
+    pub frames: usize,
+    /// The input script on a profile, `None` where the case doesn't run.
+    pub keys: fn(&Profile) -> Option<&'static str>,
+}
+
+fn no_keys(_: &Profile) -> Option<&'static str> {
+    return Some("");
+}
+
+/// Picks CHIP-8 in the menu, which judges the quirks against the original
+/// interpreter: `originalChip8` passes every check and `modernChip8` fails
+/// the vF reset and memory ones, as it should. The SUPER-CHIP and XO-CHIP
+/// targets start by switching to hires with `00FF`, which krhip8 doesn't
+/// have.
+fn quirks_menu(profile: &Profile) -> Option<&'static str> {
+    return match profile.id {
+        "originalChip8" | "modernChip8" => Some("frame 60: press 1; frame 70: release 1"),
+        _ => None,
+    };
+}
+
+/// Holds 5 and A down, which light up.
+fn keypad_down(_: &Profile) -> Option<&'static str> {
+    return Some("frame 60: press 1; frame 70: release 1; frame 120: press 5; frame 120: press A");
+}
+
+/// Holds 5 down, every key but that one lights up.
+fn keypad_up(_: &Profile) -> Option<&'static str> {
+    return Some("frame 60: press 2; frame 70: release 2; frame 120: press 5");
+}
+
+/// Presses and releases 7.
+fn keypad_get_key(_: &Profile) -> Option<&'static str> {
+    return Some("frame 60: press 3; frame 70: release 3; frame 120: press 7; frame 130: release 7");
+}
+
+/// The suite in its order, from the ROMs in the tree. `1-chip8-logo`,
+/// `2-ibm-logo` and `7-beep` aren't vendored, so they have no cases or
+/// goldens. `8-scrolling` is left out, it needs hires and the SUPER-CHIP
+/// and XO-CHIP scroll instructions, which krhip8 doesn't run.
+pub const SUITE: [Case; 6] = [
+    Case {
+        name: "3-corax+",
+        rom: "3-corax+.ch8",
+        frames: 100,
+        keys: no_keys,
+    },
+    Case {
+        name: "4-flags",
+        rom: "4-flags.ch8",
+        frames: 100,
+        keys: no_keys,
+    },
+    Case {
+        name: "5-quirks",
+        rom: "5-quirks.ch8",
+        frames: 600,
+        keys: quirks_menu,
+    },
+    Case {
+        name: "6-keypad-down",
+        rom: "6-keypad.ch8",
+        frames: 200,
+        keys: keypad_down,
+    },
+    Case {
+        name: "6-keypad-up",
+        rom: "6-keypad.ch8",
+        frames: 200,
+        keys: keypad_up,
+    },
+    Case {
+        name: "6-keypad-get-key",
+        rom: "6-keypad.ch8",
+        frames: 200,
+        keys: keypad_get_key,
+    },
+];
+
+/// How a case went.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Pass,
+    /// The golden was written from this run.
+    Updated,
+    /// The ROM isn't there, the case didn't run.
+    NoRom,
+    NoGolden,
+    /// The screen isn't the golden one, `expected` and `actual` are as
+    /// written by `text`.
+    Differs {
+        expected: String,
+        actual: String,
+    },
+    /// The script didn't parse or the program hit an error.
+    Failed(String),
+}
+
+impl Outcome {
+    pub fn failed(&self) -> bool {
+        return !matches!(self, Outcome::Pass | Outcome::Updated | Outcome::NoRom);
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            Outcome::Pass => write!(f, "ok"),
+            Outcome::Updated => write!(f, "golden updated"),
+            Outcome::NoRom => write!(f, "skipped, no rom"),
+            Outcome::NoGolden => write!(f, "no golden, run with --update to record one"),
+            Outcome::Differs { expected, actual } => {
+                let row = expected
+                    .lines()
+                    .zip(actual.lines())
+                    .position(|(a, b)| a != b)
+                    .unwrap_or(0);
+                write!(f, "screen differs from the golden from row {}", row)
+            }
+            Outcome::Failed(err) => write!(f, "{}", err),
+        };
+    }
+}
+
+/// A screen as text, a line per row with `.` for pixels that are off,
+/// `#` for the first plane, `o` for the second and `@` for both.
+pub fn text(frame: &Frame) -> String {
+    let mut out = String::with_capacity((frame.width + 1) * frame.height);
+    for y in 0..frame.height {
+        for x in 0..frame.width {
+            out.push(['.', '#', 'o', '@'][frame.get(x, y) as usize & 3]);
+        }
+        out.push('\n');
+    }
+    return out;
+}
+
+/// Where the golden screen of `case` on `profile` is kept in `goldens`.
+pub fn golden_path(goldens: &Path, case: &Case, profile: &Profile) -> PathBuf {
+    return goldens.join(format!("{}.{}.txt", case.name, profile.id));
+}
+
+/// Runs `rom` as `case` on `profile` and returns the last screen.
+pub fn run(case: &Case, profile: &Profile, rom: &[u8]) -> Result<Frame, String> {
+    let keys = (case.keys)(profile).unwrap_or("");
+    let script = InputScript::parse(keys).map_err(|err| err.to_string())?;
+    let mut chip8 = Chip8::new(Config {
+        platform: profile.platform,
+        quirks: profile.quirks(),
+        seed: Some(0),
+        ..Default::default()
+    });
+    chip8.load_rom(rom).map_err(|err| err.to_string())?;
+    let screenshot = Screenshot::default();
+    for frame in 0..case.frames {
+        script
+            .apply(frame, &mut chip8, &screenshot)
+            .map_err(|err| err.to_string())?;
+        chip8.run_frame().map_err(|err| format!("frame {}: {}", frame, err))?;
+    }
+    return Ok(Frame::from_chip8(&chip8));
+}
+
+/// Runs `case` on `profile` with its ROM from `roms` and compares the
+/// screen with the golden one in `goldens`, or with `update` writes the
+/// golden instead.
+pub fn check(case: &Case, profile: &Profile, roms: &Path, goldens: &Path, update: bool) -> Outcome {
+    let Ok(rom) = std::fs::read(roms.join(case.rom)) else {
+        return Outcome::NoRom;
+    };
+    let actual = match run(case, profile, &rom) {
+        Ok(frame) => text(&frame),
+        Err(err) => return Outcome::Failed(err),
+    };
+    let path = golden_path(goldens, case, profile);
+    if update {
+        return match std::fs::write(&path, actual) {
+            Ok(()) => Outcome::Updated,
+            Err(err) => Outcome::Failed(format!("{}: {}", path.display(), err)),
+        };
+    }
+    return match std::fs::read_to_string(&path) {
+        Ok(expected) if expected == actual => Outcome::Pass,
+        Ok(expected) => Outcome::Differs { expected, actual },
+        Err(_) => Outcome::NoGolden,
+    };
+}
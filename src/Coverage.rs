@@ -0,0 +1,259 @@
+use crate::Cfg::{length, Cfg, Flow};
+use crate::Helpers::disassemble;
+use crate::Op::Op;
+use crate::Ram::Ram;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// The instruction kinds `exec` handles, in its order, with the `Op` each
+/// stands for.
+const KINDS: [&str; 34] = [
+    "00E0", "00EE", "1NNN", "2NNN", "3XKK", "4XKK", "5XY0", "6XKK", "7XKK", "8XY0", "8XY1", "8XY2", "8XY3", "8XY4",
+    "8XY5", "8XY6", "8XY7", "8XYE", "9XY0", "ANNN", "BNNN", "CXKK", "DXYN", "EX9E", "EXA1", "FX07", "FX0A", "FX15",
+    "FX18", "FX1E", "FX29", "FX33", "FX55", "FX65",
+];
+
+fn kind(op: Op) -> Option<usize> {
+    return Some(match op {
+        Op::Cls => 0,
+        Op::Ret => 1,
+        Op::Jp(_) => 2,
+        Op::Call(_) => 3,
+        Op::SeByte(..) => 4,
+        Op::SneByte(..) => 5,
+        Op::SeReg(..) => 6,
+        Op::LdByte(..) => 7,
+        Op::AddByte(..) => 8,
+        Op::LdReg(..) => 9,
+        Op::Or(..) => 10,
+        Op::And(..) => 11,
+        Op::Xor(..) => 12,
+        Op::AddReg(..) => 13,
+        Op::Sub(..) => 14,
+        Op::Shr(..) => 15,
+        Op::Subn(..) => 16,
+        Op::Shl(..) => 17,
+        Op::SneReg(..) => 18,
+        Op::LdI(_) => 19,
+        Op::JpV0(..) => 20,
+        Op::Rnd(..) => 21,
+        Op::Drw(..) => 22,
+        Op::Skp(_) => 23,
+        Op::Sknp(_) => 24,
+        Op::LdVxDt(_) => 25,
+        Op::LdVxK(_) => 26,
+        Op::LdDtVx(_) => 27,
+        Op::LdStVx(_) => 28,
+        Op::AddI(_) => 29,
+        Op::LdF(_) => 30,
+        Op::LdB(_) => 31,
+        Op::LdIVx(_) => 32,
+        Op::LdVxI(_) => 33,
+        Op::Undefined => return None,
+    });
+}
+
+/// Which instructions of a ROM ran, for checking that a test ROM exercises
+/// what it is meant to.
+///
+/// Instructions are the ones `Cfg` finds reachable in the ROM as loaded,
+/// plus whatever else ran. Skips (`3XKK`, `4XKK`, `5XYN`, `9XYN`, `EX9E`,
+/// `EXA1`, see `Flow::Skip`) count as two branches, taken and not taken,
+/// each covered once it happened.
+pub struct Coverage {
+    /// Executions by address.
+    counts: Vec<u64>,
+    /// Times each skip instruction skipped and didn't.
+    skips: BTreeMap<u16, (u64, u64)>,
+    /// Executions of every `KINDS` entry.
+    kinds: [u64; KINDS.len()],
+    /// Reachable instructions by address.
+    instructions: BTreeMap<u16, u16>,
+}
+
+impl Coverage {
+    /// Coverage of the program loaded at `load_address` in `memory`.
+    pub fn new(memory: &[u8], load_address: u16) -> Self {
+        let rom = &memory[(load_address as usize).min(memory.len())..];
+        return Coverage {
+            counts: vec![0; memory.len()],
+            skips: BTreeMap::new(),
+            kinds: [0; KINDS.len()],
+            instructions: Cfg::build(rom, load_address).instructions,
+        };
+    }
+
+    /// Counts the instruction `opcode` at `pc`, after which execution went
+    /// on at `next`.
+    pub fn record(&mut self, pc: u16, opcode: u16, next: u16) -> () {
+        self.counts[pc as usize] += 1;
+        if let Some(kind) = kind(Op::decode(opcode)) {
+            self.kinds[kind] += 1;
+        }
+        if Flow::of(opcode) == Flow::Skip {
+            let skips = self.skips.entry(pc).or_insert((0, 0));
+            if next == pc.wrapping_add(2) {
+                skips.1 += 1;
+            } else {
+                skips.0 += 1;
+            }
+        }
+    }
+
+    /// Every instruction reachable in the ROM as loaded, run, or skipped
+    /// over or to by a skip that ran, which catches code the program wrote
+    /// itself. Opcodes are as loaded for the first and as they are now in
+    /// `ram` for the others.
+    fn instructions(&self, ram: &Ram) -> BTreeMap<u16, u16> {
+        let fetch = |address: usize| -> Option<u16> {
+            if address + 1 >= ram.size() {
+                return None;
+            }
+            return Some((ram[address] as u16) << 8 | ram[address + 1] as u16);
+        };
+        let mut instructions = self.instructions.clone();
+        for (address, count) in self.counts.iter().enumerate() {
+            if *count > 0 {
+                if let Some(opcode) = fetch(address) {
+                    instructions.insert(address as u16, opcode);
+                }
+            }
+        }
+        for address in self.skips.keys() {
+            let next = *address as usize + 2;
+            if let Some(opcode) = fetch(next) {
+                instructions.entry(next as u16).or_insert(opcode);
+                let skipped = next + length(opcode) as usize;
+                if let Some(opcode) = fetch(skipped) {
+                    instructions.entry(skipped as u16).or_insert(opcode);
+                }
+            }
+        }
+        return instructions;
+    }
+
+    /// Instructions run and all instructions, skip branches taken and all
+    /// skip branches.
+    pub fn totals(&self, ram: &Ram) -> (usize, usize, usize, usize) {
+        let instructions = self.instructions(ram);
+        let ran = instructions
+            .keys()
+            .filter(|address| self.counts[**address as usize] > 0)
+            .count();
+        let skips: Vec<u16> = instructions
+            .iter()
+            .filter(|(_, opcode)| Flow::of(**opcode) == Flow::Skip)
+            .map(|(address, _)| *address)
+            .collect();
+        let covered = skips
+            .iter()
+            .map(|address| {
+                let (taken, not_taken) = self.skips.get(address).copied().unwrap_or((0, 0));
+                (taken > 0) as usize + (not_taken > 0) as usize
+            })
+            .sum();
+        return (ran, instructions.len(), covered, 2 * skips.len());
+    }
+
+    /// A disassembly of every instruction marking what ran: `!` in front
+    /// of instructions that never ran and of skips that only went one way,
+    /// then the instruction kinds that never ran.
+    pub fn annotated(&self, ram: &Ram) -> String {
+        let (ran, total, covered, branches) = self.totals(ram);
+        let percent = |part: usize, all: usize| 100.0 * part as f64 / all.max(1) as f64;
+        let mut out = String::new();
+        writeln!(
+            out,
+            "{} of {} instructions ({:.1}%), {} of {} skip branches ({:.1}%)",
+            ran,
+            total,
+            percent(ran, total),
+            covered,
+            branches,
+            percent(covered, branches)
+        )
+        .unwrap();
+
+        let mut next = None;
+        for (address, opcode) in self.instructions(ram) {
+            if next.is_some_and(|next| next != address) {
+                writeln!(out).unwrap();
+            }
+            next = Some(address.wrapping_add(length(opcode)));
+
+            let count = self.counts[address as usize];
+            let mut note = match count {
+                0 => "never ran".to_string(),
+                count => format!("ran {}x", count),
+            };
+            let mut missed = count == 0;
+            if Flow::of(opcode) == Flow::Skip && count > 0 {
+                let (taken, not_taken) = self.skips.get(&address).copied().unwrap_or((0, 0));
+                note.push_str(&format!(", skipped {}x, went on {}x", taken, not_taken));
+                missed = taken == 0 || not_taken == 0;
+            }
+            writeln!(
+                out,
+                "{} {:#06x}  {:04X}  {:<18} {}",
+                if missed { '!' } else { ' ' },
+                address,
+                opcode,
+                disassemble(opcode),
+                note
+            )
+            .unwrap();
+        }
+
+        let unused: Vec<&str> = KINDS
+            .iter()
+            .zip(self.kinds.iter())
+            .filter(|(_, count)| **count == 0)
+            .map(|(kind, _)| *kind)
+            .collect();
+        writeln!(
+            out,
+            "\ninstruction kinds never run: {}",
+            if unused.is_empty() {
+                "none".to_string()
+            } else {
+                unused.join(" ")
+            }
+        )
+        .unwrap();
+        return out;
+    }
+
+    /// The coverage as an LCOV tracefile for `source`, with addresses as
+    /// line numbers and the two ways of every skip as a branch pair.
+    pub fn lcov(&self, ram: &Ram, source: &str) -> String {
+        let mut out = String::new();
+        writeln!(out, "TN:krhip8").unwrap();
+        writeln!(out, "SF:{}", source).unwrap();
+        let instructions = self.instructions(ram);
+        for address in instructions.keys() {
+            writeln!(out, "DA:{},{}", address, self.counts[*address as usize]).unwrap();
+        }
+        let (ran, total, covered, branches) = self.totals(ram);
+        for (address, opcode) in instructions.iter() {
+            if Flow::of(*opcode) != Flow::Skip {
+                continue;
+            }
+            let hits = |count: u64| {
+                if self.counts[*address as usize] == 0 {
+                    "-".to_string()
+                } else {
+                    count.to_string()
+                }
+            };
+            let (taken, not_taken) = self.skips.get(address).copied().unwrap_or((0, 0));
+            writeln!(out, "BRDA:{},0,0,{}", address, hits(taken)).unwrap();
+            writeln!(out, "BRDA:{},0,1,{}", address, hits(not_taken)).unwrap();
+        }
+        writeln!(out, "BRF:{}", branches).unwrap();
+        writeln!(out, "BRH:{}", covered).unwrap();
+        writeln!(out, "LF:{}", total).unwrap();
+        writeln!(out, "LH:{}", ran).unwrap();
+        writeln!(out, "end_of_record").unwrap();
+        return out;
+    }
+}
@@ -0,0 +1,153 @@
+use crate::Quirks::Quirks;
+use crate::Rom::Platform;
+use crate::Screenshot::Palette;
+use serde_json::Value;
+use std::path::Path;
+
+/// What the database knows about one ROM.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RomInfo {
+    pub title: String,
+    pub authors: Vec<String>,
+    /// Platform id as used by the database, e.g. `originalChip8`.
+    pub platform_id: Option<String>,
+    pub platform: Option<Platform>,
+    pub quirks: Option<Quirks>,
+    /// Instructions per frame.
+    pub tickrate: Option<usize>,
+    /// Named buttons to CHIP-8 keys, e.g. `up` to 5.
+    pub keys: Vec<(String, u8)>,
+    pub colours: Option<Palette>,
+}
+
+/// ROM metadata in the format of the community chip-8-database
+/// (https://github.com/chip-8/chip-8-database): `sha1-hashes.json` maps
+/// hashes to an index into `programs.json`, and `platforms.json` describes
+/// the quirks and tick rate of each platform.
+pub struct Database {
+    hashes: Value,
+    programs: Value,
+    platforms: Value,
+}
+
+/// SHA-1 of a ROM image as lowercase hex, the key used by the database.
+pub fn sha1_hex(rom: &[u8]) -> String {
+    return sha1_smol::Sha1::from(rom).digest().to_string();
+}
+
+fn parse(name: &str, text: &str) -> std::io::Result<Value> {
+    return serde_json::from_str(text).map_err(|err| {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}: {}", name, err))
+    });
+}
+
+impl Database {
+    /// The small database shipped with krhip8, covering the ROMs in this
+    /// repository. It has no `platforms.json`, the built in quirks are used.
+    pub fn bundled() -> Self {
+        return Database {
+            hashes: parse("sha1-hashes.json", include_str!("../db/sha1-hashes.json")).unwrap(),
+            programs: parse("programs.json", include_str!("../db/programs.json")).unwrap(),
+            platforms: Value::Array(Vec::new()),
+        };
+    }
+
+    /// Reads a checkout of the community database. `platforms.json` is
+    /// optional, known platforms fall back to built in quirks.
+    pub fn load(dir: &str) -> std::io::Result<Self> {
+        let dir = Path::new(dir);
+        let read = |name: &str| std::fs::read_to_string(dir.join(name));
+        let platforms = match read("platforms.json") {
+            Ok(text) => parse("platforms.json", &text)?,
+            Err(_) => Value::Array(Vec::new()),
+        };
+        return Ok(Database {
+            hashes: parse("sha1-hashes.json", &read("sha1-hashes.json")?)?,
+            programs: parse("programs.json", &read("programs.json")?)?,
+            platforms,
+        });
+    }
+
+    pub fn lookup(&self, rom: &[u8]) -> Option<RomInfo> {
+        let hash = sha1_hex(rom);
+        let program = &self.programs[self.hashes.get(&hash)?.as_u64()? as usize];
+        let entry = &program["roms"][&hash];
+
+        let platform_id = entry["platforms"][0].as_str().map(str::to_string);
+        let platform_info = self
+            .platforms
+            .as_array()
+            .and_then(|platforms| platforms.iter().find(|p| p["id"].as_str() == platform_id.as_deref()));
+
+        let mut quirks = platform_id.as_deref().and_then(|id| self.platform_quirks(id));
+        if let (Some(quirks), Some(id)) = (quirks.as_mut(), platform_id.as_deref()) {
+            if let Some(overrides) = entry["quirkyPlatforms"][id].as_object() {
+                for (name, value) in overrides {
+                    quirks.set(name, value.as_bool().unwrap_or(false));
+                }
+            }
+        }
+
+        let tickrate = entry["tickrate"]
+            .as_u64()
+            .or_else(|| platform_info.and_then(|p| p["defaultTickrate"].as_u64()))
+            .map(|rate| rate as usize);
+
+        let keys = entry["keys"]
+            .as_object()
+            .map(|keys| {
+                keys.iter()
+                    .filter_map(|(name, key)| Some((name.clone(), key.as_u64()? as u8)))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let colours = entry["colors"]["pixels"].as_array().and_then(|pixels| {
+            let list: Vec<&str> = pixels.iter().filter_map(Value::as_str).collect();
+            Palette::parse(&list.join(","))
+        });
+
+        return Some(RomInfo {
+            title: program["title"].as_str().unwrap_or("").to_string(),
+            authors: program["authors"]
+                .as_array()
+                .map(|a| a.iter().filter_map(|n| Some(n.as_str()?.to_string())).collect())
+                .unwrap_or_default(),
+            platform: platform_id.as_deref().and_then(platform_from_id),
+            platform_id,
+            quirks,
+            tickrate,
+            keys,
+            colours,
+        });
+    }
+
+    fn platform_quirks(&self, id: &str) -> Option<Quirks> {
+        let listed = self
+            .platforms
+            .as_array()
+            .and_then(|platforms| platforms.iter().find(|p| p["id"].as_str() == Some(id)))
+            .and_then(|p| p["quirks"].as_object());
+
+        return match listed {
+            Some(listed) => {
+                let mut quirks = Quirks::for_platform("modernChip8").unwrap();
+                for (name, value) in listed {
+                    quirks.set(name, value.as_bool().unwrap_or(false));
+                }
+                Some(quirks)
+            }
+            None => Quirks::for_platform(id),
+        };
+    }
+}
+
+/// Maps the database platform ids onto the platforms krhip8 knows.
+pub fn platform_from_id(id: &str) -> Option<Platform> {
+    return match id {
+        "originalChip8" | "hybridVIP" | "modernChip8" | "chip8x" => Some(Platform::Chip8),
+        "chip48" | "superchip1" | "superchip" | "megachip8" => Some(Platform::Schip),
+        "xochip" => Some(Platform::XoChip),
+        _ => None,
+    };
+}
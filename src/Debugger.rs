@@ -0,0 +1,668 @@
+use crate::Breakpoints::Breakpoints;
+use crate::Chip8::{Chip8, Chip8Errors, StackFrame};
+use crate::Helpers::disassemble;
+use crate::Ram::{self, printable};
+use crate::Sprites::{Sprite, SpriteShape, FONT_ADDRESS};
+use std::io::Write;
+use std::thread::sleep;
+use std::time::Duration;
+
+/// Terminal row the debugger starts at, just below the display.
+const TOP: u16 = 35;
+/// Rows of memory shown at once.
+const ROWS: u16 = 16;
+
+/// A key as the debugger understands it, with the escape sequences of the
+/// arrow and page keys decoded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Key {
+    Char(u8),
+    Up,
+    Down,
+    Left,
+    Right,
+    PageUp,
+    PageDown,
+    Enter,
+    Escape,
+}
+
+impl Key {
+    /// Decodes a key starting with `first`, reading the rest of an escape
+    /// sequence from `next`.
+    pub fn decode(first: u8, mut next: impl FnMut() -> Option<u8>) -> Self {
+        return match first {
+            b'\r' | b'\n' => Key::Enter,
+            27 => match next() {
+                Some(b'[') => match next() {
+                    Some(b'A') => Key::Up,
+                    Some(b'B') => Key::Down,
+                    Some(b'C') => Key::Right,
+                    Some(b'D') => Key::Left,
+                    Some(b'5') => {
+                        next();
+                        Key::PageUp
+                    }
+                    Some(b'6') => {
+                        next();
+                        Key::PageDown
+                    }
+                    _ => Key::Escape,
+                },
+                _ => Key::Escape,
+            },
+            other => Key::Char(other),
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pane {
+    Memory,
+    Sprites,
+    Stack,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Mode {
+    Normal,
+    /// Typing hex into the byte under the cursor, `high` holds the first
+    /// nibble once typed.
+    Edit { high: Option<u8> },
+    /// Typing an address to jump to.
+    Goto(String),
+    /// Typing a breakpoint.
+    Break(String),
+}
+
+/// The interactive debugger drawn below the display in the terminal. While
+/// it is paused it owns the keyboard: the game gets no key presses and
+/// nothing runs unless stepped.
+///
+/// The memory pane shows 16 bytes per row with their ASCII, and next to
+/// that the 16 bytes from the cursor on as an 8 pixel wide sprite. The
+/// bytes at `pc` are green, the byte at `I` blue and recently written
+/// bytes yellow.
+///
+/// The sprite pane draws memory from the cursor on as a row of sprites, 8xN
+/// or SCHIP 16x16, and marks the one the last `DXYN` drew.
+///
+/// The stack pane lists the subroutines being run, innermost first, each
+/// named after the routine with the offset into it, `sub_0300+0x12`.
+///
+/// While running, `run_cycles` checks the breakpoints (see
+/// `Breakpoint::parse`) and pauses on the first that stops.
+pub struct Debugger {
+    pub paused: bool,
+    pub pane: Pane,
+    pub breakpoints: Breakpoints,
+    /// Just resumed: the condition breakpoints that stopped at `pc` must not
+    /// stop again before the instruction there runs.
+    resuming: bool,
+    /// Shape of the sprites in the sprite pane.
+    shape: SpriteShape,
+    cursor: u16,
+    top: u16,
+    /// Selected line of the stack pane.
+    frame: usize,
+    mode: Mode,
+    message: String,
+}
+
+/// `sub_0300+0x12`, `program+0x4`, or just `?` when the call that led
+/// there is gone. Addresses before the routine start get no offset.
+fn frame_label(frame: &StackFrame, program: bool) -> String {
+    let Some(routine) = frame.routine else {
+        return "?".to_string();
+    };
+    let name = if program { "program".to_string() } else { format!("sub_{:04x}", routine) };
+    return match frame.address.checked_sub(routine) {
+        Some(0) => name,
+        Some(offset) if offset < 0x1000 => format!("{}+{:#x}", name, offset),
+        _ => name,
+    };
+}
+
+/// ` rw-`-style access bits of a tracked byte, `?` before it's set.
+fn access_label(access: u8) -> String {
+    let bit = |bit: u8, c: char| if access & bit != 0 { c } else { '-' };
+    let mut label = format!(" {}{}{}", bit(Ram::READ, 'r'), bit(Ram::WRITE, 'w'), bit(Ram::EXECUTE, 'x'));
+    if access & Ram::INITIALIZED == 0 {
+        label.push('?');
+    }
+    return label;
+}
+
+fn hex_digit(key: u8) -> Option<u8> {
+    return (key as char).to_digit(16).map(|digit| digit as u8);
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        return Debugger {
+            paused: false,
+            pane: Pane::Memory,
+            breakpoints: Breakpoints::new(),
+            resuming: false,
+            shape: SpriteShape::Lores { height: 8 },
+            cursor: 0x200,
+            top: 0x200,
+            frame: 0,
+            mode: Mode::Normal,
+            message: String::new(),
+        };
+    }
+
+    /// Pauses and puts the cursor on the next instruction.
+    pub fn pause(&mut self, chip8: &Chip8) -> () {
+        self.paused = true;
+        self.mode = Mode::Normal;
+        self.frame = 0;
+        self.move_to(chip8.pc(), chip8);
+    }
+
+    /// Runs up to `cycles` instructions like `Chip8::run_cycles`, but with
+    /// breakpoints set goes one instruction at a time and pauses on the
+    /// first breakpoint that stops. Errors a breakpoint catches pause too,
+    /// with `pc` still at the failed instruction so resuming runs it again,
+    /// others are returned.
+    pub fn run_cycles(&mut self, chip8: &mut Chip8, cycles: usize) -> Result<(), Chip8Errors> {
+        if self.breakpoints.is_empty() {
+            self.resuming = false;
+            return chip8.run_cycles(cycles);
+        }
+        for _ in 0..cycles {
+            if chip8.quit {
+                break;
+            }
+            if !std::mem::take(&mut self.resuming) {
+                if let Some(bp) = self.breakpoints.before(chip8) {
+                    let message = format!("break {}", bp);
+                    self.stop(chip8, message);
+                    return Ok(());
+                }
+            }
+            let pc = chip8.pc();
+            let opcode = chip8.ram.opcode(pc as usize).unwrap_or(0);
+            let warnings = chip8.warning_count();
+            if let Err(err) = chip8.step() {
+                match self.breakpoints.error(&err) {
+                    Some(bp) => {
+                        // failed instructions change nothing but `pc`
+                        chip8.set_pc(pc);
+                        let message = format!("{:04X} at {:#06x}: {}, break {}", opcode, pc, err, bp);
+                        self.stop(chip8, message);
+                        return Ok(());
+                    }
+                    None => return Err(err),
+                }
+            }
+            if let Some(bp) = self.breakpoints.after(chip8, opcode) {
+                let message = format!("{:04X} at {:#06x}, break {}", opcode, pc, bp);
+                self.stop(chip8, message);
+                return Ok(());
+            }
+            let recent = chip8.recent_warnings();
+            let new = (chip8.warning_count() - warnings) as usize;
+            let mut stop = None;
+            for warning in recent.iter().skip(recent.len().saturating_sub(new)) {
+                if let Some(bp) = self.breakpoints.warning(warning) {
+                    stop = stop.or(Some(format!("{}, break {}", warning, bp)));
+                }
+            }
+            if let Some(message) = stop {
+                self.stop(chip8, message);
+                return Ok(());
+            }
+        }
+        return Ok(());
+    }
+
+    fn stop(&mut self, chip8: &Chip8, message: String) -> () {
+        self.pause(chip8);
+        self.message = message;
+    }
+
+    /// Shows the debugger and handles keys until the user resumes or quits.
+    /// Without a terminal it only prints why it stopped and carries on.
+    pub fn run(&mut self, chip8: &mut Chip8) -> () {
+        if chip8.outbuff.is_none() {
+            if !self.message.is_empty() {
+                println!("{}", self.message);
+                self.message.clear();
+            }
+            self.paused = false;
+            self.resuming = true;
+            return;
+        }
+        self.draw(chip8);
+        while self.paused && !chip8.quit {
+            let Some(first) = chip8.read_key() else {
+                sleep(Duration::from_millis(10));
+                continue;
+            };
+            let key = Key::decode(first, || chip8.read_key());
+            self.handle(key, chip8);
+            chip8.render();
+            self.draw(chip8);
+        }
+        self.clear(chip8);
+    }
+
+    /// Reacts to one key, returns whether it was used.
+    pub fn handle(&mut self, key: Key, chip8: &mut Chip8) -> bool {
+        self.message.clear();
+        match self.mode.clone() {
+            Mode::Normal => return self.handle_normal(key, chip8),
+            Mode::Edit { high } => match key {
+                Key::Char(c) if hex_digit(c).is_some() => {
+                    let digit = hex_digit(c).unwrap();
+                    match high {
+                        None => self.mode = Mode::Edit { high: Some(digit) },
+                        Some(high) => {
+                            if let Err(err) = chip8.write_ram(self.cursor, high << 4 | digit) {
+                                self.message = err.to_string();
+                            }
+                            self.mode = Mode::Edit { high: None };
+                            self.move_to(self.cursor.saturating_add(1), chip8);
+                        }
+                    }
+                }
+                Key::Escape | Key::Enter => self.mode = Mode::Normal,
+                other => return self.handle_cursor(other, chip8),
+            },
+            Mode::Goto(mut typed) => match key {
+                Key::Char(8) | Key::Char(127) => {
+                    typed.pop();
+                    self.mode = Mode::Goto(typed);
+                }
+                Key::Char(c) if c.is_ascii_alphanumeric() => {
+                    typed.push(c as char);
+                    self.mode = Mode::Goto(typed);
+                }
+                Key::Enter => {
+                    self.mode = Mode::Normal;
+                    match self.parse_address(&typed, chip8) {
+                        Some(address) => self.move_to(address, chip8),
+                        None => self.message = format!("no address {}", typed),
+                    }
+                }
+                Key::Escape => self.mode = Mode::Normal,
+                _ => return false,
+            },
+            Mode::Break(mut typed) => match key {
+                Key::Char(8) | Key::Char(127) => {
+                    typed.pop();
+                    self.mode = Mode::Break(typed);
+                }
+                Key::Char(c) if (b' '..=b'~').contains(&c) => {
+                    typed.push(c as char);
+                    self.mode = Mode::Break(typed);
+                }
+                Key::Enter => {
+                    self.mode = Mode::Normal;
+                    self.message = match self.breakpoints.add(&typed) {
+                        Ok(()) => format!("breakpoint {} set", self.breakpoints.len()),
+                        Err(err) => err.to_string(),
+                    };
+                }
+                Key::Escape => self.mode = Mode::Normal,
+                _ => return false,
+            },
+        }
+        return true;
+    }
+
+    fn handle_normal(&mut self, key: Key, chip8: &mut Chip8) -> bool {
+        match key {
+            Key::Char(b' ') => {
+                self.paused = false;
+                self.resuming = true;
+            }
+            Key::Char(3) | Key::Char(4) => chip8.quit = true,
+            Key::Char(b'n') => {
+                if let Err(err) = chip8.step() {
+                    self.message = err.to_string();
+                }
+                self.move_to(chip8.pc(), chip8);
+            }
+            Key::Char(b'g') => self.mode = Mode::Goto(String::new()),
+            Key::Char(b'b') => self.mode = Mode::Break(String::new()),
+            Key::Char(b'c') => {
+                self.breakpoints.clear();
+                self.message = "breakpoints cleared".to_string();
+            }
+            Key::Char(b'\t') => {
+                self.pane = match self.pane {
+                    Pane::Memory => Pane::Sprites,
+                    Pane::Sprites => Pane::Stack,
+                    Pane::Stack => Pane::Memory,
+                }
+            }
+            other if self.pane == Pane::Sprites => return self.handle_sprites(other, chip8),
+            other if self.pane == Pane::Stack => return self.handle_stack(other, chip8),
+            Key::Char(b'e') | Key::Char(b'i') => self.mode = Mode::Edit { high: None },
+            other => return self.handle_cursor(other, chip8),
+        }
+        return true;
+    }
+
+    fn handle_sprites(&mut self, key: Key, chip8: &Chip8) -> bool {
+        let size = self.shape.bytes() as u16;
+        match key {
+            Key::Left | Key::Char(b'h') => self.move_to(self.cursor.saturating_sub(size), chip8),
+            Key::Right | Key::Char(b'l') => self.move_to(self.cursor.saturating_add(size), chip8),
+            Key::Up | Key::Char(b'k') => self.move_to(self.cursor.saturating_sub(1), chip8),
+            Key::Down | Key::Char(b'j') => self.move_to(self.cursor.saturating_add(1), chip8),
+            Key::Char(b'+') | Key::Char(b'-') => {
+                if let SpriteShape::Lores { height } = self.shape {
+                    let height = if key == Key::Char(b'+') { height + 1 } else { height - 1 };
+                    self.shape = SpriteShape::Lores {
+                        height: height.clamp(1, 15),
+                    };
+                }
+            }
+            Key::Char(b'w') => {
+                self.shape = match self.shape {
+                    SpriteShape::Lores { .. } => SpriteShape::Hires,
+                    SpriteShape::Hires => SpriteShape::Lores { height: 8 },
+                }
+            }
+            Key::Char(b'f') => {
+                self.shape = SpriteShape::Lores { height: 5 };
+                self.move_to(FONT_ADDRESS, chip8);
+            }
+            Key::Char(b'd') => match chip8.last_sprite() {
+                Some((address, height)) => {
+                    self.shape = SpriteShape::Lores {
+                        height: height.max(1),
+                    };
+                    self.move_to(address, chip8);
+                }
+                None => self.message = "nothing drawn yet".to_string(),
+            },
+            _ => return false,
+        }
+        return true;
+    }
+
+    fn handle_stack(&mut self, key: Key, chip8: &Chip8) -> bool {
+        let frames = chip8.call_stack();
+        match key {
+            Key::Up | Key::Char(b'k') => self.frame = self.frame.saturating_sub(1),
+            Key::Down | Key::Char(b'j') => self.frame = (self.frame + 1).min(frames.len() - 1),
+            Key::Enter => {
+                let frame = frames[self.frame.min(frames.len() - 1)];
+                self.move_to(frame.address, chip8);
+                self.pane = Pane::Memory;
+            }
+            _ => return false,
+        }
+        return true;
+    }
+
+    fn handle_cursor(&mut self, key: Key, chip8: &Chip8) -> bool {
+        let cursor = self.cursor;
+        let target = match key {
+            Key::Left | Key::Char(b'h') => cursor.saturating_sub(1),
+            Key::Right | Key::Char(b'l') => cursor.saturating_add(1),
+            Key::Up | Key::Char(b'k') => cursor.saturating_sub(16),
+            Key::Down | Key::Char(b'j') => cursor.saturating_add(16),
+            Key::PageUp => cursor.saturating_sub(16 * ROWS),
+            Key::PageDown => cursor.saturating_add(16 * ROWS),
+            _ => return false,
+        };
+        self.move_to(target, chip8);
+        return true;
+    }
+
+    /// Hex, optionally with `0x`, or `pc` and `i` for the registers.
+    fn parse_address(&self, typed: &str, chip8: &Chip8) -> Option<u16> {
+        let typed = typed.to_ascii_lowercase();
+        let address = match typed.as_str() {
+            "pc" => chip8.pc(),
+            "i" => chip8.ir(),
+            hex => u16::from_str_radix(hex.trim_start_matches("0x"), 16).ok()?,
+        };
+        if address as usize >= chip8.ram.size() {
+            return None;
+        }
+        return Some(address);
+    }
+
+    /// Moves the cursor, scrolling so it stays in view.
+    fn move_to(&mut self, address: u16, chip8: &Chip8) -> () {
+        let last = (chip8.ram.size() - 1) as u16;
+        self.cursor = address.min(last);
+        let row = self.cursor & !0xF;
+        if row < self.top {
+            self.top = row;
+        } else if row as u32 >= self.top as u32 + 16 * ROWS as u32 {
+            self.top = row - 16 * (ROWS - 1);
+        }
+    }
+
+    /// The lines of the memory pane, with colours.
+    pub fn memory_lines(&self, chip8: &Chip8) -> Vec<String> {
+        let mut lines = Vec::new();
+        let pc = chip8.pc();
+        let opcode = chip8.ram.opcode(pc as usize).unwrap_or(0);
+        lines.push(format!(
+            "memory  pc {:#06x} {:<16} I {:#06x}  cursor {:#06x} = {:#04x}{}  breakpoints {}",
+            pc,
+            disassemble(opcode),
+            chip8.ir(),
+            self.cursor,
+            chip8.ram[self.cursor],
+            chip8.ram.access(self.cursor as usize).map(access_label).unwrap_or_default(),
+            self.breakpoints.len()
+        ));
+        if let Some(warning) = chip8.recent_warnings().back() {
+            lines.push(format!(
+                "\x1b[1;31mwarning\x1b[0m {} ({} in all)",
+                warning,
+                chip8.warning_count()
+            ));
+        }
+
+        for row in 0..ROWS {
+            let start = self.top as usize + 16 * row as usize;
+            let mut line = String::new();
+            if start < chip8.ram.size() {
+                line.push_str(&format!("{:#06x} ", start));
+                let end = (start + 16).min(chip8.ram.size());
+                for address in start..end {
+                    line.push_str(&format!(
+                        " {}{:02X}\x1b[0m",
+                        self.style(address as u16, chip8),
+                        chip8.ram[address]
+                    ));
+                }
+                line.push_str("  |");
+                line.extend((start..end).map(|address| printable(chip8.ram[address])));
+                line.push('|');
+            } else {
+                line.push_str(&" ".repeat(74));
+            }
+
+            let sprite = self.cursor as usize + row as usize;
+            if sprite < chip8.ram.size() {
+                line.push_str("  ");
+                for bit in (0..8).rev() {
+                    let on = (chip8.ram[sprite] >> bit) & 1 == 1;
+                    line.push_str(if on { "\x1b[47m  \x1b[0m" } else { "\x1b[40m  \x1b[0m" });
+                }
+            }
+            lines.push(line);
+        }
+
+        let help = match &self.mode {
+            Mode::Normal => "space run  n step  tab sprites  hjkl/arrows move  g goto  e edit  b break  c clear".to_string(),
+            Mode::Edit { high: None } => "edit: type two hex digits, enter to stop".to_string(),
+            Mode::Edit { high: Some(high) } => format!("edit: {:X}_", high),
+            Mode::Goto(typed) => format!("goto (hex, pc or i): {}_", typed),
+            Mode::Break(typed) => format!("break (condition, op PATTERN [if ...], error or warning): {}_", typed),
+        };
+        lines.push(format!("{}  {}", help, self.message));
+        return lines;
+    }
+
+    /// The lines of the sprite pane, with colours.
+    pub fn sprite_lines(&self, chip8: &Chip8) -> Vec<String> {
+        let mut lines = Vec::new();
+        let last = chip8.last_sprite();
+        let drawn = match last {
+            Some((address, rows)) => format!("last DXYN {} rows from {:#06x}", rows, address),
+            None => "nothing drawn yet".to_string(),
+        };
+        lines.push(format!(
+            "sprites  {}x{} from {:#06x}  {}",
+            self.shape.width(),
+            self.shape.height(),
+            self.cursor,
+            drawn
+        ));
+
+        let across = 128 / (self.shape.width() * 2 + 2);
+        let sprites: Vec<Sprite> = (0..across)
+            .map_while(|i| {
+                let address = u16::try_from(self.cursor as usize + i * self.shape.bytes()).ok()?;
+                Sprite::read(&chip8.ram, address, self.shape)
+            })
+            .collect();
+        let is_last = |sprite: &Sprite| last.is_some_and(|(address, _)| sprite.contains(address));
+
+        let mut labels = String::new();
+        for sprite in sprites.iter() {
+            let label = format!("{:#06x}", sprite.address);
+            let label = format!("{:<w$}", label, w = self.shape.width() * 2 + 2);
+            if is_last(sprite) {
+                labels.push_str(&format!("\x1b[1;33m{}\x1b[0m", label));
+            } else {
+                labels.push_str(&label);
+            }
+        }
+        lines.push(labels);
+
+        for y in 0..self.shape.height() {
+            let mut line = String::new();
+            for sprite in sprites.iter() {
+                let off = if is_last(sprite) { "\x1b[44m  \x1b[0m" } else { "\x1b[40m  \x1b[0m" };
+                for x in 0..self.shape.width() {
+                    line.push_str(if sprite.pixel(x, y) { "\x1b[47m  \x1b[0m" } else { off });
+                }
+                line.push_str("  ");
+            }
+            lines.push(line);
+        }
+
+        let help = match &self.mode {
+            Mode::Goto(typed) => format!("goto (hex, pc or i): {}_", typed),
+            Mode::Break(typed) => format!("break (condition, op PATTERN [if ...], error or warning): {}_", typed),
+            _ => "space run  n step  tab stack  hl sprite  jk byte  +- height  w 16x16  f font  d last drawn".to_string(),
+        };
+        lines.push(format!("{}  {}", help, self.message));
+        return lines;
+    }
+
+    /// The lines of the stack pane, with colours.
+    pub fn stack_lines(&self, chip8: &Chip8) -> Vec<String> {
+        let mut lines = Vec::new();
+        let frames = chip8.call_stack();
+        let depth = match chip8.stack_depth() {
+            usize::MAX => "unlimited".to_string(),
+            depth => depth.to_string(),
+        };
+        lines.push(format!("stack  {} of {} return addresses", chip8.stack().len(), depth));
+
+        for (i, frame) in frames.iter().enumerate() {
+            let opcode = chip8.ram.opcode(frame.address as usize).unwrap_or(0);
+            let line = format!(
+                "  #{:<2} {:#06x}  {:<20} {}",
+                i,
+                frame.address,
+                frame_label(frame, i == frames.len() - 1),
+                disassemble(opcode)
+            );
+            if i == self.frame {
+                lines.push(format!("\x1b[7m{}\x1b[0m", line));
+            } else {
+                lines.push(line);
+            }
+        }
+
+        let help = match &self.mode {
+            Mode::Goto(typed) => format!("goto (hex, pc or i): {}_", typed),
+            Mode::Break(typed) => format!("break (condition, op PATTERN [if ...], error or warning): {}_", typed),
+            _ => "space run  n step  tab memory  jk frame  enter show in memory".to_string(),
+        };
+        lines.push(format!("{}  {}", help, self.message));
+        return lines;
+    }
+
+    fn style(&self, address: u16, chip8: &Chip8) -> String {
+        let mut style = String::new();
+        if address == self.cursor {
+            style.push_str("\x1b[7m");
+        }
+        let pc = chip8.pc();
+        if address == pc || address == pc.wrapping_add(1) {
+            style.push_str("\x1b[42;30m");
+        } else if address == chip8.ir() {
+            style.push_str("\x1b[44;37m");
+        } else if chip8.recent_writes().contains(&address) {
+            style.push_str("\x1b[1;33m");
+        }
+        return style;
+    }
+
+    fn draw(&self, chip8: &mut Chip8) -> () {
+        let lines = match self.pane {
+            Pane::Memory => self.memory_lines(chip8),
+            Pane::Sprites => self.sprite_lines(chip8),
+            Pane::Stack => self.stack_lines(chip8),
+        };
+        let Some(outbuff) = chip8.outbuff.as_mut() else {
+            return;
+        };
+        for (i, line) in lines.iter().enumerate() {
+            write!(
+                outbuff,
+                "{}{}{}",
+                termion::cursor::Goto(1, TOP + i as u16),
+                termion::clear::CurrentLine,
+                line
+            )
+            .unwrap();
+        }
+        // the other pane may have been taller
+        write!(
+            outbuff,
+            "{}{}",
+            termion::cursor::Goto(1, TOP + lines.len() as u16),
+            termion::clear::AfterCursor
+        )
+        .unwrap();
+        outbuff.flush().unwrap();
+    }
+
+    fn clear(&self, chip8: &mut Chip8) -> () {
+        let Some(outbuff) = chip8.outbuff.as_mut() else {
+            return;
+        };
+        write!(
+            outbuff,
+            "{}{}",
+            termion::cursor::Goto(1, TOP),
+            termion::clear::AfterCursor
+        )
+        .unwrap();
+        outbuff.flush().unwrap();
+    }
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        return Debugger::new();
+    }
+}
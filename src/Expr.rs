@@ -0,0 +1,319 @@
+use crate::Chip8::Chip8;
+use std::fmt;
+
+/// Something an expression can read from the machine.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Var {
+    /// `v0` to `vf`.
+    V(usize),
+    /// `i`
+    I,
+    /// `pc`
+    Pc,
+    /// `dt`, the delay timer.
+    Dt,
+    /// `st`, the sound timer.
+    St,
+    /// `sp`, how many return addresses are on the stack.
+    Sp,
+    /// `opcode`, the instruction at `pc`.
+    Opcode,
+}
+
+impl Var {
+    fn parse(name: &str) -> Option<Self> {
+        return match name {
+            "i" => Some(Var::I),
+            "pc" => Some(Var::Pc),
+            "dt" => Some(Var::Dt),
+            "st" => Some(Var::St),
+            "sp" => Some(Var::Sp),
+            "opcode" => Some(Var::Opcode),
+            _ => {
+                let x = name.strip_prefix('v')?;
+                if x.len() != 1 {
+                    return None;
+                }
+                Some(Var::V(usize::from_str_radix(x, 16).ok()?))
+            }
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+    BitNot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Shl,
+    Shr,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl BinaryOp {
+    /// The operator spelled `token` and how tightly it binds, C's order.
+    fn parse(token: &str) -> Option<(Self, u8)> {
+        return Some(match token {
+            "||" => (BinaryOp::Or, 1),
+            "&&" => (BinaryOp::And, 2),
+            "|" => (BinaryOp::BitOr, 3),
+            "^" => (BinaryOp::BitXor, 4),
+            "&" => (BinaryOp::BitAnd, 5),
+            "==" => (BinaryOp::Eq, 6),
+            "!=" => (BinaryOp::Ne, 6),
+            "<" => (BinaryOp::Lt, 7),
+            "<=" => (BinaryOp::Le, 7),
+            ">" => (BinaryOp::Gt, 7),
+            ">=" => (BinaryOp::Ge, 7),
+            "<<" => (BinaryOp::Shl, 8),
+            ">>" => (BinaryOp::Shr, 8),
+            "+" => (BinaryOp::Add, 9),
+            "-" => (BinaryOp::Sub, 9),
+            "*" => (BinaryOp::Mul, 10),
+            "/" => (BinaryOp::Div, 10),
+            "%" => (BinaryOp::Rem, 10),
+            _ => return None,
+        });
+    }
+}
+
+/// An expression over the machine state, as used by conditional
+/// breakpoints: `v3 == 0x10 && i > 0x300`, `mem[i + 2] != 0`.
+///
+/// Numbers are decimal or `0x` hex, the names are those of `Var` and
+/// `mem[ADDRESS]` reads a byte. The operators are C's with C's precedence.
+/// Everything is a signed 64 bit integer, comparisons give 0 or 1 and any
+/// non zero value counts as true. Dividing by zero gives 0, as does
+/// reading outside memory.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(i64),
+    Var(Var),
+    Mem(Box<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprErrors {
+    Parse { expr: String, reason: String },
+}
+
+impl fmt::Display for ExprErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            ExprErrors::Parse { expr, reason } => write!(f, "`{}`: {}", expr, reason),
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Op(String),
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_alphanumeric() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect::<String>().to_ascii_lowercase();
+            if c.is_ascii_digit() {
+                let number = match word.strip_prefix("0x") {
+                    Some(hex) => i64::from_str_radix(hex, 16),
+                    None => word.parse::<i64>(),
+                };
+                tokens.push(Token::Number(number.map_err(|_| format!("bad number {}", word))?));
+            } else {
+                tokens.push(Token::Name(word));
+            }
+        } else {
+            let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+            if BinaryOp::parse(&two).is_some() {
+                tokens.push(Token::Op(two));
+                i += 2;
+            } else if "+-*/%&|^<>!~()[]".contains(c) {
+                tokens.push(Token::Op(c.to_string()));
+                i += 1;
+            } else {
+                return Err(format!("unexpected {}", c));
+            }
+        }
+    }
+    return Ok(tokens);
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek_op(&self) -> Option<&str> {
+        return match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) => Some(op.as_str()),
+            _ => None,
+        };
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), String> {
+        if self.peek_op() != Some(op) {
+            return Err(format!("expected {}", op));
+        }
+        self.pos += 1;
+        return Ok(());
+    }
+
+    /// Binary operators binding at least as tightly as `min`.
+    fn binary(&mut self, min: u8) -> Result<Expr, String> {
+        let mut left = self.unary()?;
+        while let Some((op, precedence)) = self.peek_op().and_then(BinaryOp::parse) {
+            if precedence < min {
+                break;
+            }
+            self.pos += 1;
+            let right = self.binary(precedence + 1)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        return Ok(left);
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        let op = match self.peek_op() {
+            Some("-") => UnaryOp::Neg,
+            Some("!") => UnaryOp::Not,
+            Some("~") => UnaryOp::BitNot,
+            _ => return self.primary(),
+        };
+        self.pos += 1;
+        return Ok(Expr::Unary(op, Box::new(self.unary()?)));
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        let token = self.tokens.get(self.pos).cloned().ok_or("unexpected end")?;
+        self.pos += 1;
+        return match token {
+            Token::Number(number) => Ok(Expr::Number(number)),
+            Token::Name(name) if name == "mem" => {
+                self.expect("[")?;
+                let address = self.binary(0)?;
+                self.expect("]")?;
+                Ok(Expr::Mem(Box::new(address)))
+            }
+            Token::Name(name) => Var::parse(&name).map(Expr::Var).ok_or(format!("unknown name {}", name)),
+            Token::Op(op) if op == "(" => {
+                let inner = self.binary(0)?;
+                self.expect(")")?;
+                Ok(inner)
+            }
+            Token::Op(op) => Err(format!("unexpected {}", op)),
+        };
+    }
+}
+
+impl Expr {
+    pub fn parse(text: &str) -> Result<Self, ExprErrors> {
+        let fail = |reason| ExprErrors::Parse {
+            expr: text.to_string(),
+            reason,
+        };
+        let tokens = tokenize(text).map_err(fail)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.binary(0).map_err(fail)?;
+        if parser.pos < parser.tokens.len() {
+            return Err(fail("trailing input".to_string()));
+        }
+        return Ok(expr);
+    }
+
+    pub fn eval(&self, chip8: &Chip8) -> i64 {
+        return match self {
+            Expr::Number(number) => *number,
+            Expr::Var(var) => match var {
+                Var::V(x) => chip8.v(*x) as i64,
+                Var::I => chip8.ir() as i64,
+                Var::Pc => chip8.pc() as i64,
+                Var::Dt => chip8.delay_timer as i64,
+                Var::St => chip8.soud_timer as i64,
+                Var::Sp => chip8.stack().len() as i64,
+                Var::Opcode => read(chip8, chip8.pc() as i64) << 8 | read(chip8, chip8.pc() as i64 + 1),
+            },
+            Expr::Mem(address) => read(chip8, address.eval(chip8)),
+            Expr::Unary(op, value) => {
+                let value = value.eval(chip8);
+                match op {
+                    UnaryOp::Neg => value.wrapping_neg(),
+                    UnaryOp::Not => (value == 0) as i64,
+                    UnaryOp::BitNot => !value,
+                }
+            }
+            Expr::Binary(op, left, right) => {
+                // reading has no side effects, `&&` and `||` can evaluate
+                // both sides
+                let (a, b) = (left.eval(chip8), right.eval(chip8));
+                match op {
+                    BinaryOp::Or => (a != 0 || b != 0) as i64,
+                    BinaryOp::And => (a != 0 && b != 0) as i64,
+                    BinaryOp::BitOr => a | b,
+                    BinaryOp::BitXor => a ^ b,
+                    BinaryOp::BitAnd => a & b,
+                    BinaryOp::Eq => (a == b) as i64,
+                    BinaryOp::Ne => (a != b) as i64,
+                    BinaryOp::Lt => (a < b) as i64,
+                    BinaryOp::Le => (a <= b) as i64,
+                    BinaryOp::Gt => (a > b) as i64,
+                    BinaryOp::Ge => (a >= b) as i64,
+                    BinaryOp::Shl => a.wrapping_shl(b as u32),
+                    BinaryOp::Shr => a.wrapping_shr(b as u32),
+                    BinaryOp::Add => a.wrapping_add(b),
+                    BinaryOp::Sub => a.wrapping_sub(b),
+                    BinaryOp::Mul => a.wrapping_mul(b),
+                    BinaryOp::Div => a.checked_div(b).unwrap_or(0),
+                    BinaryOp::Rem => a.checked_rem(b).unwrap_or(0),
+                }
+            }
+        };
+    }
+
+    /// Whether the expression is true, not zero.
+    pub fn holds(&self, chip8: &Chip8) -> bool {
+        return self.eval(chip8) != 0;
+    }
+}
+
+/// The byte at `address`, 0 outside memory.
+fn read(chip8: &Chip8, address: i64) -> i64 {
+    return u16::try_from(address)
+        .ok()
+        .and_then(|address| chip8.read_ram(address))
+        .map_or(0, |byte| byte as i64);
+}
@@ -0,0 +1,160 @@
+use std::ops::{BitAnd, BitXorAssign};
+
+/// A row of pixels packed into an integer, the leftmost pixel in the
+/// highest bit.
+trait PackedRow: Copy + PartialEq + BitAnd<Output = Self> + BitXorAssign {
+    const WIDTH: u32;
+    const EMPTY: Self;
+    /// The `width` low bits of `bits` moved to the leftmost pixels.
+    fn from_left(bits: u16, width: u32) -> Self;
+    fn shr(self, n: u32) -> Self;
+    fn rotate_right(self, n: u32) -> Self;
+}
+
+impl PackedRow for u64 {
+    const WIDTH: u32 = 64;
+    const EMPTY: Self = 0;
+
+    fn from_left(bits: u16, width: u32) -> Self {
+        return (bits as u64) << (64 - width);
+    }
+
+    fn shr(self, n: u32) -> Self {
+        return self >> n;
+    }
+
+    fn rotate_right(self, n: u32) -> Self {
+        return u64::rotate_right(self, n);
+    }
+}
+
+impl PackedRow for u128 {
+    const WIDTH: u32 = 128;
+    const EMPTY: Self = 0;
+
+    fn from_left(bits: u16, width: u32) -> Self {
+        return (bits as u128) << (128 - width);
+    }
+
+    fn shr(self, n: u32) -> Self {
+        return self >> n;
+    }
+
+    fn rotate_right(self, n: u32) -> Self {
+        return u128::rotate_right(self, n);
+    }
+}
+
+/// XORs `sprite` onto `rows` at `x`, `y` (taken modulo the screen size),
+/// one row per shift, AND and XOR. Without `wrap` whatever goes past the
+/// right or bottom edge is clipped, with it it comes back on the other side.
+/// Returns whether any pixel was turned off.
+fn draw_rows<R: PackedRow>(rows: &mut [R], x: usize, y: usize, sprite: &[u16], width: u32, wrap: bool) -> bool {
+    let height = rows.len();
+    let x = x as u32 % R::WIDTH;
+    let y = y % height;
+    let mut collided = false;
+
+    for (i, bits) in sprite.iter().enumerate() {
+        let mut row = y + i;
+        if row >= height {
+            if !wrap {
+                break;
+            }
+            row %= height;
+        }
+        let line = R::from_left(*bits, width);
+        let line = if wrap { line.rotate_right(x) } else { line.shr(x) };
+        collided |= rows[row] & line != R::EMPTY;
+        rows[row] ^= line;
+    }
+    return collided;
+}
+
+/// The screen, one integer per row: `u64` rows for the 64x32 CHIP-8
+/// display and `u128` rows for the 128x64 SCHIP hires display, both drawn
+/// by `draw_rows`. Lores rows are kept inline since nearly every ROM only
+/// ever uses them; `Chip8` doesn't run SCHIP's `00FF` yet, so its screen is
+/// always lores.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Framebuffer {
+    Lores([u64; 32]),
+    Hires(Box<[u128; 64]>),
+}
+
+impl Framebuffer {
+    pub fn lores() -> Self {
+        return Framebuffer::Lores([0; 32]);
+    }
+
+    pub fn hires() -> Self {
+        return Framebuffer::Hires(Box::new([0; 64]));
+    }
+
+    pub fn width(&self) -> usize {
+        return match self {
+            Framebuffer::Lores(_) => 64,
+            Framebuffer::Hires(_) => 128,
+        };
+    }
+
+    pub fn height(&self) -> usize {
+        return match self {
+            Framebuffer::Lores(_) => 32,
+            Framebuffer::Hires(_) => 64,
+        };
+    }
+
+    /// Row `y` with the leftmost pixel in bit `width - 1`.
+    pub fn row(&self, y: usize) -> u128 {
+        return match self {
+            Framebuffer::Lores(rows) => rows[y] as u128,
+            Framebuffer::Hires(rows) => rows[y],
+        };
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        return (self.row(y) >> (self.width() - 1 - x)) & 1 == 1;
+    }
+
+    /// One byte per pixel, 0 or 1, row by row.
+    pub fn unpack(&self) -> Vec<u8> {
+        let mut pixels = Vec::with_capacity(self.width() * self.height());
+        match self {
+            Framebuffer::Lores(rows) => {
+                for row in rows.iter() {
+                    pixels.extend((0..64).rev().map(|bit| (row >> bit) as u8 & 1));
+                }
+            }
+            Framebuffer::Hires(rows) => {
+                for row in rows.iter() {
+                    pixels.extend((0..128).rev().map(|bit| (row >> bit) as u8 & 1));
+                }
+            }
+        }
+        return pixels;
+    }
+
+    pub fn clear(&mut self) -> () {
+        match self {
+            Framebuffer::Lores(rows) => rows.fill(0),
+            Framebuffer::Hires(rows) => rows.fill(0),
+        }
+    }
+
+    /// Draws `sprite`, rows of `width` pixels (8 or 16) with the leftmost
+    /// pixel in the highest bit, as `DXYN` does. Returns the collision flag.
+    pub fn draw(&mut self, x: usize, y: usize, sprite: &[u16], width: u32, wrap: bool) -> bool {
+        return match self {
+            Framebuffer::Lores(rows) => draw_rows(rows, x, y, sprite, width, wrap),
+            Framebuffer::Hires(rows) => draw_rows(&mut rows[..], x, y, sprite, width, wrap),
+        };
+    }
+}
+
+impl Default for Framebuffer {
+    fn default() -> Self {
+        return Framebuffer::lores();
+    }
+}
@@ -0,0 +1,442 @@
+use crate::Chip8::{Chip8, Chip8Errors};
+use std::collections::{BTreeSet, VecDeque};
+use std::fmt::Write as _;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::TcpStream;
+
+/// Registers in the order of `g`/`G` and their numbers in `p`/`P`: V0 to
+/// VF, I, pc, the stack depth and the two timers.
+const REGISTERS: [(&str, usize); 21] = [
+    ("v0", 8),
+    ("v1", 8),
+    ("v2", 8),
+    ("v3", 8),
+    ("v4", 8),
+    ("v5", 8),
+    ("v6", 8),
+    ("v7", 8),
+    ("v8", 8),
+    ("v9", 8),
+    ("va", 8),
+    ("vb", 8),
+    ("vc", 8),
+    ("vd", 8),
+    ("ve", 8),
+    ("vf", 8),
+    ("i", 16),
+    ("pc", 16),
+    ("sp", 8),
+    ("dt", 8),
+    ("st", 8),
+];
+
+/// Signals in stop replies.
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+fn target_xml() -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\"?>\n<!DOCTYPE target SYSTEM \"gdb-target.dtd\">\n<target version=\"1.0\">\n  <feature name=\"org.krhip8.chip8\">\n",
+    );
+    for (number, (name, bits)) in REGISTERS.iter().enumerate() {
+        let kind = match *name {
+            "pc" => " type=\"code_ptr\"",
+            "i" => " type=\"data_ptr\"",
+            _ => "",
+        };
+        writeln!(xml, "    <reg name=\"{}\" bitsize=\"{}\" regnum=\"{}\"{}/>", name, bits, number, kind).unwrap();
+    }
+    xml.push_str("  </feature>\n</target>\n");
+    return xml;
+}
+
+fn hex_bytes(bytes: &[u8]) -> String {
+    return bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+}
+
+fn parse_hex_bytes(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    return (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect();
+}
+
+fn parse_hex(hex: &str) -> Option<usize> {
+    return usize::from_str_radix(hex, 16).ok();
+}
+
+/// Why running stopped, becomes the stop reply.
+enum Stop {
+    Signal(u8),
+    /// The user quit the emulator.
+    Exited,
+}
+
+/// A GDB remote serial protocol server for one connection. Registers are
+/// sent little endian, which is what GDB reads them as when the target
+/// description names no architecture, memory is all of `Ram`.
+///
+/// Supported are the register and memory packets (`g`, `G`, `p`, `P`, `m`,
+/// `M`), `c` and `s`, software and hardware breakpoints (`Z0`/`Z1`), `?`,
+/// ctrl-c while running, `D` and `k`, and the target description through
+/// `qXfer:features:read`. Anything else gets the empty reply, which tells
+/// GDB it isn't supported.
+pub struct GdbStub {
+    stream: TcpStream,
+    breakpoints: BTreeSet<u16>,
+    /// `QStartNoAckMode` was accepted, packets aren't acknowledged anymore.
+    no_ack: bool,
+    /// Bytes `interrupted` read that weren't ctrl-c, read before the stream.
+    pending: VecDeque<u8>,
+}
+
+impl GdbStub {
+    pub fn new(stream: TcpStream) -> Self {
+        // packets are small and every one waits for an answer
+        let _ = stream.set_nodelay(true);
+        return GdbStub {
+            stream,
+            breakpoints: BTreeSet::new(),
+            no_ack: false,
+            pending: VecDeque::new(),
+        };
+    }
+
+    /// Answers packets until GDB detaches or kills the program, or the
+    /// connection closes. The machine runs only on `c` and `s`; a kill
+    /// sets `quit`.
+    pub fn serve(&mut self, chip8: &mut Chip8) -> io::Result<()> {
+        while let Some(packet) = self.read_packet()? {
+            let reply = match packet.as_str() {
+                "D" => {
+                    self.send("OK")?;
+                    return Ok(());
+                }
+                "k" => {
+                    chip8.quit = true;
+                    return Ok(());
+                }
+                _ => self.handle(&packet, chip8)?,
+            };
+            self.send(&reply)?;
+            if chip8.quit {
+                return Ok(());
+            }
+        }
+        return Ok(());
+    }
+
+    /// The reply to one packet, running the machine for `c` and `s`.
+    fn handle(&mut self, packet: &str, chip8: &mut Chip8) -> io::Result<String> {
+        let reply = match packet.as_bytes().first() {
+            Some(b'?') => format!("S{:02x}", SIGTRAP),
+            Some(b'g') => hex_bytes(&read_registers(chip8)),
+            Some(b'G') => match parse_hex_bytes(&packet[1..]) {
+                Some(bytes) if bytes.len() == register_bytes() => {
+                    write_registers(chip8, &bytes);
+                    "OK".to_string()
+                }
+                _ => "E01".to_string(),
+            },
+            Some(b'p') => match parse_hex(&packet[1..]).and_then(|n| read_register(chip8, n)) {
+                Some(bytes) => hex_bytes(&bytes),
+                None => "E01".to_string(),
+            },
+            Some(b'P') => {
+                let written = packet[1..].split_once('=').and_then(|(number, value)| {
+                    write_register(chip8, parse_hex(number)?, &parse_hex_bytes(value)?)
+                });
+                if written.is_some() { "OK" } else { "E01" }.to_string()
+            }
+            Some(b'm') => match self.memory_range(&packet[1..], chip8) {
+                Some((start, len)) => hex_bytes(&chip8.memory()[start..start + len]),
+                None => "E01".to_string(),
+            },
+            Some(b'M') => {
+                let written = packet[1..].split_once(':').and_then(|(range, data)| {
+                    let (start, len) = self.memory_range(range, chip8)?;
+                    let data = parse_hex_bytes(data).filter(|data| data.len() == len)?;
+                    for (i, byte) in data.into_iter().enumerate() {
+                        chip8.write_ram((start + i) as u16, byte).ok()?;
+                    }
+                    Some(())
+                });
+                if written.is_some() { "OK" } else { "E01" }.to_string()
+            }
+            Some(b'c') => {
+                if !self.jump(&packet[1..], chip8) {
+                    return Ok("E01".to_string());
+                }
+                let stop = self.run(chip8)?;
+                stop_reply(stop)
+            }
+            Some(b's') => {
+                if !self.jump(&packet[1..], chip8) {
+                    return Ok("E01".to_string());
+                }
+                match chip8.step() {
+                    Ok(()) => format!("S{:02x}", SIGTRAP),
+                    Err(err) => stop_reply(Stop::Signal(signal(&err))),
+                }
+            }
+            Some(b'Z') | Some(b'z') => self.breakpoint(packet),
+            Some(b'H') => "OK".to_string(),
+            Some(b'T') => "OK".to_string(),
+            _ => self.query(packet),
+        };
+        return Ok(reply);
+    }
+
+    fn query(&mut self, packet: &str) -> String {
+        if packet.starts_with("qSupported") {
+            return "PacketSize=4000;qXfer:features:read+;QStartNoAckMode+".to_string();
+        }
+        if let Some(rest) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            let Some((offset, length)) = rest.split_once(',') else {
+                return "E01".to_string();
+            };
+            let (Some(offset), Some(length)) = (parse_hex(offset), parse_hex(length)) else {
+                return "E01".to_string();
+            };
+            let xml = target_xml();
+            let start = offset.min(xml.len());
+            let end = (start + length).min(xml.len());
+            let more = if end < xml.len() { 'm' } else { 'l' };
+            return format!("{}{}", more, &xml[start..end]);
+        }
+        return match packet {
+            "QStartNoAckMode" => {
+                self.no_ack = true;
+                "OK".to_string()
+            }
+            "qAttached" => "1".to_string(),
+            "qC" => "QC1".to_string(),
+            "qfThreadInfo" => "m1".to_string(),
+            "qsThreadInfo" => "l".to_string(),
+            _ => String::new(),
+        };
+    }
+
+    /// `Z0,ADDR,KIND` and `z0,ADDR,KIND`, type 1 is treated the same.
+    fn breakpoint(&mut self, packet: &str) -> String {
+        let mut fields = packet[1..].split(',');
+        let (Some(kind), Some(address)) = (fields.next(), fields.next()) else {
+            return "E01".to_string();
+        };
+        if kind != "0" && kind != "1" {
+            return String::new();
+        }
+        let Some(address) = parse_hex(address).and_then(|address| u16::try_from(address).ok()) else {
+            return "E01".to_string();
+        };
+        if packet.starts_with('Z') {
+            self.breakpoints.insert(address);
+        } else {
+            self.breakpoints.remove(&address);
+        }
+        return "OK".to_string();
+    }
+
+    /// `ADDR,LEN` inside memory.
+    fn memory_range(&self, range: &str, chip8: &Chip8) -> Option<(usize, usize)> {
+        let (start, len) = range.split_once(',')?;
+        let (start, len) = (parse_hex(start)?, parse_hex(len)?);
+        if start.checked_add(len)? > chip8.memory().len() {
+            return None;
+        }
+        return Some((start, len));
+    }
+
+    /// The optional address of `c`/`s` to resume at, false if it's bad.
+    fn jump(&self, address: &str, chip8: &mut Chip8) -> bool {
+        if address.is_empty() {
+            return true;
+        }
+        match parse_hex(address).and_then(|address| u16::try_from(address).ok()) {
+            Some(address) => chip8.set_pc(address),
+            None => return false,
+        }
+        return true;
+    }
+
+    /// Runs frame by frame until a breakpoint, an error, ctrl-c from GDB or
+    /// the user quitting. The breakpoint at the starting `pc` doesn't stop
+    /// it, so continuing from one works.
+    fn run(&mut self, chip8: &mut Chip8) -> io::Result<Stop> {
+        let mut first = true;
+        loop {
+            chip8.handle_input();
+            chip8.begin_frame();
+            for _ in 0..chip8.cycles_per_frame.max(1) {
+                if chip8.quit {
+                    return Ok(Stop::Exited);
+                }
+                if !first && self.breakpoints.contains(&chip8.pc()) {
+                    return Ok(Stop::Signal(SIGTRAP));
+                }
+                first = false;
+                if let Err(err) = chip8.step() {
+                    return Ok(Stop::Signal(signal(&err)));
+                }
+            }
+            chip8.render();
+            if self.interrupted()? {
+                return Ok(Stop::Signal(SIGINT));
+            }
+        }
+    }
+
+    /// Whether GDB sent ctrl-c, without waiting for it. Whatever else came
+    /// in is kept for `read_packet`.
+    fn interrupted(&mut self) -> io::Result<bool> {
+        self.stream.set_nonblocking(true)?;
+        let mut buffer = [0u8; 64];
+        let read = loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => break Err(io::Error::from(ErrorKind::UnexpectedEof)),
+                Ok(len) => self.pending.extend(&buffer[..len]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break Ok(()),
+                Err(err) => break Err(err),
+            }
+        };
+        self.stream.set_nonblocking(false)?;
+        read?;
+        return match self.pending.iter().position(|byte| *byte == 0x03) {
+            Some(i) => {
+                self.pending.remove(i);
+                Ok(true)
+            }
+            None => Ok(false),
+        };
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        if let Some(byte) = self.pending.pop_front() {
+            return Ok(Some(byte));
+        }
+        let mut byte = [0u8; 1];
+        return match self.stream.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        };
+    }
+
+    /// The next `$DATA#CK` packet, acknowledged unless in no ack mode.
+    /// `None` once the connection is closed.
+    fn read_packet(&mut self) -> io::Result<Option<String>> {
+        loop {
+            match self.read_byte()? {
+                None => return Ok(None),
+                Some(b'$') => {}
+                // acks, and ctrl-c while already stopped
+                Some(_) => continue,
+            }
+            let mut data = Vec::new();
+            loop {
+                match self.read_byte()? {
+                    None => return Ok(None),
+                    Some(b'#') => break,
+                    Some(byte) => data.push(byte),
+                }
+            }
+            let mut checksum = [0u8; 2];
+            for digit in checksum.iter_mut() {
+                *digit = self.read_byte()?.ok_or(io::Error::from(ErrorKind::UnexpectedEof))?;
+            }
+            let expected = std::str::from_utf8(&checksum).ok().and_then(|ck| u8::from_str_radix(ck, 16).ok());
+            let sum = data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+            if expected != Some(sum) {
+                if !self.no_ack {
+                    self.stream.write_all(b"-")?;
+                }
+                continue;
+            }
+            if !self.no_ack {
+                self.stream.write_all(b"+")?;
+            }
+            return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+        }
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let mut escaped = Vec::with_capacity(data.len());
+        for byte in data.bytes() {
+            match byte {
+                b'$' | b'#' | b'}' | b'*' => escaped.extend([b'}', byte ^ 0x20]),
+                _ => escaped.push(byte),
+            }
+        }
+        let sum = escaped.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        let mut packet = vec![b'$'];
+        packet.extend(escaped);
+        packet.extend(format!("#{:02x}", sum).bytes());
+        self.stream.write_all(&packet)?;
+        return self.stream.flush();
+    }
+}
+
+fn signal(err: &Chip8Errors) -> u8 {
+    return match err {
+        Chip8Errors::UndefinedInstruction => SIGILL,
+        Chip8Errors::StackOverflow { .. } | Chip8Errors::StackUnderflow { .. } | Chip8Errors::OutOfBounds { .. } => SIGSEGV,
+    };
+}
+
+fn stop_reply(stop: Stop) -> String {
+    return match stop {
+        Stop::Signal(signal) => format!("S{:02x}", signal),
+        Stop::Exited => "W00".to_string(),
+    };
+}
+
+fn register_bytes() -> usize {
+    return REGISTERS.iter().map(|(_, bits)| bits / 8).sum();
+}
+
+fn read_register(chip8: &Chip8, number: usize) -> Option<Vec<u8>> {
+    return match number {
+        0..=15 => Some(vec![chip8.v(number)]),
+        16 => Some(chip8.ir().to_le_bytes().to_vec()),
+        17 => Some(chip8.pc().to_le_bytes().to_vec()),
+        18 => Some(vec![chip8.stack().len() as u8]),
+        19 => Some(vec![chip8.delay_timer]),
+        20 => Some(vec![chip8.soud_timer]),
+        _ => None,
+    };
+}
+
+/// Sets a register from its little endian bytes. The stack depth can't be
+/// written, writes to it are ignored.
+fn write_register(chip8: &mut Chip8, number: usize, bytes: &[u8]) -> Option<()> {
+    let (_, bits) = REGISTERS.get(number)?;
+    if bytes.len() != bits / 8 {
+        return None;
+    }
+    match number {
+        0..=15 => chip8.set_v(number, bytes[0]),
+        16 => chip8.set_ir(u16::from_le_bytes([bytes[0], bytes[1]])),
+        17 => chip8.set_pc(u16::from_le_bytes([bytes[0], bytes[1]])),
+        19 => chip8.delay_timer = bytes[0],
+        20 => chip8.soud_timer = bytes[0],
+        _ => {}
+    }
+    return Some(());
+}
+
+fn read_registers(chip8: &Chip8) -> Vec<u8> {
+    return (0..REGISTERS.len()).flat_map(|number| read_register(chip8, number).unwrap()).collect();
+}
+
+fn write_registers(chip8: &mut Chip8, bytes: &[u8]) -> () {
+    let mut offset = 0;
+    for (number, (_, bits)) in REGISTERS.iter().enumerate() {
+        let size = bits / 8;
+        write_register(chip8, number, &bytes[offset..offset + size]);
+        offset += size;
+    }
+}
@@ -8,11 +8,11 @@ pub fn y(upcode: u16) -> usize {
 }
 #[inline(always)]
 pub fn n(upcode: u16) -> u16 {
-    return (upcode & 0x000F);
+    return upcode & 0x000F;
 }
 #[inline(always)]
 pub fn nnn(upcode: u16) -> u16 {
-    return (upcode & 0x0FFF);
+    return upcode & 0x0FFF;
 }
 #[inline(always)]
 pub fn kk(upcode: u16) -> u8 {
@@ -24,13 +24,68 @@ pub fn bg_id(upcode: u16) -> u16 {
 }
 #[inline(always)]
 pub fn end_id(upcode: u16) -> u16 {
-    return (upcode & 0x000F);
+    return upcode & 0x000F;
 }
 #[inline(always)]
 pub fn two_end_id(upcode: u16) -> u16 {
-    return (upcode & 0x00FF);
+    return upcode & 0x00FF;
 }
 #[inline(always)]
 pub fn index(xx: usize, yy: usize) -> usize {
     return (yy * 64) + xx;
 }
+
+/// Decodes an opcode into its mnemonic, using Cowgod's notation.
+/// Unknown opcodes are shown as a raw data word.
+pub fn disassemble(upcode: u16) -> String {
+    let (vx, vy) = (x(upcode), y(upcode));
+    return match bg_id(upcode) {
+        0x0 => match upcode {
+            0x00E0 => "CLS".to_string(),
+            0x00EE => "RET".to_string(),
+            _ => format!("SYS {:#05x}", nnn(upcode)),
+        },
+        0x1 => format!("JP {:#05x}", nnn(upcode)),
+        0x2 => format!("CALL {:#05x}", nnn(upcode)),
+        0x3 => format!("SE V{:X}, {:#04x}", vx, kk(upcode)),
+        0x4 => format!("SNE V{:X}, {:#04x}", vx, kk(upcode)),
+        0x5 if end_id(upcode) == 0 => format!("SE V{:X}, V{:X}", vx, vy),
+        0x6 => format!("LD V{:X}, {:#04x}", vx, kk(upcode)),
+        0x7 => format!("ADD V{:X}, {:#04x}", vx, kk(upcode)),
+        0x8 => match end_id(upcode) {
+            0x0 => format!("LD V{:X}, V{:X}", vx, vy),
+            0x1 => format!("OR V{:X}, V{:X}", vx, vy),
+            0x2 => format!("AND V{:X}, V{:X}", vx, vy),
+            0x3 => format!("XOR V{:X}, V{:X}", vx, vy),
+            0x4 => format!("ADD V{:X}, V{:X}", vx, vy),
+            0x5 => format!("SUB V{:X}, V{:X}", vx, vy),
+            0x6 => format!("SHR V{:X}, V{:X}", vx, vy),
+            0x7 => format!("SUBN V{:X}, V{:X}", vx, vy),
+            0xE => format!("SHL V{:X}, V{:X}", vx, vy),
+            _ => format!("DW {:#06x}", upcode),
+        },
+        0x9 if end_id(upcode) == 0 => format!("SNE V{:X}, V{:X}", vx, vy),
+        0xA => format!("LD I, {:#05x}", nnn(upcode)),
+        0xB => format!("JP V0, {:#05x}", nnn(upcode)),
+        0xC => format!("RND V{:X}, {:#04x}", vx, kk(upcode)),
+        0xD => format!("DRW V{:X}, V{:X}, {}", vx, vy, n(upcode)),
+        0xE => match two_end_id(upcode) {
+            0x9E => format!("SKP V{:X}", vx),
+            0xA1 => format!("SKNP V{:X}", vx),
+            _ => format!("DW {:#06x}", upcode),
+        },
+        0xF => match two_end_id(upcode) {
+            0x07 => format!("LD V{:X}, DT", vx),
+            0x0A => format!("LD V{:X}, K", vx),
+            0x15 => format!("LD DT, V{:X}", vx),
+            0x18 => format!("LD ST, V{:X}", vx),
+            0x1E => format!("ADD I, V{:X}", vx),
+            0x29 => format!("LD F, V{:X}", vx),
+            0x33 => format!("LD B, V{:X}", vx),
+            0x55 => format!("LD [I], V{:X}", vx),
+            0x65 => format!("LD V{:X}, [I]", vx),
+            _ => format!("DW {:#06x}", upcode),
+        },
+        _ => format!("DW {:#06x}", upcode),
+    };
+}
@@ -0,0 +1,141 @@
+use crate::Chip8::Config;
+use crate::Quirks::Quirks;
+use crate::Random::Random;
+use crate::Rom::Platform;
+use std::fs::{read_to_string, File};
+use std::io::{BufWriter, Error, ErrorKind, Write};
+
+/// Keypad input of a whole session, one entry per frame, plus what else is
+/// needed to replay it exactly: the random seed and generator, the
+/// platform, the quirks and the frame length.
+///
+/// Saved as text:
+///
+/// ```text
+/// krhip8 movie 1
+/// seed 1234
+/// platform chip8
+/// quirks shift,memoryLeaveIUnchanged
+/// cycles-per-frame 10
+/// frames
+/// 0000
+/// 0020
+/// ```
+///
+/// Every line after `frames` is the keypad of one frame as a hex mask,
+/// bit n set while key n is held. Movies without a `quirks` line use the
+/// default quirks, ones without a `platform` line are CHIP-8 and ones
+/// without `cycles-per-frame` run `Config`'s 10 instructions a frame. A
+/// `random vip` line after the seed marks a session that ran the VIP random
+/// routine.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Movie {
+    pub seed: u64,
+    /// Recorded with the VIP random routine.
+    pub vip_random: bool,
+    pub platform: Platform,
+    pub quirks: Quirks,
+    pub cycles_per_frame: usize,
+    pub frames: Vec<u16>,
+}
+
+fn invalid(msg: &str) -> Error {
+    return Error::new(ErrorKind::InvalidData, msg.to_string());
+}
+
+impl Movie {
+    pub fn new(rng: &Random, platform: Platform, quirks: Quirks, cycles_per_frame: usize) -> Self {
+        return Movie {
+            seed: rng.seed(),
+            vip_random: rng.is_vip(),
+            platform,
+            quirks,
+            cycles_per_frame,
+            frames: Vec::new(),
+        };
+    }
+
+    pub fn record(&mut self, keys: &[u8; 16]) -> () {
+        let mut mask = 0;
+        for (i, key) in keys.iter().enumerate() {
+            if *key == 1 {
+                mask |= 1 << i;
+            }
+        }
+        self.frames.push(mask);
+    }
+
+    /// Keypad for the given frame, `None` once the movie is over.
+    pub fn keys(&self, frame: usize) -> Option<[u8; 16]> {
+        let mask = self.frames.get(frame)?;
+        let mut keys = [0; 16];
+        for (i, key) in keys.iter_mut().enumerate() {
+            *key = ((mask >> i) & 1) as u8;
+        }
+        return Some(keys);
+    }
+
+    /// A generator in the state the recorded session started with. The VIP
+    /// routine needs the interpreter `page`, without it this is `None`.
+    pub fn rng(&self, page: Option<&[u8; 256]>) -> Option<Random> {
+        if !self.vip_random {
+            return Some(Random::new(self.seed));
+        }
+        return Some(Random::vip(self.seed, page?));
+    }
+
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "krhip8 movie 1")?;
+        writeln!(out, "seed {}", self.seed)?;
+        if self.vip_random {
+            writeln!(out, "random vip")?;
+        }
+        writeln!(out, "platform {}", self.platform.name())?;
+        writeln!(out, "quirks {}", self.quirks.to_list())?;
+        writeln!(out, "cycles-per-frame {}", self.cycles_per_frame)?;
+        writeln!(out, "frames")?;
+        for frame in self.frames.iter() {
+            writeln!(out, "{:04X}", frame)?;
+        }
+        return out.flush();
+    }
+
+    pub fn load(path: &str) -> std::io::Result<Self> {
+        let text = read_to_string(path)?;
+        let mut lines = text.lines();
+
+        if lines.next() != Some("krhip8 movie 1") {
+            return Err(invalid("not a krhip8 movie"));
+        }
+
+        let cycles_per_frame = Config::default().cycles_per_frame;
+        let mut movie = Movie::new(&Random::new(0), Platform::Chip8, Default::default(), cycles_per_frame);
+        for line in lines.by_ref() {
+            match line.split_once(' ') {
+                Some(("seed", seed)) => {
+                    movie.seed = seed.parse().map_err(|_| invalid("bad seed"))?;
+                }
+                Some(("platform", name)) => {
+                    movie.platform = Platform::parse(name).ok_or(invalid("unknown platform"))?;
+                }
+                Some(("random", "vip")) => movie.vip_random = true,
+                Some(("quirks", list)) => {
+                    movie.quirks = Quirks::from_list(list).ok_or(invalid("unknown quirk"))?;
+                }
+                None if line == "quirks" => movie.quirks = Quirks::from_list("").unwrap(),
+                Some(("cycles-per-frame", cycles)) => {
+                    movie.cycles_per_frame = cycles.parse().map_err(|_| invalid("bad cycles-per-frame"))?;
+                }
+                None if line == "frames" => break,
+                _ => return Err(invalid(&format!("unknown movie header `{}`", line))),
+            }
+        }
+
+        for line in lines {
+            let mask = u16::from_str_radix(line.trim(), 16).map_err(|_| invalid("bad frame"))?;
+            movie.frames.push(mask);
+        }
+        return Ok(movie);
+    }
+}
@@ -54,7 +54,7 @@ impl NonblockingBufReader {
 // for use in BufReader...
 impl std::io::Read for RawFd2 {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        assert!(buf.len() <= isize::max_value() as usize);
+        assert!(buf.len() <= isize::MAX as usize);
         match unsafe { libc::read(self.fd, buf.as_mut_ptr() as _, buf.len()) } {
             x if x < 0 => Err(std::io::Error::last_os_error()),
             x => Ok(x as usize),
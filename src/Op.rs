@@ -0,0 +1,133 @@
+use crate::Helpers::*;
+
+/// An instruction with its operands taken apart, so executing it doesn't
+/// decode the opcode again. Names follow the Cowgod mnemonics used by
+/// `disassemble`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    /// `00E0`
+    Cls,
+    /// `00EE`
+    Ret,
+    /// `1NNN`
+    Jp(u16),
+    /// `2NNN`
+    Call(u16),
+    /// `3XKK`
+    SeByte(usize, u8),
+    /// `4XKK`
+    SneByte(usize, u8),
+    /// `5XY0`
+    SeReg(usize, usize),
+    /// `6XKK`
+    LdByte(usize, u8),
+    /// `7XKK`
+    AddByte(usize, u8),
+    /// `8XY0`
+    LdReg(usize, usize),
+    /// `8XY1`
+    Or(usize, usize),
+    /// `8XY2`
+    And(usize, usize),
+    /// `8XY3`
+    Xor(usize, usize),
+    /// `8XY4`
+    AddReg(usize, usize),
+    /// `8XY5`
+    Sub(usize, usize),
+    /// `8XY6`
+    Shr(usize, usize),
+    /// `8XY7`
+    Subn(usize, usize),
+    /// `8XYE`
+    Shl(usize, usize),
+    /// `9XY0`
+    SneReg(usize, usize),
+    /// `ANNN`
+    LdI(u16),
+    /// `BNNN`, X kept for the jump quirk.
+    JpV0(u16, usize),
+    /// `CXKK`
+    Rnd(usize, u8),
+    /// `DXYN`
+    Drw(usize, usize, u16),
+    /// `EX9E`
+    Skp(usize),
+    /// `EXA1`
+    Sknp(usize),
+    /// `FX07`
+    LdVxDt(usize),
+    /// `FX0A`
+    LdVxK(usize),
+    /// `FX15`
+    LdDtVx(usize),
+    /// `FX18`
+    LdStVx(usize),
+    /// `FX1E`
+    AddI(usize),
+    /// `FX29`
+    LdF(usize),
+    /// `FX33`
+    LdB(usize),
+    /// `FX55`
+    LdIVx(usize),
+    /// `FX65`
+    LdVxI(usize),
+    /// Anything the interpreter doesn't execute.
+    Undefined,
+}
+
+impl Op {
+    pub fn decode(uc: u16) -> Self {
+        let (x, y) = (x(uc), y(uc));
+        return match bg_id(uc) {
+            0x0 => match two_end_id(uc) {
+                0xE0 => Op::Cls,
+                0xEE => Op::Ret,
+                _ => Op::Undefined,
+            },
+            0x1 => Op::Jp(nnn(uc)),
+            0x2 => Op::Call(nnn(uc)),
+            0x3 => Op::SeByte(x, kk(uc)),
+            0x4 => Op::SneByte(x, kk(uc)),
+            0x5 => Op::SeReg(x, y),
+            0x6 => Op::LdByte(x, kk(uc)),
+            0x7 => Op::AddByte(x, kk(uc)),
+            0x8 => match end_id(uc) {
+                0x0 => Op::LdReg(x, y),
+                0x1 => Op::Or(x, y),
+                0x2 => Op::And(x, y),
+                0x3 => Op::Xor(x, y),
+                0x4 => Op::AddReg(x, y),
+                0x5 => Op::Sub(x, y),
+                0x6 => Op::Shr(x, y),
+                0x7 => Op::Subn(x, y),
+                0xE => Op::Shl(x, y),
+                _ => Op::Undefined,
+            },
+            0x9 => Op::SneReg(x, y),
+            0xA => Op::LdI(nnn(uc)),
+            0xB => Op::JpV0(nnn(uc), x),
+            0xC => Op::Rnd(x, kk(uc)),
+            0xD => Op::Drw(x, y, n(uc)),
+            0xE => match two_end_id(uc) {
+                0x9E => Op::Skp(x),
+                0xA1 => Op::Sknp(x),
+                _ => Op::Undefined,
+            },
+            0xF => match two_end_id(uc) {
+                0x07 => Op::LdVxDt(x),
+                0x0A => Op::LdVxK(x),
+                0x15 => Op::LdDtVx(x),
+                0x18 => Op::LdStVx(x),
+                0x1E => Op::AddI(x),
+                0x29 => Op::LdF(x),
+                0x33 => Op::LdB(x),
+                0x55 => Op::LdIVx(x),
+                0x65 => Op::LdVxI(x),
+                _ => Op::Undefined,
+            },
+            _ => Op::Undefined,
+        };
+    }
+}
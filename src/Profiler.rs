@@ -0,0 +1,258 @@
+use crate::Helpers::disassemble;
+use crate::Ram::Ram;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+/// Hotspots listed in the report.
+const HOTSPOTS: usize = 20;
+/// Longest loop, in instructions after the `FX07`, taken for polling the
+/// delay timer.
+const POLL_LOOP: u64 = 4;
+
+/// Counts where a ROM spends its instructions, for fitting a game into the
+/// cycle budget of real hardware.
+///
+/// Every instruction is counted at its address and on the call path it
+/// ran on, which follows `2NNN` and `00EE` from the program down: the call
+/// instruction counts for the caller, the return for the callee. Apart
+/// from that it counts instructions spent waiting:
+///
+/// - on a key in `FX0A`, and on the next frame in `DXYN` with the vblank
+///   quirk, which both run again until they can go on,
+/// - polling the delay timer: a loop of at most `POLL_LOOP` instructions
+///   after an `FX07` that read a non zero value and came back to it,
+///
+/// and the sprites drawn in every frame.
+pub struct Profiler {
+    /// Executions by address.
+    counts: Vec<u64>,
+    /// Entries of the subroutines being run, outermost first.
+    stack: Vec<u16>,
+    /// Every call path seen, indexed by id, and the ids by path.
+    paths: Vec<Vec<u16>>,
+    path_ids: HashMap<Vec<u16>, usize>,
+    /// Instructions run on each path.
+    path_counts: Vec<u64>,
+    /// Id of `stack`.
+    path: usize,
+    /// `2NNN` executions by target.
+    calls: BTreeMap<u16, u64>,
+    /// Where the program starts, its name is `program`.
+    program: u16,
+    total: u64,
+    key_wait: u64,
+    vblank_wait: u64,
+    delay_wait: u64,
+    /// Address of the last `FX07`, instructions since and whether it read
+    /// a non zero value.
+    poll: Option<(u16, u64, bool)>,
+    frames: u64,
+    frame_cycles: u64,
+    frame_draws: u64,
+    max_frame_cycles: u64,
+    /// Frames by the number of sprites drawn in them.
+    draws_per_frame: BTreeMap<u64, u64>,
+}
+
+impl Profiler {
+    /// A profiler for `memory_size` bytes of memory and a program loaded at
+    /// `program`.
+    pub fn new(memory_size: usize, program: u16) -> Self {
+        return Profiler {
+            counts: vec![0; memory_size],
+            stack: Vec::new(),
+            paths: vec![Vec::new()],
+            path_ids: HashMap::from([(Vec::new(), 0)]),
+            path_counts: vec![0],
+            path: 0,
+            calls: BTreeMap::new(),
+            program,
+            total: 0,
+            key_wait: 0,
+            vblank_wait: 0,
+            delay_wait: 0,
+            poll: None,
+            frames: 0,
+            frame_cycles: 0,
+            frame_draws: 0,
+            max_frame_cycles: 0,
+            draws_per_frame: BTreeMap::new(),
+        };
+    }
+
+    /// Counts the instruction `opcode` at `pc`, after which execution went
+    /// on at `next` with `depth` return addresses on the stack and the
+    /// delay timer at `delay_timer`.
+    pub fn record(&mut self, pc: u16, opcode: u16, next: u16, depth: usize, delay_timer: u8) -> () {
+        self.total += 1;
+        self.frame_cycles += 1;
+        self.counts[pc as usize] += 1;
+        self.path_counts[self.path] += 1;
+
+        let waited = next == pc;
+        match opcode & 0xF000 {
+            0xD000 if waited => self.vblank_wait += 1,
+            0xD000 => self.frame_draws += 1,
+            0xF000 if opcode & 0xFF == 0x0A && waited => self.key_wait += 1,
+            _ => {}
+        }
+
+        if opcode & 0xF0FF == 0xF007 {
+            if let Some((address, since, true)) = self.poll {
+                if address == pc && since <= POLL_LOOP {
+                    self.delay_wait += since + 1;
+                }
+            }
+            self.poll = Some((pc, 0, delay_timer > 0));
+        } else if let Some((_, since, _)) = self.poll.as_mut() {
+            *since += 1;
+        }
+
+        if opcode & 0xF000 == 0x2000 && depth > self.stack.len() {
+            let target = opcode & 0x0FFF;
+            *self.calls.entry(target).or_insert(0) += 1;
+            self.stack.push(target);
+            self.enter();
+        } else if depth < self.stack.len() {
+            self.stack.truncate(depth);
+            self.enter();
+        }
+    }
+
+    /// Looks up the id of the current call path.
+    fn enter(&mut self) -> () {
+        self.path = match self.path_ids.get(&self.stack) {
+            Some(id) => *id,
+            None => {
+                let id = self.paths.len();
+                self.paths.push(self.stack.clone());
+                self.path_ids.insert(self.stack.clone(), id);
+                self.path_counts.push(0);
+                id
+            }
+        };
+    }
+
+    /// Ends a 60 Hz frame.
+    pub fn frame(&mut self) -> () {
+        if self.frame_cycles == 0 {
+            return;
+        }
+        self.frames += 1;
+        self.max_frame_cycles = self.max_frame_cycles.max(self.frame_cycles);
+        *self.draws_per_frame.entry(self.frame_draws).or_insert(0) += 1;
+        self.frame_cycles = 0;
+        self.frame_draws = 0;
+    }
+
+    fn name(&self, routine: u16) -> String {
+        if routine == self.program {
+            return "program".to_string();
+        }
+        return format!("sub_{:04x}", routine);
+    }
+
+    /// Routine names of a call path, the program first.
+    fn path_names(&self, path: &[u16]) -> Vec<String> {
+        let mut names = vec!["program".to_string()];
+        names.extend(path.iter().map(|routine| self.name(*routine)));
+        return names;
+    }
+
+    /// The call paths in the folded stack format flame graph tools read:
+    /// `program;sub_0300;sub_0350 1234`, one line per path.
+    pub fn folded(&self) -> String {
+        let mut out = String::new();
+        for (path, count) in self.paths.iter().zip(self.path_counts.iter()) {
+            if *count > 0 {
+                writeln!(out, "{} {}", self.path_names(path).join(";"), count).unwrap();
+            }
+        }
+        return out;
+    }
+
+    /// The text report: hotspots, subroutines, waits and sprites per frame.
+    /// `ram` is only read to disassemble the hotspots.
+    pub fn report(&self, ram: &Ram) -> String {
+        let total = self.total.max(1);
+        let percent = |count: u64| 100.0 * count as f64 / total as f64;
+        let mut out = String::new();
+        writeln!(out, "{} instructions in {} frames", self.total, self.frames).unwrap();
+
+        writeln!(out, "\nhotspots").unwrap();
+        writeln!(out, "{:>12} {:>7}  address  instruction", "count", "%").unwrap();
+        let mut hot: Vec<(usize, u64)> = self.counts.iter().copied().enumerate().filter(|(_, c)| *c > 0).collect();
+        hot.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        for (address, count) in hot.into_iter().take(HOTSPOTS) {
+            let opcode = (ram[address] as u16) << 8 | ram[(address + 1).min(ram.size() - 1)] as u16;
+            writeln!(
+                out,
+                "{:>12} {:>6.2}%  {:#06x}   {}",
+                count,
+                percent(count),
+                address,
+                disassemble(opcode)
+            )
+            .unwrap();
+        }
+
+        // inclusive counts a path once per routine on it, however deep the
+        // recursion
+        let mut routines: BTreeMap<u16, (u64, u64)> = BTreeMap::new();
+        for (path, count) in self.paths.iter().zip(self.path_counts.iter()) {
+            let mut seen: Vec<u16> = vec![self.program];
+            seen.extend(path.iter().filter(|routine| **routine != self.program));
+            seen.sort();
+            seen.dedup();
+            for routine in seen {
+                routines.entry(routine).or_insert((0, 0)).0 += count;
+            }
+            let own = path.last().copied().unwrap_or(self.program);
+            routines.entry(own).or_insert((0, 0)).1 += count;
+        }
+        let mut routines: Vec<(u16, (u64, u64))> = routines.into_iter().collect();
+        routines.sort_by(|a, b| b.1 .0.cmp(&a.1 .0).then(a.0.cmp(&b.0)));
+        writeln!(out, "\nsubroutines").unwrap();
+        writeln!(out, "{:>12} {:>7} {:>12} {:>7} {:>8}  routine", "inclusive", "%", "self", "%", "calls").unwrap();
+        for (routine, (inclusive, own)) in routines {
+            let calls = self.calls.get(&routine).copied().unwrap_or(0);
+            writeln!(
+                out,
+                "{:>12} {:>6.2}% {:>12} {:>6.2}% {:>8}  {}",
+                inclusive,
+                percent(inclusive),
+                own,
+                percent(own),
+                calls,
+                self.name(routine)
+            )
+            .unwrap();
+        }
+
+        writeln!(out, "\nwaiting").unwrap();
+        for (what, count) in [
+            ("FX0A key wait", self.key_wait),
+            ("DXYN vblank wait", self.vblank_wait),
+            ("delay timer polling", self.delay_wait),
+        ] {
+            writeln!(out, "{:>12} {:>6.2}%  {}", count, percent(count), what).unwrap();
+        }
+
+        let draws: u64 = self.draws_per_frame.iter().map(|(draws, frames)| draws * frames).sum();
+        let max = self.draws_per_frame.keys().last().copied().unwrap_or(0);
+        writeln!(out, "\nsprites").unwrap();
+        writeln!(
+            out,
+            "{} DXYN, {:.2} per frame, at most {}; at most {} instructions in a frame",
+            draws,
+            draws as f64 / self.frames.max(1) as f64,
+            max,
+            self.max_frame_cycles
+        )
+        .unwrap();
+        for (draws, frames) in self.draws_per_frame.iter() {
+            writeln!(out, "{:>12} frames with {} DXYN", frames, draws).unwrap();
+        }
+        return out;
+    }
+}
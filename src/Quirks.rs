@@ -0,0 +1,132 @@
+/// Behaviours that differ between CHIP-8 interpreters. The names follow the
+/// community chip-8-database, where a quirk being `true` means the
+/// interpreter behaves in the non original way described below.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quirks {
+    /// `8XY6`/`8XYE` shift VX in place instead of shifting VY into VX.
+    pub shift: bool,
+    /// `FX55`/`FX65` advance I by X instead of X + 1.
+    pub memory_increment_by_x: bool,
+    /// `FX55`/`FX65` leave I unchanged.
+    pub memory_leave_i_unchanged: bool,
+    /// Sprites wrap around the screen edges instead of being clipped.
+    pub wrap: bool,
+    /// `BXNN` jumps to XNN + VX instead of NNN + V0.
+    pub jump: bool,
+    /// `DXYN` waits for the next frame before drawing, one sprite per frame.
+    pub vblank: bool,
+    /// `8XY1`/`8XY2`/`8XY3` reset VF to 0.
+    pub logic: bool,
+}
+
+impl Quirks {
+    pub const NAMES: [&'static str; 7] = [
+        "shift",
+        "memoryIncrementByX",
+        "memoryLeaveIUnchanged",
+        "wrap",
+        "jump",
+        "vblank",
+        "logic",
+    ];
+
+    /// The quirks of a platform id from the chip-8-database.
+    pub fn for_platform(id: &str) -> Option<Self> {
+        let none = Quirks {
+            shift: false,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: false,
+            wrap: false,
+            jump: false,
+            vblank: false,
+            logic: false,
+        };
+        return match id {
+            "originalChip8" | "hybridVIP" => Some(Quirks {
+                vblank: true,
+                logic: true,
+                ..none
+            }),
+            "modernChip8" => Some(none),
+            "chip48" => Some(Quirks {
+                shift: true,
+                memory_increment_by_x: true,
+                jump: true,
+                ..none
+            }),
+            "superchip1" | "superchip" => Some(Quirks {
+                shift: true,
+                memory_leave_i_unchanged: true,
+                jump: true,
+                ..none
+            }),
+            "xochip" => Some(Quirks { wrap: true, ..none }),
+            _ => None,
+        };
+    }
+
+    /// Sets a quirk by its database name, `false` if there is no such quirk.
+    pub fn set(&mut self, name: &str, value: bool) -> bool {
+        match name {
+            "shift" => self.shift = value,
+            "memoryIncrementByX" => self.memory_increment_by_x = value,
+            "memoryLeaveIUnchanged" => self.memory_leave_i_unchanged = value,
+            "wrap" => self.wrap = value,
+            "jump" => self.jump = value,
+            "vblank" => self.vblank = value,
+            "logic" => self.logic = value,
+            _ => return false,
+        }
+        return true;
+    }
+
+    pub fn get(&self, name: &str) -> Option<bool> {
+        return match name {
+            "shift" => Some(self.shift),
+            "memoryIncrementByX" => Some(self.memory_increment_by_x),
+            "memoryLeaveIUnchanged" => Some(self.memory_leave_i_unchanged),
+            "wrap" => Some(self.wrap),
+            "jump" => Some(self.jump),
+            "vblank" => Some(self.vblank),
+            "logic" => Some(self.logic),
+            _ => None,
+        };
+    }
+
+    /// Names of the quirks that are on, comma separated.
+    pub fn to_list(&self) -> String {
+        return Quirks::NAMES
+            .iter()
+            .filter(|name| self.get(name) == Some(true))
+            .copied()
+            .collect::<Vec<_>>()
+            .join(",");
+    }
+
+    /// Parses the output of `to_list`, quirks not named are off.
+    pub fn from_list(list: &str) -> Option<Self> {
+        let mut quirks = Quirks::for_platform("modernChip8").unwrap();
+        for name in list.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            if !quirks.set(name, true) {
+                return None;
+            }
+        }
+        return Some(quirks);
+    }
+}
+
+impl Default for Quirks {
+    /// What krhip8 has always done: shifts and `FX55`/`FX65` behave like
+    /// SUPER-CHIP, everything else like a modern CHIP-8.
+    fn default() -> Self {
+        return Quirks {
+            shift: true,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: true,
+            wrap: false,
+            jump: false,
+            vblank: false,
+            logic: false,
+        };
+    }
+}
@@ -21,19 +21,104 @@ pub const FONT: [u8; 81] = [
     0x00,
 ];
 
+/// Bits of `Ram::access`, what happened to a byte since tracking started.
+pub const READ: u8 = 1;
+pub const WRITE: u8 = 2;
+pub const EXECUTE: u8 = 4;
+/// Holds the font, the ROM or something written.
+pub const INITIALIZED: u8 = 8;
+/// A warning about writing the byte was given, later writes aren't flagged.
+pub const WARNED_WRITE: u8 = 16;
+/// A warning about reading the byte was given.
+pub const WARNED_READ: u8 = 32;
+
 #[derive(Debug)]
 pub struct Ram {
-    memory: [u8; 4096],
+    memory: Vec<u8>,
+    /// Access bits of every byte, when tracked.
+    access: Option<Vec<u8>>,
 }
 
 impl Ram {
+    /// Memory of `size` bytes with the font loaded at 0x50.
+    pub fn with_size(size: usize) -> Self {
+        let mut ram = Ram {
+            memory: vec![0; size],
+            access: None,
+        };
+
+        for i in 0x50..0xA1 {
+            ram.memory[i] = FONT[i - 0x50];
+        }
+        return ram;
+    }
+
+    pub fn size(&self) -> usize {
+        return self.memory.len();
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        return &self.memory;
+    }
+
+    /// The big endian word at `address`, `None` when it doesn't fit in
+    /// memory.
+    pub fn opcode(&self, address: usize) -> Option<u16> {
+        let bytes = self.memory.get(address..address.checked_add(2)?)?;
+        return Some((bytes[0] as u16) << 8 | bytes[1] as u16);
+    }
+
+    /// Starts or stops recording the access bits of every byte. Starting
+    /// marks the font as initialized and forgets earlier accesses.
+    pub fn track_access(&mut self, on: bool) -> () {
+        self.access = on.then(|| {
+            let mut access = vec![0; self.memory.len()];
+            access[0x50..0xA1].fill(INITIALIZED);
+            access
+        });
+    }
+
+    /// Whether access bits are being recorded.
+    #[inline]
+    pub fn tracking(&self) -> bool {
+        return self.access.is_some();
+    }
+
+    /// The access bits of `address`, `None` when not tracking or past the
+    /// end of memory.
+    pub fn access(&self, address: usize) -> Option<u8> {
+        return self.access.as_ref()?.get(address).copied();
+    }
+
+    /// Adds `bits` to the access bits of `address`, when tracking.
+    pub fn mark(&mut self, address: usize, bits: u8) -> () {
+        if let Some(access) = self.access.as_mut().and_then(|access| access.get_mut(address)) {
+            *access |= bits;
+        }
+    }
+
+    /// Prints memory as a hex dump, 16 bytes per row. Rows of zeroes are
+    /// left out.
     pub fn debug(&self) -> () {
-        for c in 0..0xFFF {
-            print!("| {}:  {:#x} | ", c, self.memory[c]);
+        for (row, bytes) in self.memory.chunks(16).enumerate() {
+            if bytes.iter().all(|b| *b == 0) {
+                continue;
+            }
+            let hex: Vec<String> = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+            let ascii: String = bytes.iter().map(|b| printable(*b)).collect();
+            println!("{:#06x}  {}  |{}|\r", row * 16, hex.join(" "), ascii);
         }
     }
 }
 
+/// The byte as ASCII, `.` when it isn't printable.
+pub fn printable(byte: u8) -> char {
+    if (0x20..0x7F).contains(&byte) {
+        return byte as char;
+    }
+    return '.';
+}
+
 #[derive(Debug)]
 pub enum RamErrors {
     AddressOutOfBounds,
@@ -44,7 +129,7 @@ impl<T: Into<usize> + Copy> Index<T> for Ram {
     //    type Output = Result<u8,RamErrors>;
 
     fn index(&self, address: T) -> &Self::Output {
-        if address.into() > 0xFFF {
+        if address.into() >= self.memory.len() {
             panic!("AddressOutOfBounds");
         } else {
             return &self.memory[address.into()];
@@ -55,7 +140,7 @@ impl<T: Into<usize> + Copy> Index<T> for Ram {
 impl<T: Into<usize> + Copy> IndexMut<T> for Ram {
     #[inline]
     fn index_mut(&mut self, index: T) -> &mut Self::Output {
-        if index.into() > 0xFFF {
+        if index.into() >= self.memory.len() {
             panic!("AddressOutOfBounds");
         } else {
             return IndexMut::index_mut(&mut self.memory as &mut [u8], index.into());
@@ -65,23 +150,21 @@ impl<T: Into<usize> + Copy> IndexMut<T> for Ram {
 
 impl Default for Ram {
     fn default() -> Self {
-        let mut ram = Ram { memory: [0; 4096] };
-
-        for i in 0x50..0xA1 {
-            ram.memory[i] = FONT[i - 0x50];
-        }
-        return ram;
+        return Ram::with_size(4096);
     }
 }
 
 impl Restart for Ram {
     fn restart(&mut self) -> () {
-        for i in 0x50..=0xA1 {
+        for i in 0x50..0xA1 {
             self.memory[i] = FONT[i - 0x50];
         }
 
-        for i in 0xA2..=0xFFF {
+        for i in 0xA1..self.memory.len() {
             self.memory[i] = 0;
         }
+        if self.access.is_some() {
+            self.track_access(true);
+        }
     }
 }
@@ -0,0 +1,88 @@
+use crate::Restart;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// The random number source behind `CXKK`.
+///
+/// Everything is derived from the seed, so a run can be reproduced by
+/// reusing it. Normally the numbers come from ChaCha8, whose output for a
+/// seed is fixed, unlike `rand`'s `StdRng`. With `vip` they come from the
+/// COSMAC VIP interpreter's routine instead.
+#[derive(Debug, Clone)]
+pub struct Random {
+    seed: u64,
+    rng: ChaCha8Rng,
+    vip: Option<Vip>,
+}
+
+/// State of the VIP routine: the interpreter page it reads and `R9`.
+#[derive(Debug, Clone)]
+struct Vip {
+    page: [u8; 256],
+    r9: u16,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        let mut bytes = [0; 32];
+        bytes[..8].copy_from_slice(&seed.to_le_bytes());
+        return Random {
+            seed,
+            rng: ChaCha8Rng::from_seed(bytes),
+            vip: None,
+        };
+    }
+
+    /// The CXKK routine of the COSMAC VIP interpreter, starting with the
+    /// low 16 bits of `seed` in `R9`. Every call increments `R9`, adds the
+    /// byte of `page` its low half points at to its high half, then adds
+    /// that sum shifted right through the carry to itself, which becomes
+    /// the new high half and the number. `page` is the interpreter page
+    /// the routine runs from, 0x100 to 0x1FF of a dump of the interpreter.
+    pub fn vip(seed: u64, page: &[u8; 256]) -> Self {
+        return Random {
+            vip: Some(Vip {
+                page: *page,
+                r9: seed as u16,
+            }),
+            ..Random::new(seed)
+        };
+    }
+
+    pub fn seed(&self) -> u64 {
+        return self.seed;
+    }
+
+    /// Whether this is the VIP routine.
+    pub fn is_vip(&self) -> bool {
+        return self.vip.is_some();
+    }
+
+    pub fn draw(&mut self) -> u8 {
+        let Some(vip) = self.vip.as_mut() else {
+            return self.rng.gen::<u8>();
+        };
+        vip.r9 = vip.r9.wrapping_add(1);
+        let (sum, carry) = vip.page[vip.r9 as usize & 0xFF].overflowing_add((vip.r9 >> 8) as u8);
+        let shifted = (carry as u8) << 7 | sum >> 1;
+        let number = sum.wrapping_add(shifted);
+        vip.r9 = (number as u16) << 8 | vip.r9 & 0xFF;
+        return number;
+    }
+}
+
+impl Default for Random {
+    /// A fresh random seed, use `seed()` to find out which one.
+    fn default() -> Self {
+        return Random::new(rand::random::<u64>());
+    }
+}
+
+impl Restart for Random {
+    fn restart(&mut self) -> () {
+        *self = match self.vip.as_ref() {
+            Some(vip) => Random::vip(self.seed, &vip.page),
+            None => Random::new(self.seed),
+        };
+    }
+}
@@ -0,0 +1,247 @@
+use crate::Screenshot::{Frame, Screenshot};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{stdout, BufWriter, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordFormat {
+    /// Looping animated GIF. Runs of identical frames are merged into one
+    /// longer frame, which keeps files of mostly static games small.
+    Gif,
+    /// YUV4MPEG2 stream at 60 fps, which ffmpeg and most encoders read.
+    Y4m,
+    /// Bare RGB24 frames one after another, for
+    /// `ffmpeg -f rawvideo -pix_fmt rgb24 -s WxH -r 60 -i -`.
+    Rgb,
+}
+
+impl RecordFormat {
+    /// `.gif` and `.rgb` by extension, anything else (and `-`) is Y4M.
+    pub fn from_path(path: &str) -> Self {
+        let path = path.to_ascii_lowercase();
+        if path.ends_with(".gif") {
+            return RecordFormat::Gif;
+        } else if path.ends_with(".rgb") {
+            return RecordFormat::Rgb;
+        }
+        return RecordFormat::Y4m;
+    }
+}
+
+/// Captures one frame per call to `capture` into a GIF or video stream,
+/// using the scale and palette of a `Screenshot`.
+pub struct Recorder {
+    out: Box<dyn Write>,
+    format: RecordFormat,
+    style: Screenshot,
+    frames: usize,
+    /// GIF only: the frame waiting to be written and the frame it started at.
+    pending: Option<(Frame, usize)>,
+}
+
+impl Recorder {
+    /// Writes to `path`, or to stdout when the path is `-`.
+    pub fn create(path: &str, style: Screenshot) -> std::io::Result<Self> {
+        let out: Box<dyn Write> = if path == "-" {
+            Box::new(BufWriter::new(stdout()))
+        } else {
+            Box::new(BufWriter::new(File::create(path)?))
+        };
+        return Ok(Recorder::new(out, RecordFormat::from_path(path), style));
+    }
+
+    pub fn new(out: Box<dyn Write>, format: RecordFormat, style: Screenshot) -> Self {
+        return Recorder {
+            out,
+            format,
+            style,
+            frames: 0,
+            pending: None,
+        };
+    }
+
+    pub fn frames(&self) -> usize {
+        return self.frames;
+    }
+
+    pub fn capture(&mut self, frame: &Frame) -> std::io::Result<()> {
+        let frame = frame.scaled(self.style.scale);
+        if self.frames == 0 {
+            self.write_header(&frame)?;
+        }
+
+        match self.format {
+            RecordFormat::Gif => {
+                if let Some((pending, start)) = self.pending.take() {
+                    if pending == frame {
+                        self.pending = Some((pending, start));
+                    } else {
+                        self.write_gif_frame(&pending, start, self.frames)?;
+                        self.pending = Some((frame, self.frames));
+                    }
+                } else {
+                    self.pending = Some((frame, self.frames));
+                }
+            }
+            RecordFormat::Y4m => {
+                self.out.write_all(b"FRAME\n")?;
+                let rgb = self.rgb(&frame);
+                let (mut y, mut u, mut v) = (Vec::new(), Vec::new(), Vec::new());
+                for px in rgb.chunks(3) {
+                    let (r, g, b) = (px[0] as f32, px[1] as f32, px[2] as f32);
+                    y.push((0.299 * r + 0.587 * g + 0.114 * b).round() as u8);
+                    u.push((128.0 - 0.168736 * r - 0.331264 * g + 0.5 * b).round() as u8);
+                    v.push((128.0 + 0.5 * r - 0.418688 * g - 0.081312 * b).round() as u8);
+                }
+                self.out.write_all(&y)?;
+                self.out.write_all(&u)?;
+                self.out.write_all(&v)?;
+            }
+            RecordFormat::Rgb => {
+                let rgb = self.rgb(&frame);
+                self.out.write_all(&rgb)?;
+            }
+        }
+        self.frames += 1;
+        return Ok(());
+    }
+
+    /// Writes out anything still buffered and closes the stream.
+    pub fn finish(mut self) -> std::io::Result<()> {
+        if let Some((pending, start)) = self.pending.take() {
+            self.write_gif_frame(&pending, start, self.frames)?;
+        }
+        if self.format == RecordFormat::Gif && self.frames > 0 {
+            self.out.write_all(&[0x3B])?;
+        }
+        return self.out.flush();
+    }
+
+    fn rgb(&self, frame: &Frame) -> Vec<u8> {
+        return frame
+            .pixels
+            .iter()
+            .flat_map(|pixel| self.style.palette.0[*pixel as usize % self.style.palette.0.len()])
+            .collect();
+    }
+
+    fn write_header(&mut self, frame: &Frame) -> std::io::Result<()> {
+        match self.format {
+            RecordFormat::Gif => {
+                self.out.write_all(b"GIF89a")?;
+                self.out.write_all(&(frame.width as u16).to_le_bytes())?;
+                self.out.write_all(&(frame.height as u16).to_le_bytes())?;
+                let bits = self.colour_bits();
+                // global colour table of 2^bits entries, background colour 0
+                self.out.write_all(&[0xF0 | (bits - 1), 0, 0])?;
+                for i in 0..(1 << bits) {
+                    let colour = self.style.palette.0.get(i).copied().unwrap_or([0; 3]);
+                    self.out.write_all(&colour)?;
+                }
+                // NETSCAPE2.0 extension, loop forever
+                self.out.write_all(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00")?;
+            }
+            RecordFormat::Y4m => {
+                writeln!(
+                    self.out,
+                    "YUV4MPEG2 W{} H{} F60:1 Ip A1:1 C444 XCOLORRANGE=FULL",
+                    frame.width, frame.height
+                )?;
+            }
+            RecordFormat::Rgb => {}
+        }
+        return Ok(());
+    }
+
+    fn colour_bits(&self) -> u8 {
+        let mut bits = 1;
+        while (1 << bits) < self.style.palette.0.len() && bits < 8 {
+            bits += 1;
+        }
+        return bits;
+    }
+
+    /// Writes a frame shown from frame `start` until frame `end`, at 60 fps.
+    fn write_gif_frame(&mut self, frame: &Frame, start: usize, end: usize) -> std::io::Result<()> {
+        // GIF delays are in 1/100 s, viewers treat anything below 2 as slow
+        let delay = ((end * 100 / 60) - (start * 100 / 60)).max(2) as u16;
+        self.out.write_all(&[0x21, 0xF9, 0x04, 0x00])?;
+        self.out.write_all(&delay.to_le_bytes())?;
+        self.out.write_all(&[0x00, 0x00])?;
+
+        self.out.write_all(&[0x2C, 0, 0, 0, 0])?;
+        self.out.write_all(&(frame.width as u16).to_le_bytes())?;
+        self.out.write_all(&(frame.height as u16).to_le_bytes())?;
+        self.out.write_all(&[0x00])?;
+
+        let min_code_size = self.colour_bits().max(2);
+        let colours = self.style.palette.0.len() as u8;
+        let indices: Vec<u8> = frame.pixels.iter().map(|p| p % colours).collect();
+        let data = lzw_encode(min_code_size, &indices);
+
+        self.out.write_all(&[min_code_size])?;
+        for block in data.chunks(255) {
+            self.out.write_all(&[block.len() as u8])?;
+            self.out.write_all(block)?;
+        }
+        return self.out.write_all(&[0x00]);
+    }
+}
+
+/// Variable width LZW as used by GIF, codes packed least significant bit
+/// first.
+fn lzw_encode(min_code_size: u8, pixels: &[u8]) -> Vec<u8> {
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+
+    let mut out = Vec::new();
+    let mut acc = 0u32;
+    let mut nbits = 0;
+    let mut emit = |code: u16, width: u8, out: &mut Vec<u8>| {
+        acc |= (code as u32) << nbits;
+        nbits += width;
+        while nbits >= 8 {
+            out.push(acc as u8);
+            acc >>= 8;
+            nbits -= 8;
+        }
+    };
+
+    let mut dict: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next = end + 1;
+    let mut width = min_code_size + 1;
+    emit(clear, width, &mut out);
+
+    let Some((first, rest)) = pixels.split_first() else {
+        emit(end, width, &mut out);
+        emit(0, 7, &mut out);
+        return out;
+    };
+
+    let mut prefix = *first as u16;
+    for pixel in rest {
+        if let Some(code) = dict.get(&(prefix, *pixel)) {
+            prefix = *code;
+            continue;
+        }
+        emit(prefix, width, &mut out);
+        if next < 4096 {
+            dict.insert((prefix, *pixel), next);
+            next += 1;
+            if next > (1 << width) && width < 12 {
+                width += 1;
+            }
+        } else {
+            emit(clear, width, &mut out);
+            dict.clear();
+            next = end + 1;
+            width = min_code_size + 1;
+        }
+        prefix = *pixel as u16;
+    }
+    emit(prefix, width, &mut out);
+    emit(end, width, &mut out);
+    // pad the last byte
+    emit(0, 7, &mut out);
+    return out;
+}
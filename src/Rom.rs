@@ -0,0 +1,137 @@
+use crate::Cfg::{Cfg, Flow};
+use crate::Helpers::*;
+use std::fmt;
+
+/// The machine a ROM was written for. Decides how much memory there is and
+/// where programs are loaded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Platform {
+    /// The original COSMAC VIP interpreter.
+    Chip8,
+    /// SUPER-CHIP on the HP48.
+    Schip,
+    /// XO-CHIP, with 64 KiB of memory.
+    XoChip,
+    /// CHIP-8 on the ETI-660, programs start at 0x600.
+    Eti660,
+}
+
+impl Platform {
+    pub fn memory_size(&self) -> usize {
+        return match self {
+            Platform::XoChip => 0x10000,
+            _ => 0x1000,
+        };
+    }
+
+    pub fn load_address(&self) -> u16 {
+        return match self {
+            Platform::Eti660 => 0x600,
+            _ => 0x200,
+        };
+    }
+
+    /// Return addresses the stack holds: 12 on the VIP, 16 from SCHIP on.
+    pub fn stack_depth(&self) -> usize {
+        return match self {
+            Platform::Chip8 | Platform::Eti660 => 12,
+            Platform::Schip | Platform::XoChip => 16,
+        };
+    }
+
+    /// Whether the platform adds instructions to CHIP-8. krhip8 only runs
+    /// the CHIP-8 ones, a program using the others stops at the first.
+    pub fn is_extended(&self) -> bool {
+        return matches!(self, Platform::Schip | Platform::XoChip);
+    }
+
+    /// Largest ROM that fits between the load address and the end of memory.
+    pub fn max_rom_size(&self) -> usize {
+        return self.memory_size() - self.load_address() as usize;
+    }
+
+    /// The name `parse` reads back.
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Platform::Chip8 => "chip8",
+            Platform::Schip => "schip",
+            Platform::XoChip => "xochip",
+            Platform::Eti660 => "eti660",
+        };
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        return match name.to_ascii_lowercase().as_str() {
+            "chip8" | "chip-8" | "vip" => Some(Platform::Chip8),
+            "schip" | "superchip" | "super-chip" => Some(Platform::Schip),
+            "xochip" | "xo-chip" => Some(Platform::XoChip),
+            "eti660" | "eti-660" => Some(Platform::Eti660),
+            _ => None,
+        };
+    }
+
+    /// Guesses the platform from the instructions in the ROM. Only opcodes
+    /// that mean nothing on a plain CHIP-8 count: the SCHIP scroll, hires
+    /// and big font instructions, and the XO-CHIP long `I` load, planes,
+    /// audio and scroll up instructions. Sprite data is full of bytes that
+    /// look like those, so only instructions reachable from the start of the
+    /// program (see `Cfg`) are looked at. The ETI-660 can't be told apart by
+    /// content. ROMs too big for 4 KiB are taken as XO-CHIP.
+    pub fn detect(rom: &[u8]) -> Self {
+        if rom.len() > Platform::Chip8.max_rom_size() {
+            return Platform::XoChip;
+        }
+
+        let (mut schip, mut xochip) = (false, false);
+        for &uc in Cfg::build_with(rom, 0x200, Flow::of_extended).instructions.values() {
+            match bg_id(uc) {
+                0x0 if uc & 0xFFF0 == 0x00D0 => xochip = true,
+                0x0 if uc & 0xFFF0 == 0x00C0 || (0x00FB..=0x00FF).contains(&uc) => schip = true,
+                0x5 if end_id(uc) == 0x2 || end_id(uc) == 0x3 => xochip = true,
+                0xD if n(uc) == 0 => schip = true,
+                0xF if uc == 0xF000 || uc == 0xF002 || matches!(two_end_id(uc), 0x01 | 0x3A) => xochip = true,
+                0xF if matches!(two_end_id(uc), 0x30 | 0x75 | 0x85) => schip = true,
+                _ => {}
+            }
+        }
+
+        if xochip {
+            return Platform::XoChip;
+        } else if schip {
+            return Platform::Schip;
+        }
+        return Platform::Chip8;
+    }
+}
+
+#[derive(Debug)]
+pub enum RomErrors {
+    Empty,
+    TooLarge {
+        size: usize,
+        max: usize,
+        platform: Platform,
+    },
+    Io(std::io::Error),
+}
+
+impl fmt::Display for RomErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            RomErrors::Empty => write!(f, "the rom is empty"),
+            RomErrors::TooLarge {
+                size,
+                max,
+                platform,
+            } => write!(
+                f,
+                "the rom is {} bytes but {:?} only has room for {} bytes from {:#05x}",
+                size,
+                platform,
+                max,
+                platform.load_address()
+            ),
+            RomErrors::Io(err) => write!(f, "{}", err),
+        };
+    }
+}
@@ -0,0 +1,213 @@
+use crate::Chip8::Chip8;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// A copy of the screen. Each pixel holds a bit per display plane, so a
+/// frame of krhip8's 64x32 CHIP-8 screen only uses 0 and 1. Other sizes and
+/// values up to 3, as on a two plane XO-CHIP screen, only come from frames
+/// built by hand.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Frame {
+    pub fn from_chip8(chip8: &Chip8) -> Self {
+        let display = chip8.framebuffer();
+        let (width, height) = (display.width(), display.height());
+        return Frame {
+            width,
+            height,
+            pixels: display.unpack(),
+        };
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        return self.pixels[y * self.width + x];
+    }
+
+    /// Every pixel becomes a `scale` x `scale` square.
+    pub fn scaled(&self, scale: usize) -> Frame {
+        if scale <= 1 {
+            return self.clone();
+        }
+        let (width, height) = (self.width * scale, self.height * scale);
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                pixels.push(self.get(x / scale, y / scale));
+            }
+        }
+        return Frame {
+            width,
+            height,
+            pixels,
+        };
+    }
+}
+
+/// Colours for each plane combination, index 0 is the background.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette(pub Vec<[u8; 3]>);
+
+impl Palette {
+    /// Parses `RRGGBB,RRGGBB,...`, 2 to 256 colours.
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut colours = Vec::new();
+        for colour in text.split(',') {
+            let colour = colour.trim().trim_start_matches('#');
+            if colour.len() != 6 {
+                return None;
+            }
+            let rgb = u32::from_str_radix(colour, 16).ok()?;
+            colours.push([(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8]);
+        }
+        if colours.len() < 2 || colours.len() > 256 {
+            return None;
+        }
+        return Some(Palette(colours));
+    }
+}
+
+impl Default for Palette {
+    /// Black background, white pixels and the XO-CHIP plane colours.
+    fn default() -> Self {
+        return Palette(vec![
+            [0x00, 0x00, 0x00],
+            [0xFF, 0xFF, 0xFF],
+            [0xAA, 0xAA, 0xAA],
+            [0x55, 0x55, 0x55],
+        ]);
+    }
+}
+
+/// Writes frames to image files, each CHIP-8 pixel becoming a
+/// `scale` x `scale` square.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Screenshot {
+    pub scale: usize,
+    pub palette: Palette,
+}
+
+impl Default for Screenshot {
+    fn default() -> Self {
+        return Screenshot {
+            scale: 1,
+            palette: Default::default(),
+        };
+    }
+}
+
+impl Screenshot {
+    /// Picks the format from the extension, PNG for `.png` and PBM otherwise.
+    pub fn save(&self, frame: &Frame, path: &str) -> std::io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        if path.to_ascii_lowercase().ends_with(".png") {
+            self.write_png(frame, &mut out)?;
+        } else {
+            self.write_pbm(frame, &mut out)?;
+        }
+        return out.flush();
+    }
+
+    /// Binary PBM, any pixel with a plane set is black. PBM has no colours,
+    /// the palette is ignored.
+    pub fn write_pbm<W: Write>(&self, frame: &Frame, out: &mut W) -> std::io::Result<()> {
+        let width = frame.width * self.scale;
+        let height = frame.height * self.scale;
+        write!(out, "P4\n{} {}\n", width, height)?;
+
+        let mut row = vec![0u8; width.div_ceil(8)];
+        for y in 0..height {
+            row.fill(0);
+            for x in 0..width {
+                if frame.get(x / self.scale, y / self.scale) != 0 {
+                    row[x / 8] |= 0x80 >> (x % 8);
+                }
+            }
+            out.write_all(&row)?;
+        }
+        return Ok(());
+    }
+
+    /// Indexed colour PNG using the palette.
+    pub fn write_png<W: Write>(&self, frame: &Frame, out: &mut W) -> std::io::Result<()> {
+        let width = frame.width * self.scale;
+        let height = frame.height * self.scale;
+
+        out.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+        let mut ihdr = Vec::with_capacity(13);
+        ihdr.extend_from_slice(&(width as u32).to_be_bytes());
+        ihdr.extend_from_slice(&(height as u32).to_be_bytes());
+        // 8 bit depth, indexed colour, deflate, no filtering, no interlace
+        ihdr.extend_from_slice(&[8, 3, 0, 0, 0]);
+        png_chunk(out, b"IHDR", &ihdr)?;
+
+        // a PNG palette holds at most 256 colours
+        let colours = self.palette.0.len().min(256);
+        let plte: Vec<u8> = self.palette.0[..colours].iter().flatten().copied().collect();
+        png_chunk(out, b"PLTE", &plte)?;
+
+        let mut raw = Vec::with_capacity((width + 1) * height);
+        for y in 0..height {
+            raw.push(0);
+            for x in 0..width {
+                let pixel = frame.get(x / self.scale, y / self.scale);
+                raw.push((pixel as usize % colours) as u8);
+            }
+        }
+        png_chunk(out, b"IDAT", &zlib_stored(&raw))?;
+        return png_chunk(out, b"IEND", &[]);
+    }
+}
+
+fn png_chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> std::io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    let crc = crc32(&[kind.as_slice(), data].concat());
+    return out.write_all(&crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    return !crc;
+}
+
+/// Zlib stream made of uncompressed deflate blocks. Screens are small, so
+/// skipping compression keeps this short and dependency free.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xFFFF).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        out.push(blocks.peek().is_none() as u8);
+        let len = block.len() as u16;
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    out.extend_from_slice(&((b << 16) | a).to_be_bytes());
+    return out;
+}
@@ -0,0 +1,163 @@
+use crate::Chip8::Chip8;
+use crate::Screenshot::{Frame, Screenshot};
+use std::fmt;
+
+/// Input scripts drive headless runs frame by frame:
+///
+/// ```text
+/// frame 30: press 5; frame 35: release 5
+/// frame 120: screenshot out.png
+/// frame 200: assert-pixel 10,4 on
+/// ```
+///
+/// Entries are separated by `;` or newlines and `#` starts a comment.
+/// Keys are hex digits, pixels are `x,y` and `on`/`off`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Press(usize),
+    Release(usize),
+    Screenshot(String),
+    AssertPixel { x: usize, y: usize, on: bool },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Command {
+    pub frame: usize,
+    pub action: Action,
+}
+
+#[derive(Debug)]
+pub enum ScriptErrors {
+    /// `entry` on the 1-based `line` of the script didn't parse.
+    Parse {
+        line: usize,
+        entry: String,
+        reason: &'static str,
+    },
+    AssertFailed { frame: usize, x: usize, y: usize, on: bool },
+    Io(std::io::Error),
+}
+
+impl fmt::Display for ScriptErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            ScriptErrors::Parse { line, entry, reason } => write!(f, "line {}: `{}`: {}", line, entry, reason),
+            ScriptErrors::AssertFailed { frame, x, y, on } => write!(
+                f,
+                "frame {}: expected pixel {},{} to be {}",
+                frame,
+                x,
+                y,
+                if *on { "on" } else { "off" }
+            ),
+            ScriptErrors::Io(err) => write!(f, "{}", err),
+        };
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InputScript {
+    commands: Vec<Command>,
+}
+
+fn parse_entry(entry: &str, line: usize) -> Result<Command, ScriptErrors> {
+    let fail = |reason| ScriptErrors::Parse {
+        line,
+        entry: entry.to_string(),
+        reason,
+    };
+
+    let rest = entry.strip_prefix("frame").ok_or(fail("expected `frame N: action`"))?;
+    let (frame, action) = rest.split_once(':').ok_or(fail("missing `:`"))?;
+    let frame = frame.trim().parse::<usize>().map_err(|_| fail("bad frame number"))?;
+
+    let key = |key: &str| match usize::from_str_radix(key, 16) {
+        Ok(key) if key <= 0xF => Ok(key),
+        _ => Err(fail("keys go from 0 to F")),
+    };
+
+    let mut words = action.split_whitespace();
+    let action = match (words.next(), words.next(), words.next()) {
+        (Some("press"), Some(k), None) => Action::Press(key(k)?),
+        (Some("release"), Some(k), None) => Action::Release(key(k)?),
+        (Some("screenshot"), Some(path), None) => Action::Screenshot(path.to_string()),
+        (Some("assert-pixel"), Some(pos), Some(state)) => {
+            let (x, y) = pos.split_once(',').ok_or(fail("pixels are given as x,y"))?;
+            let x = x.parse::<usize>().map_err(|_| fail("bad x"))?;
+            let y = y.parse::<usize>().map_err(|_| fail("bad y"))?;
+            if x > 63 || y > 31 {
+                return Err(fail("pixel outside the screen"));
+            }
+            let on = match state {
+                "on" => true,
+                "off" => false,
+                _ => return Err(fail("pixels are `on` or `off`")),
+            };
+            Action::AssertPixel { x, y, on }
+        }
+        _ => return Err(fail("unknown action")),
+    };
+    return Ok(Command { frame, action });
+}
+
+impl InputScript {
+    pub fn parse(text: &str) -> Result<Self, ScriptErrors> {
+        let mut commands = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap();
+            for entry in line.split(';') {
+                let entry = entry.trim();
+                if !entry.is_empty() {
+                    commands.push(parse_entry(entry, number + 1)?);
+                }
+            }
+        }
+        // Stable, so commands for the same frame keep their order.
+        commands.sort_by_key(|command| command.frame);
+        return Ok(InputScript { commands });
+    }
+
+    pub fn load(path: &str) -> Result<Self, ScriptErrors> {
+        let text = std::fs::read_to_string(path).map_err(ScriptErrors::Io)?;
+        return InputScript::parse(&text);
+    }
+
+    pub fn commands(&self) -> &[Command] {
+        return &self.commands;
+    }
+
+    /// Frame of the last command, a run can stop once it is done.
+    pub fn last_frame(&self) -> usize {
+        return self.commands.last().map_or(0, |command| command.frame);
+    }
+
+    /// Carries out the commands for `frame`, before the frame runs.
+    /// Screenshots are taken with `screenshot`'s scale and palette.
+    pub fn apply(
+        &self,
+        frame: usize,
+        chip8: &mut Chip8,
+        screenshot: &Screenshot,
+    ) -> Result<(), ScriptErrors> {
+        for command in self.commands.iter().filter(|c| c.frame == frame) {
+            match &command.action {
+                Action::Press(key) => chip8.keys[*key] = 1,
+                Action::Release(key) => chip8.keys[*key] = 0,
+                Action::Screenshot(path) => screenshot
+                    .save(&Frame::from_chip8(chip8), path)
+                    .map_err(ScriptErrors::Io)?,
+                Action::AssertPixel { x, y, on } => {
+                    if chip8.pixel(*x, *y) != *on {
+                        return Err(ScriptErrors::AssertFailed {
+                            frame,
+                            x: *x,
+                            y: *y,
+                            on: *on,
+                        });
+                    }
+                }
+            }
+        }
+        return Ok(());
+    }
+}
@@ -0,0 +1,160 @@
+use crate::Ram::Ram;
+use crate::Screenshot::Frame;
+use std::ops::Range;
+
+/// The font glyphs, 16 sprites of 5 rows from this address on.
+pub const FONT_ADDRESS: u16 = 0x50;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpriteShape {
+    /// 8 pixels wide and 1 to 15 rows high, one byte per row, as `DXYN`
+    /// draws them.
+    Lores { height: usize },
+    /// The 16x16 sprites of SCHIP's `DXY0`, two bytes per row.
+    Hires,
+}
+
+impl SpriteShape {
+    pub fn width(&self) -> usize {
+        return match self {
+            SpriteShape::Lores { .. } => 8,
+            SpriteShape::Hires => 16,
+        };
+    }
+
+    pub fn height(&self) -> usize {
+        return match self {
+            SpriteShape::Lores { height } => *height,
+            SpriteShape::Hires => 16,
+        };
+    }
+
+    /// Bytes one sprite takes in memory.
+    pub fn bytes(&self) -> usize {
+        return self.width() / 8 * self.height();
+    }
+}
+
+/// A sprite read from memory, with the same bit layout `draw_sprite` uses:
+/// rows top to bottom, the most significant bit is the leftmost pixel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sprite {
+    pub address: u16,
+    pub shape: SpriteShape,
+    /// One entry per row, the leftmost pixel in the highest bit used.
+    pub rows: Vec<u16>,
+}
+
+impl Sprite {
+    /// `None` when the sprite would run past the end of memory.
+    pub fn read(ram: &Ram, address: u16, shape: SpriteShape) -> Option<Self> {
+        let start = address as usize;
+        if start + shape.bytes() > ram.size() {
+            return None;
+        }
+        let rows = (0..shape.height())
+            .map(|row| match shape {
+                SpriteShape::Lores { .. } => ram[start + row] as u16,
+                SpriteShape::Hires => (ram[start + 2 * row] as u16) << 8 | ram[start + 2 * row + 1] as u16,
+            })
+            .collect();
+        return Some(Sprite {
+            address,
+            shape,
+            rows,
+        });
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        let width = self.shape.width();
+        return (self.rows[y] >> (width - 1 - x)) & 1 == 1;
+    }
+
+    /// Whether `address` is one of the bytes of this sprite.
+    pub fn contains(&self, address: u16) -> bool {
+        let start = self.address as usize;
+        return (start..start + self.shape.bytes()).contains(&(address as usize));
+    }
+}
+
+/// Sprites one after another through `range`, as many whole sprites as
+/// fit.
+pub fn sprites_in(ram: &Ram, range: Range<usize>, shape: SpriteShape) -> Vec<Sprite> {
+    let mut sprites = Vec::new();
+    let mut address = range.start;
+    while address + shape.bytes() <= range.end {
+        match Sprite::read(ram, address as u16, shape) {
+            Some(sprite) => sprites.push(sprite),
+            None => break,
+        }
+        address += shape.bytes();
+    }
+    return sprites;
+}
+
+/// The 16 hex digit glyphs of the built in font.
+pub fn font(ram: &Ram) -> Vec<Sprite> {
+    let shape = SpriteShape::Lores { height: 5 };
+    let start = FONT_ADDRESS as usize;
+    return sprites_in(ram, start..start + 16 * 5, shape);
+}
+
+/// Sprites side by side, `columns` to a row, as text: `#` for pixels that
+/// are on and `.` for those that are off, each sprite headed by its address.
+/// The sprite holding the address `highlight` is marked with `*`.
+pub fn text_sheet(sprites: &[Sprite], columns: usize, highlight: Option<u16>) -> Vec<String> {
+    let mut lines = Vec::new();
+    for chunk in sprites.chunks(columns.max(1)) {
+        let width = chunk[0].shape.width();
+        let mut header = String::new();
+        for sprite in chunk {
+            let mark = if highlight.is_some_and(|a| sprite.contains(a)) { '*' } else { ' ' };
+            header.push_str(&format!("{:<w$}", format!("{}{:#06x}", mark, sprite.address), w = width + 2));
+        }
+        lines.push(header.trim_end().to_string());
+        for y in 0..chunk[0].shape.height() {
+            let mut line = String::new();
+            for sprite in chunk {
+                line.push(' ');
+                line.extend((0..width).map(|x| if sprite.pixel(x, y) { '#' } else { '.' }));
+                line.push(' ');
+            }
+            lines.push(line.trim_end().to_string());
+        }
+    }
+    return lines;
+}
+
+/// Sprites laid out on an image, `columns` to a row with a one pixel gap.
+/// Pixels that are on use colour 1, the background of the sprite holding
+/// the address `highlight` colour 3 so it stands out.
+pub fn frame_sheet(sprites: &[Sprite], columns: usize, highlight: Option<u16>) -> Frame {
+    let columns = columns.max(1).min(sprites.len().max(1));
+    let Some(first) = sprites.first() else {
+        return Frame {
+            width: 1,
+            height: 1,
+            pixels: vec![0],
+        };
+    };
+    let (cell_w, cell_h) = (first.shape.width() + 1, first.shape.height() + 1);
+    let rows = sprites.len().div_ceil(columns);
+    let (width, height) = (columns * cell_w + 1, rows * cell_h + 1);
+    let mut pixels = vec![0; width * height];
+
+    for (i, sprite) in sprites.iter().enumerate() {
+        let (left, top) = (1 + (i % columns) * cell_w, 1 + (i / columns) * cell_h);
+        let background = if highlight.is_some_and(|a| sprite.contains(a)) { 3 } else { 0 };
+        for y in 0..sprite.shape.height() {
+            for x in 0..sprite.shape.width() {
+                let colour = if sprite.pixel(x, y) { 1 } else { background };
+                pixels[(top + y) * width + left + x] = colour;
+            }
+        }
+    }
+    return Frame {
+        width,
+        height,
+        pixels,
+    };
+}
@@ -0,0 +1,315 @@
+use crate::Helpers::disassemble;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufWriter, Write};
+use std::ops::RangeInclusive;
+
+/// How each executed instruction is written to the trace file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TraceFormat {
+    /// One human readable line per instruction:
+    /// `pc opcode mnemonic | changed registers | I=.... VF=..`
+    Text,
+    /// 24 bytes per instruction, big endian: pc, opcode, ir, a mask of the
+    /// registers that changed (bit n = Vn) and the sixteen registers after
+    /// the instruction ran.
+    Binary,
+    /// The line based format `trace-diff` compares against, meant to be easy
+    /// to produce from other emulators:
+    ///
+    /// `PC=0200 OP=6A02 I=0000 V0=00 .. VF=00 SP=0 DT=00 ST=00`
+    ///
+    /// `PC` and `OP` are the address and opcode of the instruction, every
+    /// other field is the machine state after it ran. All values are hex,
+    /// fields can come in any order and lines starting with `#` are skipped.
+    Common,
+}
+
+/// State of the machine right after one instruction was executed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TraceEntry {
+    pub pc: u16,
+    pub opcode: u16,
+    pub ir: u16,
+    pub changed: u16,
+    pub v: [u8; 16],
+    pub sp: u8,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+}
+
+impl TraceEntry {
+    pub fn new(pc: u16, opcode: u16, before: &[u8; 16], after: &[u8; 16], ir: u16) -> Self {
+        let mut changed = 0;
+        for i in 0..16 {
+            if before[i] != after[i] {
+                changed |= 1 << i;
+            }
+        }
+        return TraceEntry {
+            pc,
+            opcode,
+            ir,
+            changed,
+            v: *after,
+            sp: 0,
+            delay_timer: 0,
+            sound_timer: 0,
+        };
+    }
+
+    pub fn vf(&self) -> u8 {
+        return self.v[0xF];
+    }
+
+    pub fn write_text<W: Write>(&self, out: &mut W) -> std::io::Result<()> {
+        write!(
+            out,
+            "{:04X} {:04X} {:<16} |",
+            self.pc,
+            self.opcode,
+            disassemble(self.opcode)
+        )?;
+        for i in 0..16 {
+            if self.changed & (1 << i) != 0 {
+                write!(out, " V{:X}={:02X}", i, self.v[i])?;
+            }
+        }
+        return writeln!(out, " | I={:04X} VF={:02X}", self.ir, self.vf());
+    }
+
+    pub fn write_common<W: Write>(&self, out: &mut W) -> std::io::Result<()> {
+        write!(out, "PC={:04X} OP={:04X} I={:04X}", self.pc, self.opcode, self.ir)?;
+        for i in 0..16 {
+            write!(out, " V{:X}={:02X}", i, self.v[i])?;
+        }
+        return writeln!(
+            out,
+            " SP={:X} DT={:02X} ST={:02X}",
+            self.sp, self.delay_timer, self.sound_timer
+        );
+    }
+
+    pub fn write_binary<W: Write>(&self, out: &mut W) -> std::io::Result<()> {
+        out.write_all(&self.pc.to_be_bytes())?;
+        out.write_all(&self.opcode.to_be_bytes())?;
+        out.write_all(&self.ir.to_be_bytes())?;
+        out.write_all(&self.changed.to_be_bytes())?;
+        return out.write_all(&self.v);
+    }
+}
+
+/// A line of the trace: an instruction or a `note`.
+enum Line {
+    Entry(TraceEntry),
+    Note(String),
+}
+
+/// Records every instruction `Chip8::do_cycle` executes.
+///
+/// By default entries are streamed to the output as they happen. In ring
+/// mode only the last N entries are kept in memory and written out by
+/// `dump` when the emulator hits an error.
+///
+/// Recording doesn't return errors, the first write that fails is kept and
+/// returned by the next `flush` or `dump`.
+pub struct Tracer {
+    out: Box<dyn Write>,
+    format: TraceFormat,
+    range: Option<RangeInclusive<u16>>,
+    ring: Option<VecDeque<Line>>,
+    ring_size: usize,
+    error: Option<std::io::Error>,
+}
+
+impl Tracer {
+    pub fn new<W: Write + 'static>(out: W, format: TraceFormat) -> Self {
+        return Tracer {
+            out: Box::new(out),
+            format,
+            range: None,
+            ring: None,
+            ring_size: 0,
+            error: None,
+        };
+    }
+
+    pub fn to_file(path: &str, format: TraceFormat) -> std::io::Result<Self> {
+        let file = File::create(path)?;
+        return Ok(Tracer::new(BufWriter::new(file), format));
+    }
+
+    /// Only record instructions whose address falls inside `range`.
+    pub fn with_range(mut self, range: RangeInclusive<u16>) -> Self {
+        self.range = Some(range);
+        return self;
+    }
+
+    /// Keep the last `size` entries in memory and only write them on `dump`.
+    pub fn with_ring(mut self, size: usize) -> Self {
+        self.ring = Some(VecDeque::with_capacity(size));
+        self.ring_size = size;
+        return self;
+    }
+
+    pub fn record(&mut self, entry: TraceEntry) -> () {
+        self.push(entry.pc, Line::Entry(entry));
+    }
+
+    /// Adds `# text` after the instruction at `pc`, for the text and common
+    /// formats; binary traces leave notes out.
+    pub fn note(&mut self, pc: u16, text: &str) -> () {
+        if self.format != TraceFormat::Binary {
+            self.push(pc, Line::Note(text.to_string()));
+        }
+    }
+
+    fn push(&mut self, pc: u16, line: Line) -> () {
+        if let Some(range) = &self.range {
+            if !range.contains(&pc) {
+                return;
+            }
+        }
+
+        match self.ring.as_mut() {
+            Some(ring) => {
+                if ring.len() == self.ring_size {
+                    ring.pop_front();
+                }
+                if self.ring_size > 0 {
+                    ring.push_back(line);
+                }
+            }
+            None => {
+                if let Err(err) = self.write(&line) {
+                    self.error.get_or_insert(err);
+                }
+            }
+        }
+    }
+
+    /// Writes out whatever the ring buffer holds and flushes the output.
+    pub fn dump(&mut self) -> std::io::Result<()> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        if let Some(mut ring) = self.ring.take() {
+            for line in ring.drain(..) {
+                self.write(&line)?;
+            }
+            self.ring = Some(ring);
+        }
+        return self.out.flush();
+    }
+
+    /// Flushes the output, or returns the first write that failed since the
+    /// last `flush`.
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        return self.out.flush();
+    }
+
+    fn write(&mut self, line: &Line) -> std::io::Result<()> {
+        let entry = match line {
+            Line::Entry(entry) => entry,
+            Line::Note(text) => return writeln!(self.out, "# {}", text),
+        };
+        return match self.format {
+            TraceFormat::Text => entry.write_text(&mut self.out),
+            TraceFormat::Binary => entry.write_binary(&mut self.out),
+            TraceFormat::Common => entry.write_common(&mut self.out),
+        };
+    }
+}
+
+/// First point where two traces in the common format disagree.
+#[derive(Debug, PartialEq)]
+pub struct Divergence {
+    /// 1 based number of the instruction, counting the lines of the
+    /// reference trace that aren't blank or comments.
+    pub instruction: usize,
+    /// Our line, `None` when our trace ended first.
+    pub ours: Option<String>,
+    pub reference: String,
+    /// Names of the fields that differ.
+    pub fields: Vec<String>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "traces diverge at instruction {}", self.instruction)?;
+        match &self.ours {
+            Some(ours) => {
+                if let Some(op) = parse_common(ours).iter().find(|(k, _)| k == "OP") {
+                    writeln!(f, "  instruction: {}", disassemble(op.1))?;
+                }
+                writeln!(f, "  differs in:  {}", self.fields.join(", "))?;
+                writeln!(f, "  ours:        {}", ours)?;
+            }
+            None => writeln!(f, "  ours:        <trace ended>")?,
+        }
+        return write!(f, "  reference:   {}", self.reference);
+    }
+}
+
+fn parse_common(line: &str) -> Vec<(String, u16)> {
+    return line
+        .split_whitespace()
+        .filter_map(|field| field.split_once('='))
+        .filter_map(|(key, value)| {
+            let value = value.trim_start_matches("0x").trim_start_matches("0X");
+            let value = u16::from_str_radix(value, 16).ok()?;
+            Some((key.to_ascii_uppercase(), value))
+        })
+        .collect();
+}
+
+/// The instruction lines of a trace, without comments and blank lines.
+fn trace_lines<R: BufRead>(trace: R) -> impl Iterator<Item = std::io::Result<String>> {
+    return trace
+        .lines()
+        .filter(|l| l.as_ref().map_or(true, |l| !l.starts_with('#') && !l.trim().is_empty()));
+}
+
+/// Compares our trace with a reference trace, both in the common format,
+/// line by line. Only the fields present in the reference are checked, so
+/// logs from emulators that don't track e.g. the timers still line up.
+pub fn diff_traces<A: BufRead, B: BufRead>(
+    ours: A,
+    reference: B,
+) -> std::io::Result<Option<Divergence>> {
+    let mut ours = trace_lines(ours);
+    let reference = trace_lines(reference);
+
+    for (i, reference) in reference.enumerate() {
+        let reference = reference?;
+        let Some(ours) = ours.next().transpose()? else {
+            return Ok(Some(Divergence {
+                instruction: i + 1,
+                ours: None,
+                reference,
+                fields: Vec::new(),
+            }));
+        };
+
+        let our_fields = parse_common(&ours);
+        let fields: Vec<String> = parse_common(&reference)
+            .into_iter()
+            .filter(|(key, value)| !our_fields.contains(&(key.clone(), *value)))
+            .map(|(key, _)| key)
+            .collect();
+
+        if !fields.is_empty() {
+            return Ok(Some(Divergence {
+                instruction: i + 1,
+                ours: Some(ours),
+                reference,
+                fields,
+            }));
+        }
+    }
+    return Ok(None);
+}
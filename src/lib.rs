@@ -1,7 +1,55 @@
+//! A CHIP-8 interpreter that can run without the terminal front end.
+//!
+//! [`Chip8::Chip8::new`] builds a headless machine from a
+//! [`Chip8::Config`]; load a ROM, then drive it with `run_frame` once per
+//! 60 Hz frame (or `step` per instruction), feed the keypad with `set_key`
+//! and read the screen from `framebuffer()`:
+//!
+//! ```
+//! use krhip8::Chip8::{Chip8, Config, Event};
+//!
+//! let mut chip8 = Chip8::new(Config { seed: Some(1), ..Default::default() });
+//! // LD V0, 5; LD F, V0; DRW V0, V0, 5; JP 0x206
+//! chip8.load_rom(&[0x60, 0x05, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x06]).unwrap();
+//! chip8.on_event(|event| {
+//!     if let Event::Draw { x, y, .. } = event {
+//!         println!("sprite at {}, {}", x, y);
+//!     }
+//! });
+//! chip8.set_key(0x5, true);
+//! chip8.run_frame().unwrap();
+//! assert!(chip8.framebuffer().pixel(5, 5));
+//! assert_eq!(chip8.v(0xF), 0);
+//! assert!(!chip8.sound_active());
+//! ```
+
+#![allow(non_snake_case, clippy::needless_return, clippy::unused_unit)]
+
+pub mod BlockCache;
+pub mod Breakpoints;
+pub mod Cfg;
 pub mod Chip8;
+pub mod Conformance;
+pub mod Coverage;
+pub mod Database;
+pub mod Debugger;
+pub mod Expr;
+pub mod Framebuffer;
+pub mod Gdb;
 pub mod Helpers;
+pub mod Movie;
 pub mod NonBlockingReader;
+pub mod Op;
+pub mod Profiler;
+pub mod Quirks;
 pub mod Ram;
+pub mod Recorder;
+pub mod Rom;
+pub mod Random;
+pub mod Screenshot;
+pub mod Script;
+pub mod Sprites;
+pub mod Tracer;
 
 pub trait Restart {
     fn restart(&mut self) -> ();
@@ -1,32 +1,820 @@
-use krhip8::Chip8::Chip8;
-use std::fs::read;
-use std::thread::sleep;
-use std::time::Duration;
+#![allow(clippy::needless_return, clippy::unused_unit)]
+
+use krhip8::Breakpoints::Breakpoints;
+use krhip8::Cfg::Cfg;
+use krhip8::Chip8::{Chip8, Chip8Errors, Event};
+use krhip8::Conformance::{check, Outcome, PROFILES, SUITE};
+use krhip8::Coverage::Coverage;
+use krhip8::Database::{Database, RomInfo};
+use krhip8::Debugger::Debugger;
+use krhip8::Gdb::GdbStub;
+use krhip8::Movie::Movie;
+use krhip8::Profiler::Profiler;
+use krhip8::Quirks::Quirks;
+use krhip8::Random::Random;
+use krhip8::Recorder::Recorder;
+use krhip8::Rom::{Platform, RomErrors};
+use krhip8::Screenshot::{Frame, Palette, Screenshot};
+use krhip8::Script::InputScript;
+use krhip8::Sprites::{font, frame_sheet, sprites_in, text_sheet, SpriteShape};
+use krhip8::Tracer::{diff_traces, TraceFormat, Tracer};
 use std::env::args;
+use std::fs::{read, File};
+use std::io::BufReader;
+use std::net::TcpListener;
+use std::path::Path;
+use std::time::Instant;
+
+/// Frames a headless run lasts when no script, movie or `--frames` ends it.
+const HEADLESS_FRAMES: usize = 600;
+
+/// The value of `result`, or prints the error for `path` and exits.
+fn or_exit<T>(result: std::io::Result<T>, path: &str) -> T {
+    return match result {
+        Ok(value) => value,
+        Err(err) => {
+            println!("{}: {}", path, err);
+            std::process::exit(1);
+        }
+    };
+}
+
+/// The page the VIP's `CXKK` routine reads, from a dump of the 512 byte
+/// COSMAC VIP CHIP-8 interpreter as it sits at 0x000.
+fn vip_page(path: &str) -> [u8; 256] {
+    let interpreter = or_exit(read(path), path);
+    if interpreter.len() != 0x200 {
+        println!("{}: expected the 512 byte VIP interpreter", path);
+        std::process::exit(1);
+    }
+    return interpreter[0x100..].try_into().unwrap();
+}
+
+fn parse_addr(s: &str) -> u16 {
+    let s = s.trim_start_matches("0x");
+    return u16::from_str_radix(s, 16).expect("addresses are given in hex, e.g. 0x200");
+}
+
+/// Loads the ROM for `platform`, or else for the platform the database
+/// lists or its contents suggest. A ROM that looks like it needs SCHIP or
+/// XO-CHIP instructions is loaded as CHIP-8 with a warning, unless only
+/// XO-CHIP memory fits it. Quirks and tick rate from the database are
+/// applied too. Exits with a message when the ROM can't be loaded.
+fn load(
+    chip8: &mut Chip8,
+    romname: &str,
+    platform: Option<Platform>,
+    database: Option<&Database>,
+) -> Option<RomInfo> {
+    let mut info = None;
+    let result = read(romname).map_err(RomErrors::Io).and_then(|rom| {
+        info = database.and_then(|database| database.lookup(&rom));
+        let known = info.as_ref().and_then(|info| info.platform);
+        chip8.set_platform(platform.or(known).unwrap_or_else(|| detect(romname, &rom)));
+        chip8.load_rom(&rom)
+    });
+
+    if let Err(err) = result {
+        println!("{}: {}", romname, err);
+        std::process::exit(1);
+    }
+
+    if let Some(info) = info.as_ref() {
+        if let Some(quirks) = info.quirks {
+            chip8.quirks = quirks;
+        }
+        if let Some(tickrate) = info.tickrate {
+            chip8.cycles_per_frame = tickrate;
+        }
+    }
+    return info;
+}
+
+/// The platform `rom` is loaded for when nothing names one: what
+/// `Platform::detect` guesses, but CHIP-8 with a warning instead of a
+/// platform whose instructions krhip8 doesn't run.
+fn detect(romname: &str, rom: &[u8]) -> Platform {
+    let detected = Platform::detect(rom);
+    if !detected.is_extended() {
+        return detected;
+    }
+    let platform = if rom.len() <= Platform::Chip8.max_rom_size() {
+        Platform::Chip8
+    } else {
+        detected
+    };
+    println!(
+        "{}: looks like a {:?} program but krhip8 only runs CHIP-8 instructions, loading it as {:?}; \
+         it stops at the first {:?} one",
+        romname, detected, platform, detected
+    );
+    return platform;
+}
+
+fn parse_platform(name: Option<String>) -> Option<Platform> {
+    let usage = "--platform takes chip8, schip, xochip or eti660";
+    return Some(Platform::parse(&name.expect(usage)).expect(usage));
+}
+
+/// `krhip8 trace-diff ROM REFERENCE [--out FILE] [--cycles N] [--seed N] [--vip-random INTERPRETER] [--keys SCRIPT] [--platform NAME]`
+///
+/// Runs the ROM headless, writes its trace in the common format and compares
+/// it with the reference trace, stopping at the first divergent instruction.
+/// The random seed defaults to 0 so runs are repeatable. The trace goes to
+/// ROM.trace in the current directory unless `--out` says otherwise.
+fn trace_diff(mut argv: impl Iterator<Item = String>) -> () {
+    let usage = "usage: krhip8 trace-diff ROM REFERENCE [--out FILE] [--cycles N] [--seed N] [--vip-random INTERPRETER] [--keys SCRIPT] [--platform NAME]";
+    let romname = argv.next().expect(usage);
+    let reference = argv.next().expect(usage);
+    let name = Path::new(&romname)
+        .file_name()
+        .map_or(romname.clone(), |name| name.to_string_lossy().into_owned());
+    let mut out = format!("{}.trace", name);
+    let mut cycles = None;
+    let mut seed = 0;
+    let mut vip = None;
+    let mut script = InputScript::default();
+    let mut platform = None;
+
+    while let Some(arg) = argv.next() {
+        match arg.as_str() {
+            "--out" => out = argv.next().expect(usage),
+            "--cycles" => cycles = Some(argv.next().expect(usage).parse::<usize>().unwrap()),
+            "--seed" => seed = argv.next().expect(usage).parse::<u64>().unwrap(),
+            "--vip-random" => vip = Some(vip_page(&argv.next().expect(usage))),
+            "--keys" => script = InputScript::load(&argv.next().expect(usage)).unwrap(),
+            "--platform" => platform = parse_platform(argv.next()),
+            _ => panic!("{}", usage),
+        }
+    }
+    let cycles = cycles.unwrap_or_else(|| {
+        let file = std::fs::read_to_string(&reference).unwrap();
+        file.lines()
+            .filter(|l| !l.starts_with('#') && !l.trim().is_empty())
+            .count()
+    });
+
+    let mut chip8 = Chip8::headless();
+    chip8.rng = match vip.as_ref() {
+        Some(page) => Random::vip(seed, page),
+        None => Random::new(seed),
+    };
+    load(&mut chip8, &romname, platform, None);
+    chip8.tracer = Some(Tracer::to_file(&out, TraceFormat::Common).unwrap());
+
+    let mut executed = 0;
+    let mut frame = 0;
+    'run: while executed < cycles {
+        if let Err(err) = script.apply(frame, &mut chip8, &Default::default()) {
+            println!("{}", err);
+            std::process::exit(1);
+        }
+        chip8.begin_frame();
+        for _ in 0..chip8.cycles_per_frame {
+            if executed == cycles {
+                break 'run;
+            }
+            if let Err(err) = chip8.step() {
+                println!("stopped after {}", err);
+                break 'run;
+            }
+            executed += 1;
+        }
+        frame += 1;
+    }
+    if let Err(err) = chip8.tracer.as_mut().unwrap().flush() {
+        println!("{}: {}", out, err);
+        std::process::exit(1);
+    }
+
+    let ours = BufReader::new(File::open(&out).unwrap());
+    let theirs = BufReader::new(File::open(&reference).unwrap());
+    match diff_traces(ours, theirs).unwrap() {
+        Some(divergence) => {
+            println!("{}", divergence);
+            std::process::exit(1);
+        }
+        None => println!("traces match ({} written to {})", cycles, out),
+    }
+}
+
+/// `krhip8 sprites ROM [--range START-END] [--height N] [--hires] [--font] [--frames N] [--columns N] [--out FILE] [--scale N] [--platform NAME]`
+///
+/// Shows memory as sprites, by default the whole ROM as 8x8 sprites. With
+/// `--frames` the ROM runs headless first and the sprite the last `DXYN`
+/// drew is highlighted. Prints text unless `--out` names an image.
+fn sprites(mut argv: impl Iterator<Item = String>) -> () {
+    let usage = "usage: krhip8 sprites ROM [--range START-END] [--height N] [--hires] [--font] [--frames N] [--columns N] [--out FILE] [--scale N] [--platform NAME]";
+    let romname = argv.next().expect(usage);
+    let mut range = None;
+    let mut height = None;
+    let mut hires = false;
+    let mut show_font = false;
+    let mut frames = 0;
+    let mut columns = 8;
+    let mut out = None;
+    let mut screenshot = Screenshot::default();
+    let mut platform = None;
+
+    while let Some(arg) = argv.next() {
+        match arg.as_str() {
+            "--range" => {
+                let text = argv.next().expect(usage);
+                let (start, end) = text.split_once('-').expect("--range takes START-END");
+                range = Some(parse_addr(start) as usize..parse_addr(end) as usize + 1);
+            }
+            "--height" => height = Some(argv.next().expect(usage).parse::<usize>().unwrap().clamp(1, 15)),
+            "--hires" => hires = true,
+            "--font" => show_font = true,
+            "--frames" => frames = argv.next().expect(usage).parse::<usize>().unwrap(),
+            "--columns" => columns = argv.next().expect(usage).parse::<usize>().unwrap().max(1),
+            "--out" => out = argv.next(),
+            "--scale" => screenshot.scale = argv.next().expect(usage).parse::<usize>().unwrap().max(1),
+            "--platform" => platform = parse_platform(argv.next()),
+            _ => panic!("{}", usage),
+        }
+    }
+
+    let mut chip8 = Chip8::headless();
+    chip8.rng = Random::new(0);
+    load(&mut chip8, &romname, platform, None);
+    let start = chip8.platform().load_address() as usize;
+    let rom_size = std::fs::metadata(&romname).map(|m| m.len() as usize).unwrap_or(0);
+
+    for _ in 0..frames {
+        if let Err(err) = chip8.run_frame() {
+            println!("stopped at {:#06x}: {:?}", chip8.pc(), err);
+            break;
+        }
+    }
+    let last = chip8.last_sprite();
+    if let Some((address, rows)) = last {
+        println!("last DXYN drew {} rows from {:#06x}", rows, address);
+        if out.is_none() && rows > 0 {
+            let shape = SpriteShape::Lores { height: rows };
+            let sprite = sprites_in(&chip8.ram, address as usize..address as usize + rows, shape);
+            for line in text_sheet(&sprite, 1, None) {
+                println!("{}", line);
+            }
+        }
+    }
+
+    let shape = if hires {
+        SpriteShape::Hires
+    } else {
+        SpriteShape::Lores {
+            height: height.unwrap_or(8),
+        }
+    };
+    let sprites = if show_font {
+        font(&chip8.ram)
+    } else {
+        sprites_in(&chip8.ram, range.unwrap_or(start..start + rom_size), shape)
+    };
+    let highlight = last.map(|(address, _)| address);
+
+    match out {
+        Some(path) => {
+            screenshot.save(&frame_sheet(&sprites, columns, highlight), &path).unwrap();
+            println!("{} sprites written to {}", sprites.len(), path);
+        }
+        None => {
+            for line in text_sheet(&sprites, columns, highlight) {
+                println!("{}", line);
+            }
+        }
+    }
+}
+
+/// `krhip8 cfg ROM [--dot] [--out FILE] [--platform NAME]`
+///
+/// Prints the control flow graph of the ROM as a listing of subroutines and
+/// basic blocks, or as Graphviz DOT with `--dot` or `--out`.
+fn cfg(mut argv: impl Iterator<Item = String>) -> () {
+    let usage = "usage: krhip8 cfg ROM [--dot] [--out FILE] [--platform NAME]";
+    let romname = argv.next().expect(usage);
+    let mut dot = false;
+    let mut out = None;
+    let mut platform = None;
+
+    while let Some(arg) = argv.next() {
+        match arg.as_str() {
+            "--dot" => dot = true,
+            "--out" => out = argv.next(),
+            "--platform" => platform = parse_platform(argv.next()),
+            _ => panic!("{}", usage),
+        }
+    }
+
+    let rom = match read(&romname) {
+        Ok(rom) => rom,
+        Err(err) => {
+            println!("{}: {}", romname, err);
+            std::process::exit(1);
+        }
+    };
+    let platform = platform.unwrap_or_else(|| Platform::detect(&rom));
+    let cfg = Cfg::build(&rom, platform.load_address());
+
+    match out {
+        Some(path) => {
+            std::fs::write(&path, cfg.to_dot()).unwrap();
+            println!(
+                "{} blocks in {} subroutines written to {}",
+                cfg.blocks.len(),
+                cfg.subroutines.len(),
+                path
+            );
+        }
+        None if dot => print!("{}", cfg.to_dot()),
+        None => print!("{}", cfg.listing()),
+    }
+}
+
+/// `krhip8 test [DIR] [--goldens DIR] [--update] [--only NAME] [--profile ID]`
+///
+/// Runs Timendus' test suite from DIR (the current directory by default) on
+/// every platform profile and compares the screens with the golden ones in
+/// `conformance`. Cases whose rom isn't there are skipped. `--update`
+/// records the goldens instead.
+fn test(mut argv: impl Iterator<Item = String>) -> () {
+    let usage = "usage: krhip8 test [DIR] [--goldens DIR] [--update] [--only NAME] [--profile ID]";
+    let mut roms = ".".to_string();
+    let mut goldens = "conformance".to_string();
+    let mut update = false;
+    let mut only = None;
+    let mut profile = None;
+
+    while let Some(arg) = argv.next() {
+        match arg.as_str() {
+            "--goldens" => goldens = argv.next().expect(usage),
+            "--update" => update = true,
+            "--only" => only = Some(argv.next().expect(usage)),
+            "--profile" => profile = Some(argv.next().expect(usage)),
+            _ if !arg.starts_with("--") => roms = arg,
+            _ => panic!("{}", usage),
+        }
+    }
+
+    let (mut passed, mut failed, mut skipped) = (0, 0, 0);
+    for case in SUITE.iter().filter(|case| only.as_ref().is_none_or(|name| name == case.name)) {
+        for profile in PROFILES.iter().filter(|p| profile.as_ref().is_none_or(|id| id == p.id)) {
+            if (case.keys)(profile).is_none() {
+                continue;
+            }
+            let outcome = check(case, profile, Path::new(&roms), Path::new(&goldens), update);
+            println!("{:<18} {:<14} {}", case.name, profile.id, outcome);
+            if let Outcome::Differs { expected, actual } = &outcome {
+                for (row, (expected, actual)) in expected.lines().zip(actual.lines()).enumerate() {
+                    if expected != actual {
+                        println!("    {:>2} expected {}", row, expected);
+                        println!("       got      {}", actual);
+                    }
+                }
+            }
+            match outcome {
+                Outcome::NoRom => skipped += 1,
+                outcome if outcome.failed() => failed += 1,
+                _ => passed += 1,
+            }
+        }
+    }
+    println!("{} passed, {} failed, {} skipped", passed, failed, skipped);
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// What an error the rom runs into does: dumps the trace, or memory without
+/// a tracer, prints the error and exits.
+fn crash(chip8: &mut Chip8, err: Chip8Errors) -> ! {
+    if let Some(outbuff) = chip8.outbuff.as_ref() {
+        let _ = outbuff.suspend_raw_mode();
+    }
+    match chip8.tracer.as_mut() {
+        Some(tracer) => {
+            if let Err(err) = tracer.dump() {
+                println!("trace: {}", err);
+            }
+        }
+        None => chip8.ram.debug(),
+    }
+    let opcode = chip8.ram.opcode(chip8.pc().wrapping_sub(2) as usize).unwrap_or(0);
+    println!("{}, pc {:#06x}, opcode {:04X}", err, chip8.pc(), opcode);
+    std::process::exit(1);
+}
+
+/// Runs `cycles` instructions as fast as possible, without a screen or
+/// frame pacing, and prints the instructions per second.
+fn benchmark(chip8: &mut Chip8, cycles: usize) -> () {
+    let start = Instant::now();
+    let mut executed = 0;
+    while executed < cycles {
+        let frame = chip8.cycles_per_frame.min(cycles - executed);
+        chip8.begin_frame();
+        if let Err(err) = chip8.run_cycles(frame) {
+            println!("stopped at {:#06x}: {:?}", chip8.pc(), err);
+            break;
+        }
+        executed += frame;
+    }
+    let seconds = start.elapsed().as_secs_f64();
+    let ips = executed as f64 / seconds;
+    println!(
+        "{} instructions in {:.3}s: {:.0} instructions per second, {:.0}x a 700 Hz CHIP-8",
+        executed,
+        seconds,
+        ips,
+        ips / 700.0
+    );
+}
+
+/// Waits for GDB on localhost and lets it control the machine until it
+/// detaches, after which the rom keeps running normally.
+fn gdb(chip8: &mut Chip8, port: u16) -> () {
+    let listener = match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => listener,
+        Err(err) => {
+            println!("127.0.0.1:{}: {}", port, err);
+            std::process::exit(1);
+        }
+    };
+    println!("waiting for gdb on 127.0.0.1:{}", port);
+    let (stream, _) = listener.accept().unwrap();
+    if let Err(err) = GdbStub::new(stream).serve(chip8) {
+        println!("gdb: {}", err);
+    }
+}
 
 fn main() {
+    if args().nth(1).as_deref() == Some("cfg") {
+        return cfg(args().skip(2));
+    }
+    if args().nth(1).as_deref() == Some("trace-diff") {
+        return trace_diff(args().skip(2));
+    }
+    if args().nth(1).as_deref() == Some("sprites") {
+        return sprites(args().skip(2));
+    }
+    if args().nth(1).as_deref() == Some("test") {
+        return test(args().skip(2));
+    }
+
     //print!("\x1B[2J\x1B[H");
     let mut ibmromname = "/home/edwjuaard/Downloads/test_opcode.ch8".to_string();
+    let mut trace_file: Option<String> = None;
+    let mut trace_format = TraceFormat::Text;
+    let mut trace_range = None;
+    let mut trace_ring = None;
+    let mut seed = None;
+    let mut vip = None;
+    let mut record_movie: Option<String> = None;
+    let mut play_movie: Option<Movie> = None;
+    let mut headless = false;
+    let mut script: Option<InputScript> = None;
+    let mut screenshot = Screenshot::default();
+    let mut record: Option<String> = None;
+    let mut frames: Option<usize> = None;
+    let mut platform = None;
+    let mut database = Some(Database::bundled());
+    let mut quirk_overrides: Vec<(String, bool)> = Vec::new();
+    let mut tickrate = None;
+    let mut stack_depth = None;
+    let mut profile: Option<String> = None;
+    let mut profile_folded: Option<String> = None;
+    let mut coverage: Option<String> = None;
+    let mut coverage_lcov: Option<String> = None;
+    let mut memory_warnings = false;
+    let mut palette_set = false;
+    let mut debug = false;
+    let mut breakpoints = Breakpoints::new();
+    let mut gdb_port: Option<u16> = None;
+    let mut fast = false;
+    let mut bench_cycles = None;
 
-    if let Some(f) = args().nth(1) {
-        ibmromname = f; 
+    let mut argv = args().skip(1);
+    while let Some(arg) = argv.next() {
+        match arg.as_str() {
+            "--trace" => trace_file = argv.next(),
+            "--trace-binary" => trace_format = TraceFormat::Binary,
+            "--trace-range" => {
+                let range = argv.next().expect("--trace-range takes START-END");
+                let (start, end) = range.split_once('-').expect("--trace-range takes START-END");
+                trace_range = Some(parse_addr(start)..=parse_addr(end));
+            }
+            "--trace-ring" => {
+                let size = argv.next().expect("--trace-ring takes a number of instructions");
+                trace_ring = Some(size.parse::<usize>().unwrap());
+            }
+            "--seed" => seed = Some(argv.next().expect("--seed takes a number").parse::<u64>().unwrap()),
+            "--vip-random" => {
+                let path = argv.next().expect("--vip-random takes a dump of the VIP interpreter");
+                vip = Some(vip_page(&path));
+            }
+            "--record-movie" => record_movie = argv.next(),
+            "--play-movie" => {
+                let path = argv.next().expect("--play-movie takes a movie file");
+                play_movie = Some(Movie::load(&path).unwrap());
+            }
+            "--headless" => headless = true,
+            "--record" => record = argv.next(),
+            "--frames" => {
+                let count = argv.next().expect("--frames takes a number of frames");
+                frames = Some(count.parse::<usize>().expect("--frames takes a number of frames"));
+            }
+            "--platform" => platform = parse_platform(argv.next()),
+            "--scale" => {
+                let scale = argv.next().expect("--scale takes a number");
+                screenshot.scale = scale.parse::<usize>().unwrap().max(1);
+            }
+            "--palette" => {
+                let palette = argv.next().expect("--palette takes RRGGBB,RRGGBB,...");
+                screenshot.palette = Palette::parse(&palette).expect("--palette takes RRGGBB,RRGGBB,...");
+                palette_set = true;
+            }
+            "--keys" => {
+                let path = argv.next().expect("--keys takes an input script");
+                match InputScript::load(&path) {
+                    Ok(keys) => script = Some(keys),
+                    Err(err) => {
+                        println!("{}: {}", path, err);
+                        std::process::exit(1);
+                    }
+                }
+                // Scripts stand in for the keyboard, they only make sense headless.
+                headless = true;
+            }
+            "--database" => {
+                let dir = argv.next().expect("--database takes a chip-8-database directory");
+                database = Some(Database::load(&dir).unwrap());
+            }
+            "--no-database" => database = None,
+            "--debug" => debug = true,
+            "--gdb" => {
+                let port = argv.next().expect("--gdb takes a port");
+                gdb_port = Some(port.parse::<u16>().expect("--gdb takes a port"));
+            }
+            "--break" => {
+                let spec = argv.next().expect("--break takes a breakpoint");
+                if let Err(err) = breakpoints.add(&spec) {
+                    println!("--break {}", err);
+                    std::process::exit(1);
+                }
+            }
+            "--fast" => fast = true,
+            "--benchmark" => {
+                bench_cycles = Some(10_000_000);
+                headless = true;
+            }
+            "--cycles" => {
+                let cycles = argv.next().expect("--cycles takes a number of instructions");
+                bench_cycles = Some(cycles.parse::<usize>().unwrap());
+                headless = true;
+            }
+            "--quirk" => {
+                let usage = "--quirk takes NAME=on or NAME=off";
+                let quirk = argv.next().expect(usage);
+                match quirk.split_once('=') {
+                    Some((name, "on")) => quirk_overrides.push((name.to_string(), true)),
+                    Some((name, "off")) => quirk_overrides.push((name.to_string(), false)),
+                    _ => panic!("{}", usage),
+                }
+            }
+            "--profile" => profile = argv.next(),
+            "--profile-folded" => profile_folded = argv.next(),
+            "--coverage" => coverage = argv.next(),
+            "--coverage-lcov" => coverage_lcov = argv.next(),
+            "--memory-warnings" => memory_warnings = true,
+            "--stack-depth" => {
+                let usage = "--stack-depth takes a number of return addresses or unlimited";
+                stack_depth = match argv.next().expect(usage).as_str() {
+                    "unlimited" => Some(usize::MAX),
+                    depth => Some(depth.parse::<usize>().expect(usage)),
+                };
+            }
+            "--tickrate" => {
+                let rate = argv.next().expect("--tickrate takes instructions per frame");
+                tickrate = Some(rate.parse::<usize>().unwrap());
+            }
+            _ => ibmromname = arg,
+        }
+    }
+    let mut chip8 = if headless {
+        Chip8::headless()
     } else {
+        Default::default()
+    };
+    let seed = seed.unwrap_or(chip8.rng.seed());
+    chip8.rng = match vip.as_ref() {
+        Some(page) => Random::vip(seed, page),
+        None => Random::new(seed),
+    };
+    if let Some(movie) = play_movie.as_ref() {
+        // The movie decides the input, the terminal only shows the screen.
+        let Some(rng) = movie.rng(vip.as_ref()) else {
+            println!("the movie was recorded with --vip-random, pass the interpreter again");
+            std::process::exit(1);
+        };
+        chip8.rng = rng;
+        chip8.detach_input();
+        platform = Some(movie.platform);
     }
-        println!("{}",ibmromname);
-    let ibmrom = read(ibmromname).unwrap();
+    chip8.set_block_cache(fast);
+    let info = load(&mut chip8, &ibmromname, platform, database.as_ref());
 
-    let mut chip8: Chip8 = Default::default();
+    if let Some(info) = info.as_ref() {
+        println!("{} by {}", info.title, info.authors.join(", "));
+        if !info.keys.is_empty() {
+            let keys: Vec<String> = info.keys.iter().map(|(name, key)| format!("{}={:X}", name, key)).collect();
+            println!("controls: {}", keys.join(" "));
+        }
+        if let (Some(colours), false) = (info.colours.as_ref(), palette_set) {
+            screenshot.palette = colours.clone();
+        }
+    }
+    for (name, value) in quirk_overrides {
+        if !chip8.quirks.set(&name, value) {
+            println!("unknown quirk {}, the quirks are {}", name, Quirks::NAMES.join(", "));
+            std::process::exit(1);
+        }
+    }
+    if let Some(rate) = tickrate {
+        chip8.cycles_per_frame = rate;
+    }
+    if let Some(depth) = stack_depth {
+        chip8.set_stack_depth(depth);
+    }
+    if let Some(movie) = play_movie.as_ref() {
+        // A movie only replays right with the quirks and speed it was recorded with.
+        chip8.quirks = movie.quirks;
+        chip8.cycles_per_frame = movie.cycles_per_frame;
+    }
+    let quirks = chip8.quirks.to_list();
+    println!(
+        "{} ({:?}, seed {}, quirks {})",
+        ibmromname,
+        chip8.platform(),
+        chip8.rng.seed(),
+        if quirks.is_empty() { "none" } else { &quirks }
+    );
+
+    if let Some(cycles) = bench_cycles {
+        return benchmark(&mut chip8, cycles);
+    }
+
+    if let Some(path) = trace_file.as_ref() {
+        let mut tracer = match Tracer::to_file(path, trace_format) {
+            Ok(tracer) => tracer,
+            Err(err) => {
+                println!("{}: {}", path, err);
+                std::process::exit(1);
+            }
+        };
+        if let Some(range) = trace_range {
+            tracer = tracer.with_range(range);
+        }
+        if let Some(size) = trace_ring {
+            tracer = tracer.with_ring(size);
+        }
+        chip8.tracer = Some(tracer);
+    }
+
+    if profile.is_some() || profile_folded.is_some() {
+        chip8.profiler = Some(Profiler::new(chip8.ram.size(), chip8.platform().load_address()));
+    }
+    if coverage.is_some() || coverage_lcov.is_some() {
+        chip8.coverage = Some(Coverage::new(chip8.ram.as_slice(), chip8.platform().load_address()));
+    }
+    if memory_warnings {
+        chip8.set_memory_tracking(true);
+        // on the terminal they'd scribble over the screen, the debugger
+        // shows them there
+        if headless {
+            chip8.on_event(|event| {
+                if let Event::Warning(warning) = event {
+                    println!("warning: {}", warning);
+                }
+            });
+        }
+    }
 
-    for i in 0..ibmrom.len() {
-        chip8.ram[i + 0x200] = ibmrom[i];
+    let mut recording = record_movie
+        .as_ref()
+        .map(|_| Movie::new(&chip8.rng, chip8.platform(), chip8.quirks, chip8.cycles_per_frame));
+    let mut recorder = record.map(|path| (or_exit(Recorder::create(&path, screenshot.clone()), &path), path));
+    if headless && frames.is_none() && script.is_none() && play_movie.is_none() {
+        // nothing would ever stop it otherwise
+        frames = Some(HEADLESS_FRAMES);
+    }
+    let mut frame = 0;
+    if let Some(port) = gdb_port {
+        gdb(&mut chip8, port);
+    }
+    let mut debugger = Debugger::new();
+    debugger.breakpoints = breakpoints;
+    if debug {
+        debugger.pause(&chip8);
     }
 
-    loop {
-        let _ = chip8.handle_input(false);
+    while !chip8.quit {
+        if frames.is_some_and(|frames| frame >= frames) {
+            break;
+        }
+        match chip8.handle_input() {
+            Some(b'p') => {
+                let path = format!("krhip8-{}.png", frame);
+                or_exit(screenshot.save(&Frame::from_chip8(&chip8), &path), &path);
+            }
+            Some(b'g') => match recorder.take() {
+                Some((recorder, path)) => or_exit(recorder.finish(), &path),
+                None => {
+                    let path = format!("krhip8-{}.gif", frame);
+                    recorder = Some((or_exit(Recorder::create(&path, screenshot.clone()), &path), path));
+                }
+            },
+            Some(b' ') => debugger.pause(&chip8),
+            _ => {}
+        }
+        if debugger.paused {
+            debugger.run(&mut chip8);
+            if chip8.quit {
+                break;
+            }
+        }
+        if let Some(movie) = play_movie.as_ref() {
+            match movie.keys(frame) {
+                Some(keys) => chip8.keys = keys,
+                None => break,
+            }
+        }
+        if let Some(script) = script.as_ref() {
+            if play_movie.is_none() && frame > script.last_frame() {
+                break;
+            }
+            if let Err(err) = script.apply(frame, &mut chip8, &screenshot) {
+                println!("{}", err);
+                std::process::exit(1);
+            }
+        }
+        if let Some(movie) = recording.as_mut() {
+            movie.record(&chip8.keys);
+        }
 //        sleep(Duration::from_millis(1));
-        chip8.do_cycle();
+        chip8.begin_frame();
+        let cycles = chip8.cycles_per_frame;
+        if let Err(err) = debugger.run_cycles(&mut chip8, cycles) {
+            crash(&mut chip8, err);
+        }
         chip8.render();
+        if let Some((recorder, path)) = recorder.as_mut() {
+            or_exit(recorder.capture(&Frame::from_chip8(&chip8)), path);
+        }
         //chip8.clear_keys();
-    } 
-    
+        frame += 1;
+    }
+
+    if let Some((recorder, path)) = recorder {
+        or_exit(recorder.finish(), &path);
+    }
+
+    if let (Some(tracer), Some(path)) = (chip8.tracer.as_mut(), trace_file.as_ref()) {
+        if let Err(err) = tracer.flush() {
+            println!("{}: {}", path, err);
+            std::process::exit(1);
+        }
+    }
+    if let Some(profiler) = chip8.profiler.as_ref() {
+        let outputs = [(profile, profiler.report(&chip8.ram)), (profile_folded, profiler.folded())];
+        for (path, text) in outputs {
+            let Some(path) = path else {
+                continue;
+            };
+            if let Err(err) = std::fs::write(&path, text) {
+                println!("{}: {}", path, err);
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Some(report) = chip8.coverage.as_ref() {
+        let outputs = [
+            (coverage, report.annotated(&chip8.ram)),
+            (coverage_lcov, report.lcov(&chip8.ram, &ibmromname)),
+        ];
+        for (path, text) in outputs {
+            let Some(path) = path else {
+                continue;
+            };
+            if let Err(err) = std::fs::write(&path, text) {
+                println!("{}: {}", path, err);
+                std::process::exit(1);
+            }
+        }
+    }
+    if let (Some(path), Some(movie)) = (record_movie, recording) {
+        movie.save(&path).unwrap();
+        println!("recorded {} frames to {}", movie.frames.len(), path);
+    }
+    if play_movie.is_some() {
+        println!("replayed {} frames", frame);
+    }
 }
@@ -0,0 +1,194 @@
+#![allow(clippy::needless_return, clippy::unused_unit)]
+
+use krhip8::Breakpoints::{BreakpointErrors, Breakpoints};
+use krhip8::Chip8::{Chip8, Chip8Errors, Config, MemoryWarning};
+use krhip8::Debugger::Debugger;
+use krhip8::Expr::{Expr, ExprErrors};
+
+/// A machine with V3 = 0x10, VF = 1, I = 0x300 and 0xAB at 0x302.
+fn machine() -> Chip8 {
+    let mut chip8 = Chip8::new(Config {
+        seed: Some(0),
+        ..Default::default()
+    });
+    chip8.load_rom(&[0x00, 0xE0]).unwrap();
+    chip8.set_v(3, 0x10);
+    chip8.set_v(0xF, 1);
+    chip8.set_ir(0x300);
+    chip8.write_ram(0x302, 0xAB).unwrap();
+    return chip8;
+}
+
+fn eval(text: &str) -> i64 {
+    return Expr::parse(text).unwrap().eval(&machine());
+}
+
+fn parse_error(text: &str) -> String {
+    let Err(ExprErrors::Parse { reason, .. }) = Expr::parse(text) else {
+        panic!("`{}` parsed", text);
+    };
+    return reason;
+}
+
+#[test]
+fn precedence() {
+    assert_eq!(eval("1 + 2 * 3"), 7);
+    assert_eq!(eval("(1 + 2) * 3"), 9);
+    assert_eq!(eval("1 << 2 + 1"), 8);
+    assert_eq!(eval("6 & 3 | 8"), 10);
+    assert_eq!(eval("1 | 2 ^ 3"), 1);
+    assert_eq!(eval("-2 * -3"), 6);
+    assert_eq!(eval("!0 + ~0"), 0);
+    assert_eq!(eval("1 || 0 && 0"), 1);
+    assert_eq!(eval("10 - 4 - 3"), 3);
+    assert_eq!(eval("7 / 0 + 7 % 0"), 0);
+}
+
+#[test]
+fn comparison_chains() {
+    // like C, a chain compares the 0 or 1 of the first comparison
+    assert_eq!(eval("1 < 2 < 3"), 1);
+    assert_eq!(eval("3 > 2 > 1"), 0);
+    assert_eq!(eval("2 == 2 == 1"), 1);
+    assert_eq!(eval("v3 == 0x10 && i > 0x2FF"), 1);
+    assert_eq!(eval("v3 != 0x10 || vf >= 2"), 0);
+}
+
+#[test]
+fn machine_state() {
+    assert_eq!(eval("v3 + vF"), 0x11);
+    assert_eq!(eval("pc"), 0x200);
+    assert_eq!(eval("opcode"), 0x00E0);
+    assert_eq!(eval("sp + dt + st"), 0);
+    assert_eq!(eval("mem[i + 2]"), 0xAB);
+    assert_eq!(eval("mem[mem[0x302] - 0xAB + 0x302]"), 0xAB);
+    // outside memory reads 0
+    assert_eq!(eval("mem[0x10000] + mem[-1]"), 0);
+}
+
+#[test]
+fn expression_errors() {
+    assert_eq!(parse_error("v3 =="), "unexpected end");
+    assert_eq!(parse_error("mem[i"), "expected ]");
+    assert_eq!(parse_error("(1 + 2"), "expected )");
+    assert_eq!(parse_error("vg"), "unknown name vg");
+    assert_eq!(parse_error("1 2"), "trailing input");
+    assert_eq!(parse_error("0xZZ"), "bad number 0xzz");
+    assert_eq!(parse_error("v1 $ 2"), "unexpected $");
+    assert_eq!(Expr::parse("v1 +").unwrap_err().to_string(), "`v1 +`: unexpected end");
+}
+
+#[test]
+fn address_and_condition() {
+    let chip8 = machine();
+    let mut breakpoints = Breakpoints::new();
+    breakpoints.add("0x300").unwrap();
+    assert!(breakpoints.before(&chip8).is_none());
+    breakpoints.add("v3 == 0x10").unwrap();
+    assert_eq!(breakpoints.before(&chip8).unwrap().text, "v3 == 0x10");
+    breakpoints.add("0x200").unwrap();
+    // both match, the first one added is the one that stops
+    assert_eq!(breakpoints.before(&chip8).unwrap().text, "v3 == 0x10");
+    let hits: Vec<u64> = breakpoints.iter().map(|bp| bp.hits).collect();
+    assert_eq!(hits, [0, 2, 1]);
+}
+
+#[test]
+fn opcode_with_condition() {
+    let mut chip8 = machine();
+    let mut breakpoints = Breakpoints::new();
+    breakpoints.add("op DXYN if vf == 1").unwrap();
+    assert_eq!(breakpoints.after(&chip8, 0xD125).unwrap().text, "op DXYN if vf == 1");
+    assert!(breakpoints.after(&chip8, 0x6125).is_none());
+    chip8.set_v(0xF, 0);
+    assert!(breakpoints.after(&chip8, 0xD125).is_none());
+
+    breakpoints.clear();
+    breakpoints.add("op 00EE").unwrap();
+    assert!(breakpoints.after(&chip8, 0x00EE).is_some());
+    assert!(breakpoints.after(&chip8, 0x00E0).is_none());
+}
+
+#[test]
+fn hit_counts() {
+    let chip8 = machine();
+    let mut breakpoints = Breakpoints::new();
+    breakpoints.add("op 7XNN hit 3").unwrap();
+    assert!(breakpoints.after(&chip8, 0x7101).is_none());
+    assert!(breakpoints.after(&chip8, 0x7201).is_none());
+    let bp = breakpoints.after(&chip8, 0x7301).unwrap();
+    assert_eq!(bp.to_string(), "op 7XNN hit 3 (hit 3 times)");
+    // and every time after that
+    assert!(breakpoints.after(&chip8, 0x7401).is_some());
+}
+
+#[test]
+fn errors_and_warnings() {
+    let mut breakpoints = Breakpoints::new();
+    breakpoints.add("error stack-overflow").unwrap();
+    breakpoints.add("warning font-write").unwrap();
+    assert!(breakpoints.error(&Chip8Errors::UndefinedInstruction).is_none());
+    let overflow = Chip8Errors::StackOverflow { at: 0x200, depth: 12 };
+    assert_eq!(breakpoints.error(&overflow).unwrap().text, "error stack-overflow");
+    let code = MemoryWarning::CodeWrite {
+        at: 0x200,
+        address: 0x202,
+    };
+    assert!(breakpoints.warning(&code).is_none());
+    let font = MemoryWarning::FontWrite {
+        at: 0x200,
+        address: 0x50,
+    };
+    assert_eq!(breakpoints.warning(&font).unwrap().text, "warning font-write");
+
+    breakpoints.add("error").unwrap();
+    breakpoints.add("warning").unwrap();
+    assert_eq!(
+        breakpoints.error(&Chip8Errors::UndefinedInstruction).unwrap().text,
+        "error"
+    );
+    assert_eq!(breakpoints.warning(&code).unwrap().text, "warning");
+}
+
+#[test]
+fn error_stops_at_the_instruction() {
+    // LD V0, 1; CALL 0x202 until the stack is full
+    let mut chip8 = Chip8::new(Config {
+        seed: Some(0),
+        stack_depth: Some(2),
+        ..Default::default()
+    });
+    chip8.load_rom(&[0x60, 0x01, 0x22, 0x02]).unwrap();
+    let mut debugger = Debugger::new();
+    debugger.breakpoints.add("error stack-overflow").unwrap();
+    debugger.run_cycles(&mut chip8, 10).unwrap();
+    assert!(debugger.paused);
+    assert_eq!(chip8.pc(), 0x202);
+    assert_eq!(chip8.stack().len(), 2);
+
+    // resuming runs the CALL again instead of going past it
+    debugger.paused = false;
+    debugger.run_cycles(&mut chip8, 10).unwrap();
+    assert!(debugger.paused);
+    assert_eq!(chip8.pc(), 0x202);
+}
+
+#[test]
+fn breakpoint_errors() {
+    let reason = |text: &str| match Breakpoints::new().add(text) {
+        Err(BreakpointErrors::Parse { reason, .. }) => reason,
+        other => panic!("`{}` gave {:?}", text, other),
+    };
+    assert_eq!(reason(""), "empty breakpoint");
+    assert_eq!(reason("error fire"), "unknown error");
+    assert_eq!(reason("warning smoke"), "unknown warning");
+    assert_eq!(reason("op DXY"), "opcode patterns have four characters");
+    assert_eq!(reason("op DXYN vf == 1"), "expected `if CONDITION` after the pattern");
+    assert_eq!(reason("op DXYN if"), "expected `if CONDITION` after the pattern");
+    assert_eq!(reason("0x200 hit 0"), "hit counts start at 1");
+    assert_eq!(reason("0x200 hit many"), "hit takes a count");
+    assert!(matches!(
+        Breakpoints::new().add("op DXYN if vf =="),
+        Err(BreakpointErrors::Expr(_))
+    ));
+}
@@ -0,0 +1,65 @@
+#![allow(clippy::needless_return, clippy::unused_unit)]
+
+use krhip8::Cfg::{Cfg, Flow};
+
+#[test]
+fn stops_at_instructions_krhip8_cant_run() {
+    // CLS, LOW (SCHIP), JP 0x200
+    let rom = [0x00, 0xE0, 0x00, 0xFE, 0x12, 0x00];
+    assert_eq!(Flow::of(0x00FE), Flow::Stop);
+    assert_eq!(Flow::of_extended(0x00FE), Flow::Next);
+    assert_eq!(Flow::of(0xF030), Flow::Stop);
+    assert_eq!(Flow::of(0x00E0), Flow::Next);
+
+    let cfg = Cfg::build(&rom, 0x200);
+    assert_eq!(cfg.instructions.keys().copied().collect::<Vec<_>>(), [0x200, 0x202]);
+    assert_eq!(cfg.blocks[&0x200].flow, Flow::Stop);
+
+    let cfg = Cfg::build_with(&rom, 0x200, Flow::of_extended);
+    assert_eq!(
+        cfg.instructions.keys().copied().collect::<Vec<_>>(),
+        [0x200, 0x202, 0x204]
+    );
+}
+
+#[test]
+fn stores_into_code() {
+    let rom = [
+        0xA2, 0x0A, // 0x200: LD I, 0x20A
+        0xF1, 0x55, // 0x202: LD [I], V1 writes 0x20A-0x20B
+        0xA2, 0x09, // 0x204: LD I, 0x209
+        0xF0, 0x33, // 0x206: LD B, V0 writes 0x209-0x20B
+        0xF0, 0x1E, // 0x208: ADD I, V0, I unknown from here
+        0x12, 0x0A, // 0x20A: JP 0x20A
+    ];
+    let cfg = Cfg::build(&rom, 0x200);
+    assert_eq!(cfg.code_writes, [(0x202, 0x20A), (0x206, 0x208), (0x206, 0x20A)]);
+    assert!(cfg.listing().contains("0x0202 writes over the instruction at 0x020a"));
+    let dot = cfg.to_dot();
+    assert!(
+        dot.contains("b0200 -> b020a [color=red style=dashed label=\"writes\"]"),
+        "{}",
+        dot
+    );
+
+    // stores into data are fine
+    let cfg = Cfg::build(&[0xA3, 0x00, 0xF2, 0x55, 0x12, 0x04], 0x200);
+    assert!(cfg.code_writes.is_empty());
+    assert!(!cfg.to_dot().contains("red"));
+}
+
+#[test]
+fn register_skips_ignore_n() {
+    // krhip8 runs 5XY1 and 9XY3 as 5XY0 and 9XY0
+    assert_eq!(Flow::of(0x5121), Flow::Skip);
+    assert_eq!(Flow::of(0x9123), Flow::Skip);
+    let rom = [
+        0x51, 0x21, // 0x200: SE V1, V2
+        0x12, 0x02, // 0x202: JP 0x202
+        0x12, 0x04, // 0x204: JP 0x204
+    ];
+    let cfg = Cfg::build(&rom, 0x200);
+    assert_eq!(cfg.blocks[&0x200].flow, Flow::Skip);
+    assert_eq!(cfg.blocks[&0x200].successors, [0x202, 0x204]);
+    assert!(cfg.is_code(0x204));
+}
@@ -0,0 +1,89 @@
+#![allow(clippy::needless_return, clippy::unused_unit)]
+
+use krhip8::Conformance::{check, Outcome, PROFILES, SUITE};
+use std::path::Path;
+
+/// Runs every case of `rom` on every profile against the goldens in
+/// `conformance`. The ROM has to be in the tree.
+fn suite(rom: &str) -> () {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut failures = Vec::new();
+    let mut ran = 0;
+    for case in SUITE.iter().filter(|case| case.rom == rom) {
+        for profile in PROFILES.iter().filter(|profile| (case.keys)(profile).is_some()) {
+            match check(case, profile, root, &root.join("conformance"), false) {
+                Outcome::NoRom => panic!("{} isn't in the tree", rom),
+                Outcome::Differs { expected, actual } => {
+                    failures.push(format!(
+                        "{} on {}:\n{}\ngot\n{}",
+                        case.name, profile.id, expected, actual
+                    ));
+                }
+                outcome if outcome.failed() => failures.push(format!("{} on {}: {}", case.name, profile.id, outcome)),
+                _ => ran += 1,
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n\n"));
+    assert!(ran > 0, "no cases for {}", rom);
+}
+
+#[test]
+fn corax_plus() {
+    suite("3-corax+.ch8");
+}
+
+#[test]
+fn flags() {
+    suite("4-flags.ch8");
+}
+
+#[test]
+fn quirks() {
+    suite("5-quirks.ch8");
+}
+
+#[test]
+fn keypad() {
+    suite("6-keypad.ch8");
+}
+
+#[test]
+fn goldens_are_screens() {
+    // a golden that lost a row or got mangled would never match
+    for entry in std::fs::read_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("conformance")).unwrap() {
+        let text = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+        let rows: Vec<&str> = text.lines().collect();
+        assert!(rows.len() == 32 || rows.len() == 64);
+        assert!(rows.iter().all(|row| row.len() == rows.len() * 2));
+        assert!(rows.iter().all(|row| row.chars().all(|c| ".#o@".contains(c))));
+    }
+}
+
+#[test]
+fn goldens_have_no_unexpected_crosses() {
+    // the suite marks a failed check with a cross in the bottom three rows
+    // of a line of text, a golden with one would keep a failure passing;
+    // the letter x is four rows high. modernChip8 is meant to fail the
+    // quirks test's vF reset and memory checks against the original
+    // interpreter
+    for entry in std::fs::read_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("conformance")).unwrap() {
+        let path = entry.unwrap().path();
+        let text = std::fs::read_to_string(&path).unwrap();
+        let rows: Vec<&[u8]> = text.lines().map(str::as_bytes).collect();
+        let mut crosses = Vec::new();
+        for (y, five) in rows.windows(5).enumerate() {
+            for x in 0..five[0].len().saturating_sub(2) {
+                let glyph: Vec<&[u8]> = five.iter().map(|row| &row[x..x + 3]).collect();
+                if glyph == [b"...", b"#.#", b".#.", b"#.#", b"..."] {
+                    crosses.push((x, y + 1));
+                }
+            }
+        }
+        let expected = match path.file_name().unwrap().to_str().unwrap() {
+            "5-quirks.modernChip8.txt" => vec![(59, 2), (59, 12)],
+            _ => vec![],
+        };
+        assert_eq!(crosses, expected, "crosses in {}", path.display());
+    }
+}
@@ -0,0 +1,38 @@
+#![allow(clippy::needless_return, clippy::unused_unit)]
+
+use krhip8::Chip8::{Chip8, Config};
+use krhip8::Coverage::Coverage;
+
+#[test]
+fn register_skips_with_n_are_branches() {
+    let rom = [
+        0x61, 0x05, // 0x200: LD V1, 5
+        0x62, 0x05, // 0x202: LD V2, 5
+        0x51, 0x21, // 0x204: SE V1, V2 with N=1, skips
+        0x12, 0x06, // 0x206: JP 0x206
+        0x62, 0x06, // 0x208: LD V2, 6
+        0x91, 0x23, // 0x20A: SNE V1, V2 with N=3, skips
+        0x12, 0x0C, // 0x20C: JP 0x20C
+        0x12, 0x0E, // 0x20E: JP 0x20E
+    ];
+    let mut chip8 = Chip8::new(Config {
+        seed: Some(0),
+        ..Default::default()
+    });
+    chip8.load_rom(&rom).unwrap();
+    chip8.coverage = Some(Coverage::new(chip8.ram.as_slice(), 0x200));
+    for _ in 0..6 {
+        chip8.step().unwrap();
+    }
+    assert_eq!(chip8.pc(), 0x20E);
+
+    let coverage = chip8.coverage.as_ref().unwrap();
+    // both skips went one way of two
+    assert_eq!(coverage.totals(&chip8.ram), (6, 8, 2, 4));
+    let annotated = coverage.annotated(&chip8.ram);
+    assert!(annotated.contains("! 0x0204  5121"), "{}", annotated);
+    assert!(annotated.contains("skipped 1x, went on 0x"), "{}", annotated);
+    let lcov = coverage.lcov(&chip8.ram, "rom.ch8");
+    assert!(lcov.contains("BRDA:516,0,0,1\nBRDA:516,0,1,0\n"), "{}", lcov);
+    assert!(lcov.contains("BRDA:522,0,0,1\nBRDA:522,0,1,0\n"), "{}", lcov);
+}
@@ -0,0 +1,116 @@
+#![allow(clippy::needless_return, clippy::unused_unit)]
+
+use krhip8::Database::{sha1_hex, Database};
+use krhip8::Quirks::Quirks;
+use krhip8::Rom::Platform;
+use krhip8::Screenshot::Palette;
+use std::path::PathBuf;
+
+/// A database checkout in the temp directory, removed when dropped.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str, files: &[(&str, &str)]) -> Self {
+        let dir = std::env::temp_dir().join(format!("krhip8-{}-{}", std::process::id(), name));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (file, text) in files {
+            std::fs::write(dir.join(file), text).unwrap();
+        }
+        return TempDir(dir);
+    }
+
+    fn path(&self) -> &str {
+        return self.0.to_str().unwrap();
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) -> () {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+const ROM: &[u8] = b"\x00\xE0\x12\x00";
+
+/// One program for `ROM` on the `custom` platform, which turns on `shift`
+/// and `vblank` and runs 20 instructions a frame, with `vblank` turned off
+/// again for this ROM.
+fn files(hash: &str) -> [(&'static str, String); 3] {
+    return [
+        ("sha1-hashes.json", format!("{{\"{}\": 0}}", hash)),
+        (
+            "programs.json",
+            format!(
+                r##"[{{
+                    "title": "Clear",
+                    "authors": ["a", "b"],
+                    "roms": {{"{}": {{
+                        "platforms": ["custom"],
+                        "quirkyPlatforms": {{"custom": {{"vblank": false}}}},
+                        "keys": {{"up": 5, "down": 8}},
+                        "colors": {{"pixels": ["#000000", "#FF8800"]}}
+                    }}}}
+                }}]"##,
+                hash
+            ),
+        ),
+        (
+            "platforms.json",
+            r#"[{"id": "custom", "defaultTickrate": 20, "quirks": {"shift": true, "vblank": true}}]"#.to_string(),
+        ),
+    ];
+}
+
+#[test]
+fn sha1() {
+    assert_eq!(sha1_hex(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+}
+
+#[test]
+fn bundled_hit_and_miss() {
+    let database = Database::bundled();
+    let rom = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/test_opcode.ch8")).unwrap();
+    let info = database.lookup(&rom).unwrap();
+    assert_eq!(info.title, "Opcode test");
+    assert_eq!(info.authors, ["corax89"]);
+    assert_eq!(info.platform_id.as_deref(), Some("modernChip8"));
+    assert_eq!(info.platform, Some(Platform::Chip8));
+    assert_eq!(info.quirks, Quirks::for_platform("modernChip8"));
+
+    // one byte off is a different hash
+    let mut changed = rom.clone();
+    changed[0] ^= 1;
+    assert_eq!(database.lookup(&changed), None);
+    assert_eq!(database.lookup(ROM), None);
+}
+
+#[test]
+fn loaded_checkout() {
+    let files = files(&sha1_hex(ROM));
+    let files: Vec<(&str, &str)> = files.iter().map(|(name, text)| (*name, text.as_str())).collect();
+    let dir = TempDir::new("database", &files);
+    let database = Database::load(dir.path()).unwrap();
+
+    let info = database.lookup(ROM).unwrap();
+    assert_eq!(info.title, "Clear");
+    assert_eq!(info.authors, ["a", "b"]);
+    assert_eq!(info.platform_id.as_deref(), Some("custom"));
+    assert_eq!(info.platform, None);
+    let quirks = info.quirks.unwrap();
+    assert_eq!(quirks.get("shift"), Some(true));
+    assert_eq!(quirks.get("vblank"), Some(false));
+    assert_eq!(info.tickrate, Some(20));
+    assert_eq!(info.keys, [("down".to_string(), 8), ("up".to_string(), 5)]);
+    assert_eq!(info.colours, Palette::parse("000000,FF8800"));
+    assert_eq!(database.lookup(b"\x12\x00"), None);
+}
+
+#[test]
+fn broken_checkouts() {
+    let dir = TempDir::new("no-database", &[("programs.json", "[]")]);
+    assert!(Database::load(dir.path()).is_err());
+
+    let dir = TempDir::new("bad-database", &[("sha1-hashes.json", "{"), ("programs.json", "[]")]);
+    let err = Database::load(dir.path()).err().unwrap();
+    assert!(err.to_string().starts_with("sha1-hashes.json: "), "{}", err);
+}
@@ -0,0 +1,511 @@
+#![allow(clippy::needless_return, clippy::unused_unit)]
+
+use krhip8::Chip8::{Chip8, Chip8Errors, Config};
+use krhip8::Quirks::Quirks;
+
+/// A machine with every quirk off, with `quirks` changing that, and an
+/// empty program at 0x200.
+fn machine(quirks: impl FnOnce(&mut Quirks)) -> Chip8 {
+    let mut config = Config {
+        quirks: Quirks::for_platform("modernChip8").unwrap(),
+        seed: Some(0),
+        ..Default::default()
+    };
+    quirks(&mut config.quirks);
+    let mut chip8 = Chip8::new(config);
+    chip8.load_rom(&[0x00, 0x00]).unwrap();
+    return chip8;
+}
+
+/// Puts `opcode` at `pc` and runs it.
+fn run(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Errors> {
+    let pc = chip8.pc();
+    chip8.write_ram(pc, (opcode >> 8) as u8)?;
+    chip8.write_ram(pc + 1, opcode as u8)?;
+    return chip8.step();
+}
+
+/// Runs `opcode` with `vx` in V1 and `vy` in V2, returns V1 and VF.
+fn alu(chip8: &mut Chip8, opcode: u16, vx: u8, vy: u8) -> (u8, u8) {
+    chip8.set_v(1, vx);
+    chip8.set_v(2, vy);
+    chip8.set_v(0xF, 0xAA);
+    run(chip8, opcode).unwrap();
+    return (chip8.v(1), chip8.v(0xF));
+}
+
+/// The lit pixels of the screen.
+fn lit(chip8: &Chip8) -> Vec<(usize, usize)> {
+    let mut pixels = Vec::new();
+    for y in 0..32 {
+        for x in 0..64 {
+            if chip8.pixel(x, y) {
+                pixels.push((x, y));
+            }
+        }
+    }
+    return pixels;
+}
+
+#[test]
+fn cls() {
+    let mut chip8 = machine(|_| ());
+    chip8.set_ir(0x50);
+    run(&mut chip8, 0xD005).unwrap();
+    assert!(!lit(&chip8).is_empty());
+    run(&mut chip8, 0x00E0).unwrap();
+    assert!(lit(&chip8).is_empty());
+    assert_eq!(chip8.pc(), 0x204);
+}
+
+#[test]
+fn jump_call_and_return() {
+    let mut chip8 = machine(|_| ());
+    run(&mut chip8, 0x1ABC).unwrap();
+    assert_eq!(chip8.pc(), 0xABC);
+
+    chip8.set_pc(0x200);
+    run(&mut chip8, 0x2300).unwrap();
+    assert_eq!(chip8.pc(), 0x300);
+    assert_eq!(chip8.stack(), &[0x202]);
+    run(&mut chip8, 0x00EE).unwrap();
+    assert_eq!(chip8.pc(), 0x202);
+    assert!(chip8.stack().is_empty());
+
+    let err = run(&mut chip8, 0x00EE).unwrap_err();
+    assert!(matches!(err, Chip8Errors::StackUnderflow { at: 0x202 }));
+}
+
+#[test]
+fn call_overflows_at_the_stack_depth() {
+    let mut chip8 = machine(|_| ());
+    chip8.set_stack_depth(2);
+    run(&mut chip8, 0x2200).unwrap();
+    run(&mut chip8, 0x2200).unwrap();
+    let err = run(&mut chip8, 0x2200).unwrap_err();
+    assert!(matches!(err, Chip8Errors::StackOverflow { at: 0x200, depth: 2 }));
+    assert_eq!(chip8.stack().len(), 2);
+}
+
+#[test]
+fn skips() {
+    // opcode, V1, V2, pressed key, skipped
+    let cases: [(u16, u8, u8, Option<u8>, bool); 16] = [
+        (0x3142, 0x42, 0, None, true),
+        (0x3142, 0x41, 0, None, false),
+        (0x4142, 0x42, 0, None, false),
+        (0x4142, 0x41, 0, None, true),
+        (0x5120, 7, 7, None, true),
+        (0x5120, 7, 8, None, false),
+        (0x9120, 7, 7, None, false),
+        (0x9120, 7, 8, None, true),
+        (0xE19E, 0xA, 0, Some(0xA), true),
+        (0xE19E, 0xA, 0, Some(0xB), false),
+        (0xE19E, 0xA, 0, None, false),
+        (0xE1A1, 0xA, 0, Some(0xA), false),
+        (0xE1A1, 0xA, 0, Some(0xB), true),
+        (0xE1A1, 0xA, 0, None, true),
+        // VF takes part like any register
+        (0x3F00, 0, 0, None, true),
+        (0x5FF0, 0, 0, None, true),
+    ];
+    for (opcode, v1, v2, key, skipped) in cases {
+        let mut chip8 = machine(|_| ());
+        chip8.set_v(1, v1);
+        chip8.set_v(2, v2);
+        if let Some(key) = key {
+            chip8.set_key(key, true);
+        }
+        run(&mut chip8, opcode).unwrap();
+        let next = if skipped { 0x204 } else { 0x202 };
+        assert_eq!(chip8.pc(), next, "{:04X} with V1={:02X} V2={:02X}", opcode, v1, v2);
+    }
+}
+
+#[test]
+fn load_and_add_byte() {
+    let mut chip8 = machine(|_| ());
+    run(&mut chip8, 0x61FE).unwrap();
+    assert_eq!(chip8.v(1), 0xFE);
+    chip8.set_v(0xF, 0xAA);
+    run(&mut chip8, 0x7103).unwrap();
+    // wraps around and leaves VF alone
+    assert_eq!(chip8.v(1), 0x01);
+    assert_eq!(chip8.v(0xF), 0xAA);
+    run(&mut chip8, 0x6F05).unwrap();
+    run(&mut chip8, 0x7F01).unwrap();
+    assert_eq!(chip8.v(0xF), 0x06);
+}
+
+#[test]
+fn load_register() {
+    let mut chip8 = machine(|_| ());
+    assert_eq!(alu(&mut chip8, 0x8120, 1, 0x99), (0x99, 0xAA));
+}
+
+#[test]
+fn logic_with_and_without_the_vf_reset() {
+    for logic in [false, true] {
+        let mut chip8 = machine(|q| q.logic = logic);
+        let vf = if logic { 0 } else { 0xAA };
+        assert_eq!(alu(&mut chip8, 0x8121, 0b1100, 0b1010), (0b1110, vf));
+        assert_eq!(alu(&mut chip8, 0x8122, 0b1100, 0b1010), (0b1000, vf));
+        assert_eq!(alu(&mut chip8, 0x8123, 0b1100, 0b1010), (0b0110, vf));
+    }
+}
+
+#[test]
+fn add_and_subtract_set_the_flag() {
+    let mut chip8 = machine(|_| ());
+    assert_eq!(alu(&mut chip8, 0x8124, 0xF0, 0x0F), (0xFF, 0));
+    assert_eq!(alu(&mut chip8, 0x8124, 0xF0, 0x20), (0x10, 1));
+    assert_eq!(alu(&mut chip8, 0x8124, 0xFF, 0xFF), (0xFE, 1));
+
+    // VF is 1 when there is no borrow, equal values don't borrow
+    assert_eq!(alu(&mut chip8, 0x8125, 0x30, 0x10), (0x20, 1));
+    assert_eq!(alu(&mut chip8, 0x8125, 0x10, 0x10), (0x00, 1));
+    assert_eq!(alu(&mut chip8, 0x8125, 0x10, 0x30), (0xE0, 0));
+
+    assert_eq!(alu(&mut chip8, 0x8127, 0x10, 0x30), (0x20, 1));
+    assert_eq!(alu(&mut chip8, 0x8127, 0x10, 0x10), (0x00, 1));
+    assert_eq!(alu(&mut chip8, 0x8127, 0x30, 0x10), (0xE0, 0));
+}
+
+#[test]
+fn shifts_with_and_without_the_shift_quirk() {
+    let mut chip8 = machine(|q| q.shift = false);
+    // VY is shifted into VX
+    assert_eq!(alu(&mut chip8, 0x8126, 0xFF, 0b0000_0011), (0b0000_0001, 1));
+    assert_eq!(alu(&mut chip8, 0x8126, 0xFF, 0b0000_0010), (0b0000_0001, 0));
+    assert_eq!(alu(&mut chip8, 0x812E, 0xFF, 0b1100_0000), (0b1000_0000, 1));
+    assert_eq!(alu(&mut chip8, 0x812E, 0xFF, 0b0100_0000), (0b1000_0000, 0));
+    assert_eq!(chip8.v(2), 0b0100_0000);
+
+    let mut chip8 = machine(|q| q.shift = true);
+    // VX is shifted in place, VY is ignored
+    assert_eq!(alu(&mut chip8, 0x8126, 0b0000_0101, 0xFF), (0b0000_0010, 1));
+    assert_eq!(alu(&mut chip8, 0x812E, 0b0100_0001, 0xFF), (0b1000_0010, 0));
+}
+
+#[test]
+fn flag_as_destination_keeps_the_flag() {
+    // with X = F the flag is written last and the result is lost
+    let cases: [(u16, u8, u8, u8); 6] = [
+        (0x8F14, 0xFF, 0x01, 1),
+        (0x8F14, 0x01, 0x01, 0),
+        (0x8F15, 0x01, 0x02, 0),
+        (0x8F17, 0x01, 0x02, 1),
+        (0x8F16, 0x03, 0x00, 1),
+        (0x8F1E, 0x80, 0x00, 1),
+    ];
+    for shift in [false, true] {
+        for (opcode, vf, v1, flag) in cases {
+            let mut chip8 = machine(|q| q.shift = shift);
+            chip8.set_v(0xF, vf);
+            chip8.set_v(1, v1);
+            if matches!(opcode & 0xF, 0x6 | 0xE) {
+                // without the shift quirk the shifts read VY instead
+                chip8.set_v(1, vf);
+            }
+            run(&mut chip8, opcode).unwrap();
+            assert_eq!(chip8.v(0xF), flag, "{:04X} with VF={:02X}", opcode, vf);
+        }
+    }
+
+    let mut chip8 = machine(|q| q.logic = true);
+    chip8.set_v(0xF, 0x0F);
+    chip8.set_v(1, 0xF0);
+    run(&mut chip8, 0x8F11).unwrap();
+    assert_eq!(chip8.v(0xF), 0);
+}
+
+#[test]
+fn undefined_instructions() {
+    for opcode in [
+        0x0000, 0x0123, 0x00FF, 0x8128, 0x812D, 0x8FFF, 0x8EFF, 0xE19F, 0xF000, 0xF1FF,
+    ] {
+        let mut chip8 = machine(|_| ());
+        let err = run(&mut chip8, opcode).unwrap_err();
+        assert!(matches!(err, Chip8Errors::UndefinedInstruction), "{:04X}", opcode);
+    }
+}
+
+#[test]
+fn load_i_and_jump_with_offset() {
+    let mut chip8 = machine(|_| ());
+    run(&mut chip8, 0xA123).unwrap();
+    assert_eq!(chip8.ir(), 0x123);
+
+    chip8.set_v(0, 0x10);
+    chip8.set_v(3, 0x20);
+    run(&mut chip8, 0xB300).unwrap();
+    assert_eq!(chip8.pc(), 0x310);
+
+    let mut chip8 = machine(|q| q.jump = true);
+    chip8.set_v(0, 0x10);
+    chip8.set_v(3, 0x20);
+    run(&mut chip8, 0xB300).unwrap();
+    assert_eq!(chip8.pc(), 0x320);
+}
+
+#[test]
+fn random_is_masked() {
+    let mut chip8 = machine(|_| ());
+    for _ in 0..64 {
+        chip8.set_pc(0x200);
+        run(&mut chip8, 0xC10F).unwrap();
+        assert_eq!(chip8.v(1) & 0xF0, 0);
+        run(&mut chip8, 0xC100).unwrap();
+        assert_eq!(chip8.v(1), 0);
+    }
+
+    // the same seed gives the same numbers
+    let draws = || {
+        let mut chip8 = machine(|_| ());
+        (0..8)
+            .map(|_| {
+                chip8.set_pc(0x200);
+                run(&mut chip8, 0xC1FF).unwrap();
+                chip8.v(1)
+            })
+            .collect::<Vec<u8>>()
+    };
+    assert_eq!(draws(), draws());
+}
+
+#[test]
+fn draw_and_collide() {
+    let mut chip8 = machine(|_| ());
+    // the font's 0 is 0xF0 0x90 0x90 0x90 0xF0
+    chip8.set_ir(0x50);
+    chip8.set_v(1, 10);
+    chip8.set_v(2, 5);
+    run(&mut chip8, 0xD125).unwrap();
+    assert_eq!(chip8.v(0xF), 0);
+    let pixels = lit(&chip8);
+    assert_eq!(pixels.len(), 4 + 2 + 2 + 2 + 4);
+    assert!(pixels.contains(&(10, 5)) && pixels.contains(&(13, 9)) && !pixels.contains(&(11, 6)));
+
+    // drawing it again erases it and collides
+    chip8.set_pc(0x200);
+    run(&mut chip8, 0xD125).unwrap();
+    assert_eq!(chip8.v(0xF), 1);
+    assert!(lit(&chip8).is_empty());
+    assert_eq!(chip8.last_sprite(), Some((0x50, 5)));
+}
+
+#[test]
+fn draw_clips_or_wraps_at_the_edges() {
+    for wrap in [false, true] {
+        let mut chip8 = machine(|q| q.wrap = wrap);
+        // a solid 8 pixel wide row
+        chip8.write_ram(0x300, 0xFF).unwrap();
+        chip8.write_ram(0x301, 0xFF).unwrap();
+        chip8.write_ram(0x302, 0xFF).unwrap();
+        chip8.set_ir(0x300);
+        chip8.set_v(1, 60);
+        chip8.set_v(2, 31);
+        run(&mut chip8, 0xD123).unwrap();
+        let pixels = lit(&chip8);
+        for x in 60..64 {
+            assert!(pixels.contains(&(x, 31)));
+        }
+        if wrap {
+            // 4 pixels on the right, 4 on the left, 3 rows from the bottom up
+            assert_eq!(pixels.len(), 8 * 3);
+            assert!(pixels.contains(&(0, 31)) && pixels.contains(&(3, 0)) && pixels.contains(&(63, 1)));
+        } else {
+            assert_eq!(pixels.len(), 4);
+        }
+    }
+}
+
+#[test]
+fn draw_wraps_the_start_position() {
+    for wrap in [false, true] {
+        let mut chip8 = machine(|q| q.wrap = wrap);
+        chip8.set_ir(0x50);
+        chip8.set_v(1, 64 + 2);
+        chip8.set_v(2, 32 + 1);
+        run(&mut chip8, 0xD121).unwrap();
+        assert_eq!(lit(&chip8), vec![(2, 1), (3, 1), (4, 1), (5, 1)]);
+    }
+}
+
+#[test]
+fn draw_waits_for_vblank() {
+    let mut chip8 = machine(|q| q.vblank = true);
+    chip8.set_ir(0x50);
+    run(&mut chip8, 0xD011).unwrap();
+    assert_eq!(chip8.pc(), 0x202);
+    // the second sprite in a frame waits
+    run(&mut chip8, 0xD011).unwrap();
+    assert_eq!(chip8.pc(), 0x202);
+    chip8.begin_frame();
+    chip8.step().unwrap();
+    assert_eq!(chip8.pc(), 0x204);
+
+    let mut chip8 = machine(|q| q.vblank = false);
+    chip8.set_ir(0x50);
+    run(&mut chip8, 0xD011).unwrap();
+    run(&mut chip8, 0xD011).unwrap();
+    assert_eq!(chip8.pc(), 0x204);
+}
+
+#[test]
+fn timers() {
+    let mut chip8 = machine(|_| ());
+    chip8.set_v(1, 30);
+    run(&mut chip8, 0xF115).unwrap();
+    assert_eq!(chip8.delay_timer, 30);
+    run(&mut chip8, 0xF207).unwrap();
+    assert_eq!(chip8.v(2), 30);
+    // they count down once a frame, not once an instruction
+    chip8.begin_frame();
+    assert_eq!(chip8.delay_timer, 29);
+
+    run(&mut chip8, 0xF118).unwrap();
+    assert_eq!(chip8.soud_timer, 30);
+    assert!(chip8.sound_active());
+    for _ in 0..29 {
+        chip8.begin_frame();
+    }
+    assert_eq!(chip8.delay_timer, 0);
+    assert!(chip8.sound_active());
+    chip8.begin_frame();
+    assert!(!chip8.sound_active());
+    run(&mut chip8, 0xF118).unwrap();
+    chip8.set_v(1, 0);
+    run(&mut chip8, 0xF118).unwrap();
+    assert!(!chip8.sound_active());
+}
+
+#[test]
+fn wait_for_key() {
+    let mut chip8 = machine(|_| ());
+    run(&mut chip8, 0xF30A).unwrap();
+    assert_eq!(chip8.pc(), 0x200);
+    run(&mut chip8, 0xF30A).unwrap();
+    assert_eq!(chip8.pc(), 0x200);
+    // the key has to go down and come up again, like on the VIP
+    chip8.set_key(0xC, true);
+    run(&mut chip8, 0xF30A).unwrap();
+    assert_eq!(chip8.pc(), 0x200);
+    chip8.set_key(0xA, true);
+    run(&mut chip8, 0xF30A).unwrap();
+    assert_eq!(chip8.pc(), 0x200);
+    chip8.set_key(0xC, false);
+    run(&mut chip8, 0xF30A).unwrap();
+    assert_eq!(chip8.pc(), 0x202);
+    assert_eq!(chip8.v(3), 0xC);
+}
+
+#[test]
+fn add_to_i() {
+    let mut chip8 = machine(|_| ());
+    chip8.set_ir(0x300);
+    chip8.set_v(1, 0x10);
+    chip8.set_v(0xF, 0xAA);
+    run(&mut chip8, 0xF11E).unwrap();
+    assert_eq!(chip8.ir(), 0x310);
+
+    // past the end of memory I just keeps counting, VF isn't touched
+    chip8.set_ir(0xFFF);
+    chip8.set_v(1, 0xFF);
+    run(&mut chip8, 0xF11E).unwrap();
+    assert_eq!(chip8.ir(), 0x10FE);
+    assert_eq!(chip8.v(0xF), 0xAA);
+}
+
+#[test]
+fn font_character() {
+    let mut chip8 = machine(|_| ());
+    for digit in 0..16u8 {
+        chip8.set_v(4, digit);
+        run(&mut chip8, 0xF429).unwrap();
+        assert_eq!(chip8.ir(), 0x50 + 5 * digit as u16);
+    }
+    chip8.set_v(4, 0xA);
+    run(&mut chip8, 0xF429).unwrap();
+    let glyph: Vec<u8> = (0..5).map(|row| chip8.read_ram(chip8.ir() + row).unwrap()).collect();
+    assert_eq!(glyph, [0xF0, 0x90, 0xF0, 0x90, 0x90]);
+}
+
+#[test]
+fn bcd() {
+    let mut chip8 = machine(|_| ());
+    for (value, digits) in [(254u8, [2, 5, 4]), (7, [0, 0, 7]), (60, [0, 6, 0]), (0, [0, 0, 0])] {
+        chip8.set_ir(0x300);
+        chip8.set_v(5, value);
+        run(&mut chip8, 0xF533).unwrap();
+        let stored: Vec<u8> = (0..3).map(|i| chip8.read_ram(0x300 + i).unwrap()).collect();
+        assert_eq!(stored, digits);
+        assert_eq!(chip8.ir(), 0x300);
+    }
+
+    // right up to the last byte of memory
+    let top = chip8.memory().len() as u16 - 3;
+    chip8.set_ir(top);
+    chip8.set_v(5, 123);
+    run(&mut chip8, 0xF533).unwrap();
+    assert_eq!(&chip8.memory()[top as usize..], &[1, 2, 3]);
+}
+
+#[test]
+fn store_and_load_registers() {
+    // quirks: increment by X, leave I unchanged; I afterwards for X = 3
+    let cases = [
+        (false, false, 0x304),
+        (true, false, 0x303),
+        (false, true, 0x300),
+        (true, true, 0x300),
+    ];
+    for (by_x, unchanged, ir) in cases {
+        let mut chip8 = machine(|q| {
+            q.memory_increment_by_x = by_x;
+            q.memory_leave_i_unchanged = unchanged;
+        });
+        for x in 0..16 {
+            chip8.set_v(x, 0x10 + x as u8);
+        }
+        chip8.set_ir(0x300);
+        run(&mut chip8, 0xF355).unwrap();
+        assert_eq!(&chip8.memory()[0x300..0x305], &[0x10, 0x11, 0x12, 0x13, 0x00]);
+        assert_eq!(chip8.ir(), ir);
+
+        for x in 0..16 {
+            chip8.set_v(x, 0);
+        }
+        chip8.set_ir(0x300);
+        run(&mut chip8, 0xF365).unwrap();
+        assert_eq!(&chip8.registers()[..5], &[0x10, 0x11, 0x12, 0x13, 0x00]);
+        assert_eq!(chip8.ir(), ir);
+    }
+
+    // all sixteen, VF included
+    let mut chip8 = machine(|_| ());
+    chip8.set_v(0xF, 0x5A);
+    chip8.set_ir(0x400);
+    run(&mut chip8, 0xFF55).unwrap();
+    assert_eq!(chip8.read_ram(0x40F), Some(0x5A));
+    chip8.set_v(0xF, 0);
+    chip8.set_ir(0x400);
+    run(&mut chip8, 0xFF65).unwrap();
+    assert_eq!(chip8.v(0xF), 0x5A);
+}
+
+#[test]
+fn poked_bytes_count_as_set() {
+    let mut chip8 = machine(|_| ());
+    chip8.set_memory_tracking(true);
+    chip8.write_ram(0x300, 0x42).unwrap();
+    chip8.set_ir(0x300);
+    run(&mut chip8, 0xF065).unwrap();
+    assert_eq!(chip8.v(0), 0x42);
+    assert_eq!(chip8.warning_count(), 0);
+
+    // a byte nothing wrote still warns
+    chip8.set_ir(0x301);
+    run(&mut chip8, 0xF065).unwrap();
+    assert_eq!(chip8.warning_count(), 1);
+}
@@ -0,0 +1,47 @@
+#![allow(clippy::needless_return, clippy::unused_unit)]
+
+use krhip8::Framebuffer::Framebuffer;
+
+/// The lit pixels of `display`.
+fn lit(display: &Framebuffer) -> Vec<(usize, usize)> {
+    let mut pixels = Vec::new();
+    for y in 0..display.height() {
+        for x in 0..display.width() {
+            if display.pixel(x, y) {
+                pixels.push((x, y));
+            }
+        }
+    }
+    return pixels;
+}
+
+#[test]
+fn lores_clips_and_wraps() {
+    let mut display = Framebuffer::lores();
+    assert_eq!((display.width(), display.height()), (64, 32));
+    assert!(!display.draw(62, 31, &[0b1111_0000, 0b1000_0000], 8, false));
+    assert_eq!(lit(&display), [(62, 31), (63, 31)]);
+    // the same sprite again turns them off
+    assert!(display.draw(62, 31, &[0b1111_0000, 0b1000_0000], 8, false));
+    assert!(lit(&display).is_empty());
+
+    display.draw(62, 31, &[0b1111_0000, 0b1000_0000], 8, true);
+    assert_eq!(lit(&display), [(62, 0), (0, 31), (1, 31), (62, 31), (63, 31)]);
+    display.clear();
+    assert!(lit(&display).is_empty());
+}
+
+#[test]
+fn hires_rows() {
+    let mut display = Framebuffer::hires();
+    assert_eq!((display.width(), display.height()), (128, 64));
+    // a 16 pixel wide sprite across the right edge of the wider screen
+    assert!(!display.draw(120, 63, &[0xFFFF], 16, false));
+    assert_eq!(display.row(63), 0xFF);
+    assert!(display.draw(120 + 128, 63 + 64, &[0x8001], 16, true));
+    // the last pixel comes back at the left
+    assert_eq!(display.row(63), 0x7F | 1 << 120);
+    assert!(display.pixel(7, 63));
+    assert_eq!(display.unpack().iter().filter(|pixel| **pixel == 1).count(), 8);
+    assert_ne!(display, Framebuffer::lores());
+}
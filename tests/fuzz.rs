@@ -0,0 +1,280 @@
+#![allow(clippy::needless_return, clippy::unused_unit)]
+
+use krhip8::Chip8::{Chip8, Chip8Errors, Config};
+use krhip8::Quirks::Quirks;
+use krhip8::Rom::Platform;
+use proptest::prelude::*;
+
+/// Instructions run per case, a program is over long before most of the
+/// time.
+const STEPS: usize = 3000;
+
+/// A key going down or up before the instruction with that index.
+type KeyEvent = (usize, u8, bool);
+
+fn platform() -> impl Strategy<Value = Platform> {
+    return prop_oneof![
+        Just(Platform::Chip8),
+        Just(Platform::Schip),
+        Just(Platform::XoChip),
+        Just(Platform::Eti660),
+    ];
+}
+
+fn quirks() -> impl Strategy<Value = Quirks> {
+    return any::<[bool; 7]>().prop_map(|on| {
+        let mut quirks = Quirks::default();
+        for (name, on) in Quirks::NAMES.iter().zip(on) {
+            quirks.set(name, on);
+        }
+        quirks
+    });
+}
+
+/// Mostly real instructions: `0NNN` other than `00E0`/`00EE` is undefined
+/// and would end nearly every program at once, so half of them become one
+/// of those two. `assemble` points jumps and calls into the program.
+fn program() -> impl Strategy<Value = Vec<u16>> {
+    let opcode = any::<u16>().prop_map(|opcode| match opcode {
+        0x0000..=0x07FF => 0x00E0,
+        0x0800..=0x0FFF if opcode & 1 == 0 => 0x00EE,
+        _ => opcode,
+    });
+    return prop::collection::vec(opcode, 1..256);
+}
+
+fn keys() -> impl Strategy<Value = Vec<KeyEvent>> {
+    return prop::collection::vec((0..STEPS, 0u8..16, any::<bool>()), 0..16);
+}
+
+fn machine(platform: Platform, quirks: Quirks, rom: &[u8]) -> Chip8 {
+    let mut chip8 = Chip8::new(Config {
+        platform,
+        quirks,
+        seed: Some(1),
+        ..Default::default()
+    });
+    let size = rom.len().min(platform.max_rom_size());
+    chip8.load_rom(&rom[..size]).unwrap();
+    return chip8;
+}
+
+/// Assembles `program` for `platform`, with the targets of `1NNN` and
+/// `2NNN` moved onto its instructions; random ones nearly always land in
+/// empty memory.
+fn assemble(platform: Platform, program: &[u16]) -> Vec<u8> {
+    let load = platform.load_address();
+    let mut rom = Vec::new();
+    for opcode in program {
+        let mut opcode = *opcode;
+        if matches!(opcode & 0xF000, 0x1000 | 0x2000) {
+            let target = load + 2 * (opcode & 0x0FFF) % (2 * program.len() as u16);
+            opcode = opcode & 0xF000 | target;
+        }
+        rom.extend(opcode.to_be_bytes());
+    }
+    return rom;
+}
+
+/// Runs `STEPS` instructions, pressing `keys` on the way and starting a
+/// frame every 10 instructions. Errors don't stop it, like a debugger
+/// carrying on past them, except that running off the end of memory starts
+/// the program over. Returns the errors.
+fn run(chip8: &mut Chip8, keys: &[KeyEvent]) -> Vec<Chip8Errors> {
+    let mut errors = Vec::new();
+    for step in 0..STEPS {
+        for (_, key, pressed) in keys.iter().filter(|(at, _, _)| *at == step) {
+            chip8.set_key(*key, *pressed);
+        }
+        if step % 10 == 0 {
+            chip8.begin_frame();
+        }
+        if let Err(err) = chip8.step() {
+            if matches!(err, Chip8Errors::OutOfBounds { at, .. } if at == chip8.pc()) {
+                chip8.set_pc(chip8.platform().load_address());
+            }
+            errors.push(err);
+        }
+    }
+    return errors;
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(200))]
+
+    #[test]
+    fn programs_never_panic(
+        platform in platform(),
+        quirks in quirks(),
+        tracking in any::<bool>(),
+        program in program(),
+        keys in keys(),
+    ) {
+        let mut chip8 = machine(platform, quirks, &assemble(platform, &program));
+        chip8.set_memory_tracking(tracking);
+        for err in run(&mut chip8, &keys) {
+            // the message is made without panicking too
+            prop_assert!(!err.to_string().is_empty());
+            prop_assert!(Chip8Errors::NAMES.contains(&err.name()));
+        }
+    }
+
+    #[test]
+    fn random_bytes_never_panic(platform in platform(), rom in prop::collection::vec(any::<u8>(), 1..512)) {
+        let mut chip8 = machine(platform, Quirks::default(), &rom);
+        run(&mut chip8, &[]);
+    }
+
+    #[test]
+    fn block_cache_runs_like_stepping(platform in platform(), quirks in quirks(), program in program()) {
+        let rom = assemble(platform, &program);
+        let mut stepped = machine(platform, quirks, &rom);
+        let mut cached = machine(platform, quirks, &rom);
+        cached.set_block_cache(true);
+        for _ in 0..100 {
+            stepped.begin_frame();
+            cached.begin_frame();
+            let a = stepped.run_cycles(30).map_err(|err| err.to_string());
+            let b = cached.run_cycles(30).map_err(|err| err.to_string());
+            prop_assert_eq!(&a, &b);
+            prop_assert_eq!(stepped.pc(), cached.pc());
+            prop_assert_eq!(stepped.registers(), cached.registers());
+            prop_assert_eq!(stepped.ir(), cached.ir());
+            prop_assert_eq!(stepped.memory(), cached.memory());
+            prop_assert_eq!(stepped.framebuffer(), cached.framebuffer());
+            if a.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// A VIP machine with `opcode` at `pc`.
+fn at(pc: u16, opcode: u16) -> Chip8 {
+    let mut chip8 = Chip8::new(Config {
+        seed: Some(0),
+        ..Default::default()
+    });
+    chip8.load_rom(&[0x00, 0xE0]).unwrap();
+    chip8.write_ram(pc, (opcode >> 8) as u8).unwrap();
+    chip8.write_ram(pc + 1, opcode as u8).unwrap();
+    chip8.set_pc(pc);
+    return chip8;
+}
+
+#[test]
+fn running_off_the_end_of_memory() {
+    let mut chip8 = at(0xFFE, 0x6000);
+    chip8.step().unwrap();
+    assert_eq!(chip8.pc(), 0x1000);
+    let err = chip8.step().unwrap_err();
+    assert!(matches!(
+        err,
+        Chip8Errors::OutOfBounds {
+            at: 0x1000,
+            address: 0x1000
+        }
+    ));
+
+    // half an instruction left
+    let mut chip8 = at(0xFFE, 0x6000);
+    chip8.set_pc(0xFFF);
+    let err = chip8.step().unwrap_err();
+    assert!(matches!(
+        err,
+        Chip8Errors::OutOfBounds {
+            at: 0xFFF,
+            address: 0x1000
+        }
+    ));
+
+    // skipping past the end
+    let mut chip8 = at(0xFFC, 0x3000);
+    chip8.step().unwrap();
+    assert!(matches!(chip8.step().unwrap_err(), Chip8Errors::OutOfBounds { .. }));
+
+    // BNNN as far as it goes
+    let mut chip8 = at(0x300, 0xBFFF);
+    chip8.set_v(0, 0xFF);
+    chip8.step().unwrap();
+    assert_eq!(chip8.pc(), 0x10FE);
+    assert!(matches!(chip8.step().unwrap_err(), Chip8Errors::OutOfBounds { .. }));
+}
+
+#[test]
+fn xo_chip_wraps_around_its_64k() {
+    let mut chip8 = Chip8::new(Config {
+        platform: Platform::XoChip,
+        seed: Some(0),
+        ..Default::default()
+    });
+    chip8.load_rom(&[0x00, 0xE0]).unwrap();
+    chip8.write_ram(0xFFFE, 0x60).unwrap();
+    chip8.set_pc(0xFFFE);
+    chip8.step().unwrap();
+    assert_eq!(chip8.pc(), 0x0000);
+}
+
+#[test]
+fn memory_instructions_past_the_end() {
+    // FX33, FX55 and FX65 with I so close to the end that the last byte
+    // doesn't fit; nothing is written
+    for (opcode, ir) in [(0xF033, 0xFFE), (0xF155, 0xFFF), (0xFF65, 0xFF1), (0xF033, 0xFFFF)] {
+        let mut chip8 = at(0x300, opcode);
+        chip8.set_v(0, 123);
+        chip8.set_v(1, 45);
+        chip8.set_ir(ir);
+        let err = chip8.step().unwrap_err();
+        assert!(
+            matches!(err, Chip8Errors::OutOfBounds { at: 0x300, address } if address >= 0x1000),
+            "{:04X} at I={:#x}",
+            opcode,
+            ir
+        );
+        assert_eq!(chip8.memory()[0xFFE..], [0, 0]);
+    }
+
+    // sprites too, but only the rows actually drawn count
+    let mut chip8 = at(0x300, 0xD015);
+    chip8.set_ir(0xFFE);
+    assert!(matches!(chip8.step().unwrap_err(), Chip8Errors::OutOfBounds { .. }));
+    let mut chip8 = at(0x300, 0xD015);
+    chip8.set_ir(0xFFE);
+    chip8.set_v(1, 30);
+    chip8.step().unwrap();
+}
+
+#[test]
+fn ram_accessors_past_the_end() {
+    let mut chip8 = at(0x300, 0x00E0);
+    assert_eq!(chip8.read_ram(0xFFF), Some(0));
+    assert_eq!(chip8.read_ram(0x1000), None);
+    chip8.write_ram(0xFFF, 1).unwrap();
+    let err = chip8.write_ram(0x1000, 1).unwrap_err();
+    assert!(matches!(
+        err,
+        Chip8Errors::OutOfBounds {
+            at: 0x300,
+            address: 0x1000
+        }
+    ));
+    assert_eq!(chip8.recent_writes().back(), Some(&0xFFF));
+}
+
+#[test]
+fn i_wraps_instead_of_overflowing() {
+    let mut chip8 = at(0x300, 0xF01E);
+    chip8.set_ir(0xFFFF);
+    chip8.set_v(0, 2);
+    chip8.step().unwrap();
+    assert_eq!(chip8.ir(), 1);
+}
+
+#[test]
+fn keys_use_the_low_nibble() {
+    let mut chip8 = at(0x300, 0xE09E);
+    chip8.set_v(0, 0x1A);
+    chip8.set_key(0xA, true);
+    chip8.step().unwrap();
+    assert_eq!(chip8.pc(), 0x304);
+}
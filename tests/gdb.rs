@@ -0,0 +1,193 @@
+#![allow(clippy::needless_return, clippy::unused_unit)]
+
+use krhip8::Chip8::{Chip8, Config};
+use krhip8::Gdb::GdbStub;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread::{self, JoinHandle};
+
+/// LD V0, 5; LD V1, 7; ADD V0, V1; JP 0x206
+const ROM: [u8; 8] = [0x60, 0x05, 0x61, 0x07, 0x80, 0x14, 0x12, 0x06];
+
+/// The RSP client side: sends packets and reads the replies.
+struct Client {
+    stream: TcpStream,
+}
+
+impl Client {
+    fn send(&mut self, data: &str) -> () {
+        let sum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        write!(self.stream, "${}#{:02x}", data, sum).unwrap();
+    }
+
+    /// The next reply, checking its checksum and acknowledging it.
+    fn reply(&mut self) -> String {
+        let mut byte = [0u8; 1];
+        loop {
+            self.stream.read_exact(&mut byte).unwrap();
+            if byte[0] == b'$' {
+                break;
+            }
+            assert_eq!(byte[0], b'+', "expected an ack");
+        }
+        let mut data = Vec::new();
+        loop {
+            self.stream.read_exact(&mut byte).unwrap();
+            if byte[0] == b'#' {
+                break;
+            }
+            data.push(byte[0]);
+        }
+        let mut checksum = [0u8; 2];
+        self.stream.read_exact(&mut checksum).unwrap();
+        let sum = data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        assert_eq!(std::str::from_utf8(&checksum).unwrap(), format!("{:02x}", sum));
+        self.stream.write_all(b"+").unwrap();
+        return String::from_utf8(data).unwrap();
+    }
+
+    fn ask(&mut self, data: &str) -> String {
+        self.send(data);
+        return self.reply();
+    }
+}
+
+/// Serves `rom` on a free port from another thread, the machine lives
+/// there. Returns the connected client and the server thread, which ends
+/// with `quit` set or not.
+fn start(rom: &'static [u8]) -> (Client, JoinHandle<bool>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = thread::spawn(move || {
+        let mut chip8 = Chip8::new(Config {
+            seed: Some(0),
+            ..Default::default()
+        });
+        chip8.load_rom(rom).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        GdbStub::new(stream).serve(&mut chip8).unwrap();
+        return chip8.quit;
+    });
+    let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    stream.set_nodelay(true).unwrap();
+    return (Client { stream }, server);
+}
+
+#[test]
+fn registers_step_and_breakpoints() {
+    let (mut gdb, server) = start(&ROM);
+    assert!(gdb.ask("qSupported:multiprocess+").contains("qXfer:features:read+"));
+    assert_eq!(gdb.ask("?"), "S05");
+    assert_eq!(gdb.ask("p11"), "0002");
+
+    assert_eq!(gdb.ask("s"), "S05");
+    assert_eq!(gdb.ask("p11"), "0202");
+    assert_eq!(gdb.ask("p0"), "05");
+
+    assert_eq!(gdb.ask("Z0,206,2"), "OK");
+    assert_eq!(gdb.ask("c"), "S05");
+    // v0 = 12, v1 = 7, I = 0, pc = 0x206, empty stack, timers at 0
+    let registers = gdb.ask("g");
+    assert_eq!(registers.len(), 2 * (16 + 2 + 2 + 3));
+    assert_eq!(&registers[..4], "0c07");
+    assert_eq!(&registers[32..], "00000602000000");
+
+    // continuing from the breakpoint runs the jump and stops there again
+    assert_eq!(gdb.ask("c"), "S05");
+    assert_eq!(gdb.ask("p11"), "0602");
+    assert_eq!(gdb.ask("z0,206,2"), "OK");
+
+    assert_eq!(gdb.ask("P0=ff"), "OK");
+    assert_eq!(gdb.ask("p0"), "ff");
+    assert_eq!(gdb.ask("P10=0003"), "OK");
+    assert_eq!(gdb.ask("p10"), "0003");
+    assert_eq!(gdb.ask("p30"), "E01");
+
+    assert_eq!(gdb.ask("D"), "OK");
+    assert!(!server.join().unwrap());
+}
+
+#[test]
+fn registers_are_little_endian() {
+    // without an architecture in target.xml GDB reads registers little
+    // endian, pc 0x0204 is 04 02 on the wire
+    let (mut gdb, server) = start(&ROM);
+    assert_eq!(gdb.ask("P11=0402"), "OK");
+    // ADD V0, V1 at 0x204 runs, not the empty memory at 0x402
+    assert_eq!(gdb.ask("s"), "S05");
+    assert_eq!(gdb.ask("p11"), "0602");
+    assert_eq!(gdb.ask("P10=bc0a"), "OK");
+    let registers = gdb.ask("g");
+    assert_eq!(&registers[32..40], "bc0a0602");
+    assert_eq!(gdb.ask("D"), "OK");
+    server.join().unwrap();
+}
+
+#[test]
+fn memory() {
+    let (mut gdb, server) = start(&ROM);
+    assert_eq!(gdb.ask("m200,4"), "60056107");
+    assert_eq!(gdb.ask("M300,3:abcdef"), "OK");
+    assert_eq!(gdb.ask("m300,3"), "abcdef");
+    assert_eq!(gdb.ask("mfff,1"), "00");
+    assert_eq!(gdb.ask("mfff,2"), "E01");
+    assert_eq!(gdb.ask("M300,2:ab"), "E01");
+
+    // code written through gdb runs: JP 0x200 over the jump to itself
+    assert_eq!(gdb.ask("M206,2:1200"), "OK");
+    assert_eq!(gdb.ask("Z0,200,2"), "OK");
+    assert_eq!(gdb.ask("c"), "S05");
+    assert_eq!(gdb.ask("p11"), "0002");
+
+    gdb.send("k");
+    assert!(server.join().unwrap());
+}
+
+#[test]
+fn target_description() {
+    let (mut gdb, server) = start(&ROM);
+    let mut xml = String::new();
+    loop {
+        let part = gdb.ask(&format!("qXfer:features:read:target.xml:{:x},40", xml.len()));
+        xml.push_str(&part[1..]);
+        if part.starts_with('l') {
+            break;
+        }
+        assert!(part.starts_with('m'));
+    }
+    assert!(xml.contains("<reg name=\"pc\" bitsize=\"16\" regnum=\"17\" type=\"code_ptr\"/>"));
+    assert!(xml.ends_with("</target>\n"));
+    assert_eq!(gdb.ask("vMustReplyEmpty"), "");
+    assert_eq!(gdb.ask("D"), "OK");
+    server.join().unwrap();
+}
+
+#[test]
+fn interrupt_and_errors() {
+    // CALL 0x200 forever: overflows the VIP's stack after 12 calls
+    let (mut gdb, server) = start(&[0x22, 0x00]);
+    assert_eq!(gdb.ask("c"), "S0b");
+    assert_eq!(gdb.ask("p12"), "0c");
+    assert_eq!(gdb.ask("D"), "OK");
+    server.join().unwrap();
+
+    // JP 0x200 forever, stopped with ctrl-c
+    let (mut gdb, server) = start(&[0x12, 0x00]);
+    gdb.send("c");
+    gdb.stream.write_all(&[0x03]).unwrap();
+    assert_eq!(gdb.reply(), "S02");
+    assert_eq!(gdb.ask("p11"), "0002");
+
+    // a packet sent while running is answered once it stops
+    gdb.send("c");
+    gdb.send("p11");
+    gdb.stream.write_all(&[0x03]).unwrap();
+    assert_eq!(gdb.reply(), "S02");
+    assert_eq!(gdb.reply(), "0002");
+
+    // undefined instructions stop with SIGILL
+    assert_eq!(gdb.ask("M200,2:ffff"), "OK");
+    assert_eq!(gdb.ask("s"), "S04");
+    assert_eq!(gdb.ask("D"), "OK");
+    server.join().unwrap();
+}
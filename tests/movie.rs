@@ -0,0 +1,97 @@
+#![allow(clippy::needless_return, clippy::unused_unit)]
+
+use krhip8::Movie::Movie;
+use krhip8::Quirks::Quirks;
+use krhip8::Random::Random;
+use krhip8::Rom::Platform;
+use std::path::PathBuf;
+
+/// A file in the temp directory for this test, removed when dropped.
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(name: &str) -> Self {
+        return TempFile(std::env::temp_dir().join(format!("krhip8-{}-{}", std::process::id(), name)));
+    }
+
+    fn path(&self) -> &str {
+        return self.0.to_str().unwrap();
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) -> () {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+#[test]
+fn save_and_load() {
+    let quirks = Quirks::for_platform("superchip").unwrap();
+    let mut movie = Movie::new(&Random::new(1234), Platform::Schip, quirks, 15);
+    let mut keys = [0; 16];
+    movie.record(&keys);
+    keys[5] = 1;
+    keys[0xA] = 1;
+    movie.record(&keys);
+    keys = [1; 16];
+    movie.record(&keys);
+
+    let file = TempFile::new("round-trip.movie");
+    movie.save(file.path()).unwrap();
+    let text = std::fs::read_to_string(file.path()).unwrap();
+    assert!(text.starts_with("krhip8 movie 1\nseed 1234\nplatform schip\n"));
+    assert!(text.ends_with("frames\n0000\n0420\nFFFF\n"));
+
+    let loaded = Movie::load(file.path()).unwrap();
+    assert_eq!(loaded, movie);
+    assert_eq!(loaded.keys(1).unwrap()[5], 1);
+    assert_eq!(loaded.keys(1).unwrap()[4], 0);
+    assert_eq!(loaded.keys(3), None);
+    assert_eq!(loaded.rng(None).unwrap().seed(), 1234);
+}
+
+#[test]
+fn vip_random() {
+    let page = [7; 256];
+    let movie = Movie::new(&Random::vip(99, &page), Platform::Chip8, Quirks::default(), 10);
+    let file = TempFile::new("vip.movie");
+    movie.save(file.path()).unwrap();
+    let text = std::fs::read_to_string(file.path()).unwrap();
+    assert!(text.starts_with("krhip8 movie 1\nseed 99\nrandom vip\n"));
+
+    let loaded = Movie::load(file.path()).unwrap();
+    assert!(loaded.vip_random);
+    // the page isn't in the movie, it has to be given again
+    assert!(loaded.rng(None).is_none());
+    let mut rng = loaded.rng(Some(&page)).unwrap();
+    assert_eq!(rng.draw(), Random::vip(99, &page).draw());
+}
+
+#[test]
+fn missing_headers() {
+    let file = TempFile::new("short.movie");
+    std::fs::write(file.path(), "krhip8 movie 1\nseed 7\nframes\n0001\n").unwrap();
+    let movie = Movie::load(file.path()).unwrap();
+    assert_eq!(movie.platform, Platform::Chip8);
+    assert_eq!(movie.quirks, Quirks::default());
+    assert_eq!(movie.cycles_per_frame, 10);
+    assert_eq!(movie.frames, [1]);
+}
+
+#[test]
+fn bad_movies() {
+    let file = TempFile::new("bad.movie");
+    for (text, err) in [
+        ("krhip8 film 1\n", "not a krhip8 movie"),
+        ("krhip8 movie 1\nseed many\n", "bad seed"),
+        ("krhip8 movie 1\nplatform gameboy\n", "unknown platform"),
+        ("krhip8 movie 1\nquirks wobble\n", "unknown quirk"),
+        ("krhip8 movie 1\nspeed 3\n", "unknown movie header `speed 3`"),
+        ("krhip8 movie 1\nrandom seeded\n", "unknown movie header `random seeded`"),
+        ("krhip8 movie 1\nframes\n00G0\n", "bad frame"),
+    ] {
+        std::fs::write(file.path(), text).unwrap();
+        assert_eq!(Movie::load(file.path()).unwrap_err().to_string(), err);
+    }
+}
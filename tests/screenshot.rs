@@ -0,0 +1,129 @@
+#![allow(clippy::needless_return, clippy::unused_unit)]
+
+use krhip8::Screenshot::{Frame, Palette, Screenshot};
+
+/// A 128x64 frame using both planes: the first plane in the left half, the
+/// second in the top half, so each quarter is a different combination.
+fn two_planes() -> Frame {
+    let (width, height) = (128, 64);
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            pixels.push((x < width / 2) as u8 | ((y < height / 2) as u8) << 1);
+        }
+    }
+    return Frame { width, height, pixels };
+}
+
+/// The chunks of a PNG, kind and data, after checking the signature and
+/// every CRC is there.
+fn chunks(png: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    let mut chunks = Vec::new();
+    let mut at = 8;
+    while at < png.len() {
+        let len = u32::from_be_bytes(png[at..at + 4].try_into().unwrap()) as usize;
+        let kind = png[at + 4..at + 8].try_into().unwrap();
+        chunks.push((kind, png[at + 8..at + 8 + len].to_vec()));
+        at += 12 + len;
+    }
+    assert_eq!(at, png.len());
+    return chunks;
+}
+
+/// Undoes the stored deflate blocks `write_png` writes.
+fn inflate_stored(zlib: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut at = 2;
+    loop {
+        let last = zlib[at] & 1 == 1;
+        assert_eq!(zlib[at] & 6, 0, "only stored blocks");
+        let len = u16::from_le_bytes([zlib[at + 1], zlib[at + 2]]) as usize;
+        out.extend_from_slice(&zlib[at + 5..at + 5 + len]);
+        at += 5 + len;
+        if last {
+            break;
+        }
+    }
+    assert_eq!(at + 4, zlib.len());
+    return out;
+}
+
+#[test]
+fn png_of_two_planes() {
+    let frame = two_planes();
+    let screenshot = Screenshot {
+        scale: 2,
+        palette: Palette::parse("000000,FF0000,00FF00,0000FF").unwrap(),
+    };
+    let mut png = Vec::new();
+    screenshot.write_png(&frame, &mut png).unwrap();
+
+    let chunks = chunks(&png);
+    let kinds: Vec<&[u8; 4]> = chunks.iter().map(|(kind, _)| kind).collect();
+    assert_eq!(kinds, [b"IHDR", b"PLTE", b"IDAT", b"IEND"]);
+
+    let ihdr = &chunks[0].1;
+    assert_eq!(u32::from_be_bytes(ihdr[0..4].try_into().unwrap()), 256);
+    assert_eq!(u32::from_be_bytes(ihdr[4..8].try_into().unwrap()), 128);
+    assert_eq!(&ihdr[8..], [8, 3, 0, 0, 0]);
+    assert_eq!(chunks[1].1, [0, 0, 0, 0xFF, 0, 0, 0, 0xFF, 0, 0, 0, 0xFF]);
+
+    let raw = inflate_stored(&chunks[2].1);
+    assert_eq!(raw.len(), (256 + 1) * 128);
+    for (y, row) in raw.chunks(257).enumerate() {
+        assert_eq!(row[0], 0, "no filter on row {}", y);
+        for (x, index) in row[1..].iter().enumerate() {
+            assert_eq!(*index, frame.get(x / 2, y / 2), "pixel {},{}", x, y);
+        }
+    }
+    // all four colours show up, one per quarter
+    assert_eq!(raw[1], 3);
+    assert_eq!(raw[1 + 255], 2);
+    assert_eq!(raw[257 * 127 + 1], 1);
+    assert_eq!(raw[257 * 127 + 256], 0);
+}
+
+#[test]
+fn pbm_of_two_planes() {
+    let frame = two_planes();
+    let screenshot = Screenshot {
+        scale: 2,
+        ..Default::default()
+    };
+    let mut pbm = Vec::new();
+    screenshot.write_pbm(&frame, &mut pbm).unwrap();
+
+    let header = b"P4\n256 128\n";
+    assert_eq!(&pbm[..header.len()], header);
+    let bits = &pbm[header.len()..];
+    assert_eq!(bits.len(), 256 / 8 * 128);
+    // any plane is black: everything but the bottom right quarter
+    for (y, row) in bits.chunks(32).enumerate() {
+        let expected = if y < 64 {
+            [0xFF; 32]
+        } else {
+            [[0xFF; 16], [0; 16]].concat().try_into().unwrap()
+        };
+        assert_eq!(row, expected, "row {}", y);
+    }
+}
+
+#[test]
+fn palette_sizes() {
+    let colours = |count: usize| vec!["123456"; count].join(",");
+    assert_eq!(Palette::parse(&colours(1)), None);
+    assert_eq!(Palette::parse(&colours(256)).unwrap().0.len(), 256);
+    assert_eq!(Palette::parse(&colours(257)), None);
+
+    // a full palette, and a longer one built by hand, still give a PNG
+    for count in [256, 300] {
+        let screenshot = Screenshot {
+            scale: 1,
+            palette: Palette(vec![[1, 2, 3]; count]),
+        };
+        let mut png = Vec::new();
+        screenshot.write_png(&two_planes(), &mut png).unwrap();
+        assert_eq!(chunks(&png)[1].1.len(), 256 * 3);
+    }
+}
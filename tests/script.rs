@@ -0,0 +1,93 @@
+#![allow(clippy::needless_return, clippy::unused_unit)]
+
+use krhip8::Chip8::{Chip8, Config};
+use krhip8::Screenshot::Screenshot;
+use krhip8::Script::{Action, Command, InputScript, ScriptErrors};
+
+/// The line and reason of the error parsing `text` gives.
+fn parse_error(text: &str) -> (usize, &'static str) {
+    return match InputScript::parse(text) {
+        Err(ScriptErrors::Parse { line, reason, .. }) => (line, reason),
+        other => panic!("`{}` gave {:?}", text, other),
+    };
+}
+
+#[test]
+fn commands_in_frame_order() {
+    let script = InputScript::parse(
+        "# a comment line\n\
+         frame 30: press 5; frame 35: release 5 # trailing comment\n\
+         \n\
+         frame 10: press a\n\
+         frame 30: assert-pixel 63,31 off\n",
+    )
+    .unwrap();
+    let commands = [
+        Command {
+            frame: 10,
+            action: Action::Press(0xA),
+        },
+        Command {
+            frame: 30,
+            action: Action::Press(5),
+        },
+        Command {
+            frame: 30,
+            action: Action::AssertPixel {
+                x: 63,
+                y: 31,
+                on: false,
+            },
+        },
+        Command {
+            frame: 35,
+            action: Action::Release(5),
+        },
+    ];
+    assert_eq!(script.commands(), commands);
+    assert_eq!(script.last_frame(), 35);
+}
+
+#[test]
+fn errors_name_the_line() {
+    assert_eq!(parse_error("press 5"), (1, "expected `frame N: action`"));
+    assert_eq!(
+        parse_error("frame 1: press 5\n\n# fine\nframe 2 press 5"),
+        (4, "missing `:`")
+    );
+    assert_eq!(
+        parse_error("frame 1: press 5; frame x: press 5"),
+        (1, "bad frame number")
+    );
+    assert_eq!(parse_error("\nframe 1: press 10"), (2, "keys go from 0 to F"));
+    assert_eq!(parse_error("\n\nframe 1: jump"), (3, "unknown action"));
+    assert_eq!(
+        parse_error("frame 1: assert-pixel 64,0 on"),
+        (1, "pixel outside the screen")
+    );
+    assert_eq!(parse_error("frame 1: assert-pixel 1;2 on"), (1, "unknown action"));
+    assert_eq!(
+        parse_error("frame 1: assert-pixel 1,2 lit"),
+        (1, "pixels are `on` or `off`")
+    );
+
+    let err = InputScript::parse("frame 1: press 5\nframe 2: hold 5").unwrap_err();
+    assert_eq!(err.to_string(), "line 2: `frame 2: hold 5`: unknown action");
+}
+
+#[test]
+fn apply_presses_and_asserts() {
+    let mut chip8 = Chip8::new(Config {
+        seed: Some(0),
+        ..Default::default()
+    });
+    chip8.load_rom(&[0x12, 0x00]).unwrap();
+    let script = InputScript::parse("frame 0: press F; frame 1: release F; frame 1: assert-pixel 0,0 on").unwrap();
+    let screenshot = Screenshot::default();
+
+    script.apply(0, &mut chip8, &screenshot).unwrap();
+    assert_eq!(chip8.keys[0xF], 1);
+    let err = script.apply(1, &mut chip8, &screenshot).unwrap_err();
+    assert_eq!(chip8.keys[0xF], 0);
+    assert_eq!(err.to_string(), "frame 1: expected pixel 0,0 to be on");
+}
@@ -0,0 +1,84 @@
+#![allow(clippy::needless_return, clippy::unused_unit)]
+
+use krhip8::Tracer::{diff_traces, Divergence, TraceEntry};
+
+const OURS: &str = "\
+# krhip8 trace
+PC=0200 OP=6A02 I=0000 V0=00 V1=00 VA=02 SP=0 DT=00
+PC=0202 OP=A300 I=0300 V0=00 V1=00 VA=02 SP=0 DT=00
+PC=0204 OP=7101 I=0300 V0=00 V1=01 VA=02 SP=0 DT=00
+";
+
+fn diff(ours: &str, reference: &str) -> Option<Divergence> {
+    return diff_traces(ours.as_bytes(), reference.as_bytes()).unwrap();
+}
+
+#[test]
+fn same_traces() {
+    assert_eq!(diff(OURS, OURS), None);
+    // only the fields the reference has are compared, in any order and case
+    let reference = "pc=0x200 op=6a02 va=02\n\n# a comment\nop=A300 pc=0202 i=300\nPC=0204\n";
+    assert_eq!(diff(OURS, reference), None);
+}
+
+#[test]
+fn first_divergence() {
+    let reference = "\
+PC=0200 OP=6A02 VA=02
+PC=0202 OP=A300 I=0302 V1=00
+PC=0204 OP=7101 I=0300 V1=02
+";
+    let divergence = diff(OURS, reference).unwrap();
+    assert_eq!(divergence.instruction, 2);
+    assert_eq!(divergence.fields, ["I"]);
+    assert_eq!(
+        divergence.ours.as_deref(),
+        Some("PC=0202 OP=A300 I=0300 V0=00 V1=00 VA=02 SP=0 DT=00")
+    );
+    assert_eq!(divergence.reference, "PC=0202 OP=A300 I=0302 V1=00");
+    assert_eq!(
+        divergence.to_string(),
+        "traces diverge at instruction 2\n\
+         \x20 instruction: LD I, 0x300\n\
+         \x20 differs in:  I\n\
+         \x20 ours:        PC=0202 OP=A300 I=0300 V0=00 V1=00 VA=02 SP=0 DT=00\n\
+         \x20 reference:   PC=0202 OP=A300 I=0302 V1=00"
+    );
+
+    let reference = "PC=0200\nPC=0202\nPC=0204 V1=02 DT=01 SP=1\n";
+    let divergence = diff(OURS, reference).unwrap();
+    assert_eq!(divergence.instruction, 3);
+    assert_eq!(divergence.fields, ["V1", "DT", "SP"]);
+}
+
+#[test]
+fn both_sides_skip_blank_lines() {
+    let ours = format!("\n{}", OURS.replace('\n', "\n\n"));
+    assert_eq!(diff(&ours, "PC=0200\n\nPC=0202\nPC=0204\n"), None);
+    let divergence = diff(&ours, "# header\n\nPC=0200\nPC=0202\nPC=0206\n").unwrap();
+    assert_eq!(divergence.instruction, 3);
+}
+
+#[test]
+fn our_trace_ends_first() {
+    let reference = format!("{}PC=0206 OP=1206\n", OURS);
+    let divergence = diff(OURS, &reference).unwrap();
+    assert_eq!(divergence.instruction, 4);
+    assert_eq!(divergence.ours, None);
+    assert!(divergence.to_string().contains("<trace ended>"));
+}
+
+#[test]
+fn common_lines_diff_clean() {
+    // the lines trace-diff writes for our side line up with a reference
+    let mut entry = TraceEntry::new(0x200, 0x6A02, &[0; 16], &[0; 16], 0);
+    entry.v[0xA] = 2;
+    entry.sp = 1;
+    entry.delay_timer = 0x3C;
+    let mut ours = Vec::new();
+    entry.write_common(&mut ours).unwrap();
+    let ours = String::from_utf8(ours).unwrap();
+    assert!(ours.starts_with("PC=0200 OP=6A02 I=0000 V0=00"));
+    assert_eq!(diff(&ours, "PC=0200 VA=02 SP=1 DT=3C"), None);
+    assert_eq!(diff(&ours, "PC=0200 VA=03").unwrap().fields, ["VA"]);
+}